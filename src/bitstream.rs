@@ -0,0 +1,145 @@
+//! Bit-level reading and writing of the frame format.
+
+use crate::error::{Error, ErrorKind, Result};
+
+/// Reads big-endian bit fields from a slice of bytes.
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    /// The position in bits.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader { data, pos: 0 }
+    }
+
+    /// Returns the number of whole bytes consumed, rounding up any partially read byte.
+    pub(crate) fn byte_position(&self) -> usize {
+        self.pos.div_ceil(8)
+    }
+
+    /// Skips to the start of the next byte.
+    pub(crate) fn align(&mut self) {
+        self.pos = self.byte_position() * 8;
+    }
+
+    /// Reads an unsigned value of up to 64 bits.
+    pub(crate) fn read_bits(&mut self, n: u32) -> Result<u64> {
+        if self.pos + n as usize > self.data.len() * 8 {
+            return Err(truncated());
+        }
+
+        let mut value = 0u64;
+        let mut remaining = n;
+        while remaining > 0 {
+            let byte = self.data[self.pos / 8] as u32;
+            let available = 8 - (self.pos % 8) as u32;
+            let take = available.min(remaining);
+            let bits = (byte >> (available - take)) & ((1 << take) - 1);
+            value = (value << take) | bits as u64;
+            remaining -= take;
+            self.pos += take as usize;
+        }
+
+        Ok(value)
+    }
+
+    /// Reads a two's complement signed value of up to 64 bits.
+    pub(crate) fn read_signed(&mut self, n: u32) -> Result<i64> {
+        if n == 0 {
+            return Ok(0);
+        }
+
+        let value = self.read_bits(n)?;
+        let shift = 64 - n;
+        Ok(((value << shift) as i64) >> shift)
+    }
+
+    /// Reads a unary coded value, i.e. the number of zero bits before the next one bit.
+    pub(crate) fn read_unary(&mut self) -> Result<u32> {
+        let mut count = 0;
+        loop {
+            let index = self.pos / 8;
+            if index >= self.data.len() {
+                return Err(truncated());
+            }
+
+            let bit_offset = (self.pos % 8) as u32;
+            let byte = self.data[index] << bit_offset;
+            if byte == 0 {
+                count += 8 - bit_offset;
+                self.pos += (8 - bit_offset) as usize;
+            } else {
+                let zeros = byte.leading_zeros();
+                count += zeros;
+                self.pos += zeros as usize + 1;
+                return Ok(count);
+            }
+        }
+    }
+}
+
+fn truncated() -> Error {
+    Error::new(ErrorKind::InvalidInput, "frame is truncated")
+}
+
+/// Writes big-endian bit fields to a vector of bytes.
+pub(crate) struct BitWriter {
+    bytes: Vec<u8>,
+    /// Bits which have not yet been written to `bytes`, in the low `num_bits` bits.
+    cache: u64,
+    num_bits: u32,
+}
+
+impl BitWriter {
+    pub(crate) fn new() -> BitWriter {
+        BitWriter {
+            bytes: Vec::new(),
+            cache: 0,
+            num_bits: 0,
+        }
+    }
+
+    /// Writes the low `n` bits of the value. At most 56 bits may be written at a time.
+    pub(crate) fn write_bits(&mut self, value: u64, n: u32) {
+        if n == 0 {
+            return;
+        }
+
+        self.cache = (self.cache << n) | (value & (u64::MAX >> (64 - n)));
+        self.num_bits += n;
+        while self.num_bits >= 8 {
+            self.num_bits -= 8;
+            self.bytes.push((self.cache >> self.num_bits) as u8);
+        }
+        self.cache &= (1 << self.num_bits) - 1;
+    }
+
+    /// Writes a two's complement signed value using `n` bits.
+    pub(crate) fn write_signed(&mut self, value: i64, n: u32) {
+        self.write_bits(value as u64, n);
+    }
+
+    /// Writes a unary coded value, i.e. `value` zero bits followed by a one bit.
+    pub(crate) fn write_unary(&mut self, mut value: u32) {
+        while value >= 32 {
+            self.write_bits(0, 32);
+            value -= 32;
+        }
+        self.write_bits(1, value + 1);
+    }
+
+    /// Pads the output with zero bits up to the start of the next byte.
+    pub(crate) fn align(&mut self) {
+        if self.num_bits > 0 {
+            self.write_bits(0, 8 - self.num_bits);
+        }
+    }
+
+    /// Returns the written bytes, padding the last byte with zero bits.
+    pub(crate) fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
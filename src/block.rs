@@ -1,11 +1,15 @@
 use crate::error::{Error, ErrorKind, Result};
+use crate::frame::Frames;
 
 use byteorder::{ReadBytesExt, WriteBytesExt, BE};
 
+use std::any::Any;
 use std::collections::HashMap;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
 use std::io::{Read, Write};
 use std::iter::repeat;
+use std::sync::Arc;
 
 // BlockType {{{
 /// Types of blocks. Used primarily to map blocks to block identifiers when reading and writing.
@@ -53,8 +57,11 @@ impl BlockType {
 // }}}
 
 /// The parsed content of a metadata block.
+///
+/// New variants may be added in minor releases, so matches on a `Block` need a wildcard arm.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Block {
     /// A value containing a parsed streaminfo block.
     StreamInfo(StreamInfo),
@@ -72,15 +79,48 @@ pub enum Block {
     VorbisComment(VorbisComment),
     /// An value containing the bytes of an unknown block.
     Unknown((u8, Vec<u8>)),
+    /// A value containing a block parsed by a `BlockCodec`, see `BlockRegistry`.
+    ///
+    /// This variant can not be serialized with serde, since its contents are only known to the
+    /// codec. Serializing a block or tag holding it returns an error, so custom blocks should be
+    /// removed or written as `Block::Unknown` with the bytes from `BlockCodec::encode` first.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom((u8, Box<dyn CustomBlock>)),
 }
 
 impl Block {
     /// Attempts to read a block from the reader. Returns a tuple containing a boolean indicating
     /// if the block was the last block, the length of the block in bytes, and the new `Block`.
     pub fn read_from(reader: &mut dyn Read) -> Result<(bool, u32, Block)> {
+        Block::read_from_with_registry(reader, &BlockRegistry::new())
+    }
+
+    /// Attempts to read a block from the reader, parsing blocks of the types registered in the
+    /// registry with their codec. See `Block::read_from`.
+    pub fn read_from_with_registry(
+        reader: &mut dyn Read,
+        registry: &BlockRegistry,
+    ) -> Result<(bool, u32, Block)> {
+        Block::read(reader, registry, None)
+    }
+
+    /// Attempts to read a block, leaving out the vorbis comments with an invalid field name or
+    /// without a `=` separator if `invalid` is given and adding them to it. See
+    /// `VorbisComment::from_bytes_lenient`.
+    pub(crate) fn read(
+        reader: &mut dyn Read,
+        registry: &BlockRegistry,
+        invalid: Option<&mut Vec<String>>,
+    ) -> Result<(bool, u32, Block)> {
         let byte = reader.read_u8()?;
         let is_last = (byte & 0x80) != 0;
         let blocktype_byte = byte & 0x7F;
+        if blocktype_byte == INVALID_BLOCK_TYPE {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "block type 127 is invalid",
+            ));
+        }
         let blocktype = BlockType::from_u8(blocktype_byte);
         let length = reader.read_uint::<BE>(3)? as u32;
 
@@ -92,10 +132,20 @@ impl Block {
             BlockType::Padding => Block::Padding(length),
             BlockType::Application => Block::Application(Application::from_bytes(&data[..])),
             BlockType::SeekTable => Block::SeekTable(SeekTable::from_bytes(&data[..])),
-            BlockType::VorbisComment => Block::VorbisComment(VorbisComment::from_bytes(&data[..])?),
+            BlockType::VorbisComment => match invalid {
+                Some(invalid) => {
+                    let (vorbis, dropped) = VorbisComment::from_bytes_lenient(&data[..])?;
+                    invalid.extend(dropped);
+                    Block::VorbisComment(vorbis)
+                }
+                None => Block::VorbisComment(VorbisComment::from_bytes(&data[..])?),
+            },
             BlockType::Picture => Block::Picture(Picture::from_bytes(&data[..])?),
             BlockType::CueSheet => Block::CueSheet(CueSheet::from_bytes(&data[..])?),
-            BlockType::Unknown(_) => Block::Unknown((blocktype_byte, data)),
+            BlockType::Unknown(_) => match registry.get(blocktype_byte) {
+                Some(codec) => Block::Custom((blocktype_byte, codec.decode(&data[..])?)),
+                None => Block::Unknown((blocktype_byte, data)),
+            },
         };
 
         Ok((is_last, length + 4, block))
@@ -103,6 +153,25 @@ impl Block {
 
     /// Attemps to write the block to the writer. Returns the length of the block in bytes.
     pub fn write_to(&self, is_last: bool, writer: &mut dyn Write) -> Result<u32> {
+        self.write_to_with_registry(is_last, writer, &BlockRegistry::new())
+    }
+
+    /// Attemps to write the block to the writer, encoding custom blocks with the codecs of the
+    /// registry. An `Error::InvalidInput` will be returned if there is no codec for a custom
+    /// block. See `Block::write_to`.
+    pub fn write_to_with_registry(
+        &self,
+        is_last: bool,
+        writer: &mut dyn Write,
+        registry: &BlockRegistry,
+    ) -> Result<u32> {
+        if self.block_type().to_u8() == INVALID_BLOCK_TYPE {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "block type 127 is invalid",
+            ));
+        }
+
         let (content_len, contents) = match *self {
             Block::StreamInfo(ref streaminfo) => {
                 let bytes = streaminfo.to_bytes();
@@ -126,10 +195,22 @@ impl Block {
                 (bytes.len() as u32, Some(bytes))
             }
             Block::VorbisComment(ref vorbis) => {
-                let bytes = vorbis.to_bytes();
+                let bytes = vorbis.to_bytes()?;
                 (bytes.len() as u32, Some(bytes))
             }
             Block::Unknown((_, ref bytes)) => (bytes.len() as u32, Some(bytes.clone())),
+            Block::Custom((blocktype, ref block)) => {
+                let bytes = match registry.get(blocktype) {
+                    Some(codec) => codec.encode(&**block)?,
+                    None => {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            "no codec is registered for the custom block type",
+                        ));
+                    }
+                };
+                (bytes.len() as u32, Some(bytes))
+            }
         };
 
         let mut byte: u8 = 0;
@@ -172,9 +253,152 @@ impl Block {
             Block::SeekTable(_) => BlockType::SeekTable,
             Block::VorbisComment(_) => BlockType::VorbisComment,
             Block::Unknown((blocktype, _)) => BlockType::Unknown(blocktype),
+            Block::Custom((blocktype, _)) => BlockType::Unknown(blocktype),
+        }
+    }
+}
+
+// BlockCodec {{{
+/// The block type which is invalid to avoid confusion with a frame sync code.
+const INVALID_BLOCK_TYPE: u8 = 127;
+
+/// The parsed content of a block of a custom type. This is implemented for all types which are
+/// `Clone`, `Debug`, `Send` and `Sync`.
+pub trait CustomBlock: Any + fmt::Debug + Send + Sync {
+    /// Returns a boxed copy of the block.
+    fn clone_box(&self) -> Box<dyn CustomBlock>;
+
+    /// Returns the block as `Any`, which allows it to be downcast to its concrete type.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any + Clone + fmt::Debug + Send + Sync> CustomBlock for T {
+    fn clone_box(&self) -> Box<dyn CustomBlock> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl dyn CustomBlock {
+    /// Returns a reference to the block if it is of type `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+}
+
+impl Clone for Box<dyn CustomBlock> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Parses and serializes the contents of blocks of a custom type.
+///
+/// # Example
+/// ```
+/// use metaflac::block::{BlockCodec, BlockRegistry, CustomBlock};
+/// use metaflac::{Block, Result, Tag};
+/// use std::sync::Arc;
+///
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct Loudness(f32);
+///
+/// struct LoudnessCodec;
+///
+/// impl BlockCodec for LoudnessCodec {
+///     fn block_type(&self) -> u8 {
+///         100
+///     }
+///
+///     fn decode(&self, data: &[u8]) -> Result<Box<dyn CustomBlock>> {
+///         let mut bytes = [0; 4];
+///         bytes.copy_from_slice(&data[..4]);
+///         Ok(Box::new(Loudness(f32::from_be_bytes(bytes))))
+///     }
+///
+///     fn encode(&self, block: &dyn CustomBlock) -> Result<Vec<u8>> {
+///         let loudness = block.downcast_ref::<Loudness>().unwrap();
+///         Ok(loudness.0.to_be_bytes().to_vec())
+///     }
+/// }
+///
+/// let mut registry = BlockRegistry::new();
+/// registry.register(Arc::new(LoudnessCodec)).unwrap();
+///
+/// let mut tag = Tag::new();
+/// tag.push_block(Block::Custom((100, Box::new(Loudness(-14.5)))));
+/// let mut bytes = Vec::new();
+/// tag.write_to_with_registry(&mut bytes, &registry).unwrap();
+///
+/// let tag = Tag::read_from_with_registry(&mut &bytes[..], &registry).unwrap();
+/// match tag.blocks().next() {
+///     Some(Block::Custom((100, block))) => {
+///         assert_eq!(block.downcast_ref::<Loudness>(), Some(&Loudness(-14.5)));
+///     }
+///     block => panic!("unexpected block {:?}", block),
+/// }
+///
+/// // without the codec the block is read as an unknown block
+/// let tag = Tag::read_from(&mut &bytes[..]).unwrap();
+/// assert!(matches!(tag.blocks().next(), Some(Block::Unknown((100, _)))));
+/// ```
+pub trait BlockCodec: Send + Sync {
+    /// The block type handled by the codec, between 7 and 126.
+    fn block_type(&self) -> u8;
+
+    /// Attempts to parse the contents of a block.
+    fn decode(&self, data: &[u8]) -> Result<Box<dyn CustomBlock>>;
+
+    /// Attempts to serialize a block which was returned by `decode` or created by the user.
+    fn encode(&self, block: &dyn CustomBlock) -> Result<Vec<u8>>;
+}
+
+/// A set of codecs for custom block types, which is passed to the reader and writer.
+#[derive(Clone, Default)]
+pub struct BlockRegistry {
+    codecs: HashMap<u8, Arc<dyn BlockCodec>>,
+}
+
+impl BlockRegistry {
+    /// Returns a new registry without any codecs.
+    pub fn new() -> BlockRegistry {
+        BlockRegistry {
+            codecs: HashMap::new(),
         }
     }
+
+    /// Attempts to register a codec, replacing any codec for the same block type. An
+    /// `Error::InvalidInput` will be returned if the block type is a standard or invalid type.
+    pub fn register(&mut self, codec: Arc<dyn BlockCodec>) -> Result<()> {
+        let blocktype = codec.block_type();
+        if blocktype <= 6 || blocktype >= INVALID_BLOCK_TYPE {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "codecs can only be registered for block types 7 to 126",
+            ));
+        }
+
+        self.codecs.insert(blocktype, codec);
+        Ok(())
+    }
+
+    /// Returns the codec for the block type, if any.
+    pub fn get(&self, blocktype: u8) -> Option<&dyn BlockCodec> {
+        self.codecs.get(&blocktype).map(|codec| &**codec)
+    }
+}
+
+impl fmt::Debug for BlockRegistry {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        let mut types: Vec<u8> = self.codecs.keys().cloned().collect();
+        types.sort_unstable();
+        write!(out, "BlockRegistry {{ block_types: {:?} }}", types)
+    }
 }
+//}}}
 
 // StreamInfo {{{
 /// A structure representing a STREAMINFO block.
@@ -305,15 +529,257 @@ impl Default for StreamInfo {
         Self::new()
     }
 }
+
+/// A discrepancy between a `StreamInfo` block and the audio frames of the stream. Frame offsets
+/// are in bytes from the first byte of the first frame header.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StreamInfoMismatch {
+    /// The audio data does not contain any frames.
+    NoFrames,
+    /// A frame header declares a different sample rate.
+    SampleRate {
+        /// The offset of the frame.
+        offset: u64,
+        /// The sample rate in the streaminfo block.
+        expected: u32,
+        /// The sample rate in the frame header.
+        found: u32,
+    },
+    /// A frame header declares a different number of channels.
+    NumChannels {
+        /// The offset of the frame.
+        offset: u64,
+        /// The number of channels in the streaminfo block.
+        expected: u8,
+        /// The number of channels in the frame header.
+        found: u8,
+    },
+    /// A frame header declares a different number of bits per sample.
+    BitsPerSample {
+        /// The offset of the frame.
+        offset: u64,
+        /// The bits per sample in the streaminfo block.
+        expected: u8,
+        /// The bits per sample in the frame header.
+        found: u8,
+    },
+    /// A frame other than the last frame holds fewer samples than the minimum block size.
+    BlockSizeBelowMinimum {
+        /// The offset of the frame.
+        offset: u64,
+        /// The minimum block size in the streaminfo block.
+        min: u16,
+        /// The block size of the frame.
+        found: u32,
+    },
+    /// A frame holds more samples than the maximum block size.
+    BlockSizeAboveMaximum {
+        /// The offset of the frame.
+        offset: u64,
+        /// The maximum block size in the streaminfo block.
+        max: u16,
+        /// The block size of the frame.
+        found: u32,
+    },
+    /// A frame is smaller than the minimum frame size.
+    FrameSizeBelowMinimum {
+        /// The offset of the frame.
+        offset: u64,
+        /// The minimum frame size in the streaminfo block.
+        min: u32,
+        /// The size of the frame in bytes.
+        found: u64,
+    },
+    /// A frame is larger than the maximum frame size.
+    FrameSizeAboveMaximum {
+        /// The offset of the frame.
+        offset: u64,
+        /// The maximum frame size in the streaminfo block.
+        max: u32,
+        /// The size of the frame in bytes.
+        found: u64,
+    },
+    /// The frames hold a different number of samples than the total samples.
+    TotalSamples {
+        /// The total samples in the streaminfo block.
+        expected: u64,
+        /// The sum of the block sizes of all frames.
+        found: u64,
+    },
+}
+
+impl StreamInfo {
+    /// Cross-checks the streaminfo block against the headers of the frames in the audio data
+    /// (the contents of a FLAC stream following the metadata, as returned by
+    /// `Tag::skip_metadata`). Returns every discrepancy found, or an empty vector if the
+    /// streaminfo block agrees with the frames. Frame sizes and the total samples are only
+    /// checked when they are known, i.e. nonzero.
+    pub fn check_frames(&self, audio: &[u8]) -> Vec<StreamInfoMismatch> {
+        let mut mismatches = Vec::new();
+        let mut total_samples = 0;
+
+        let mut frames = Frames::new(audio).peekable();
+        if frames.peek().is_none() {
+            mismatches.push(StreamInfoMismatch::NoFrames);
+            return mismatches;
+        }
+
+        while let Some(frame) = frames.next() {
+            let offset = frame.offset;
+            let header = frame.header;
+            total_samples += header.block_size as u64;
+
+            if let Some(found) = header.sample_rate {
+                if found != self.sample_rate {
+                    mismatches.push(StreamInfoMismatch::SampleRate {
+                        offset,
+                        expected: self.sample_rate,
+                        found,
+                    });
+                }
+            }
+
+            if header.num_channels() != self.num_channels {
+                mismatches.push(StreamInfoMismatch::NumChannels {
+                    offset,
+                    expected: self.num_channels,
+                    found: header.num_channels(),
+                });
+            }
+
+            if let Some(found) = header.bits_per_sample {
+                if found != self.bits_per_sample {
+                    mismatches.push(StreamInfoMismatch::BitsPerSample {
+                        offset,
+                        expected: self.bits_per_sample,
+                        found,
+                    });
+                }
+            }
+
+            // the last frame is allowed to be shorter than the minimum block size
+            if frames.peek().is_some() && header.block_size < self.min_block_size as u32 {
+                mismatches.push(StreamInfoMismatch::BlockSizeBelowMinimum {
+                    offset,
+                    min: self.min_block_size,
+                    found: header.block_size,
+                });
+            }
+
+            if header.block_size > self.max_block_size as u32 {
+                mismatches.push(StreamInfoMismatch::BlockSizeAboveMaximum {
+                    offset,
+                    max: self.max_block_size,
+                    found: header.block_size,
+                });
+            }
+
+            if self.min_frame_size != 0 && frame.length < self.min_frame_size as u64 {
+                mismatches.push(StreamInfoMismatch::FrameSizeBelowMinimum {
+                    offset,
+                    min: self.min_frame_size,
+                    found: frame.length,
+                });
+            }
+
+            if self.max_frame_size != 0 && frame.length > self.max_frame_size as u64 {
+                mismatches.push(StreamInfoMismatch::FrameSizeAboveMaximum {
+                    offset,
+                    max: self.max_frame_size,
+                    found: frame.length,
+                });
+            }
+        }
+
+        if self.total_samples != 0 && self.total_samples != total_samples {
+            mismatches.push(StreamInfoMismatch::TotalSamples {
+                expected: self.total_samples,
+                found: total_samples,
+            });
+        }
+
+        mismatches
+    }
+}
 //}}}
 
 // Application {{{
+/// Application IDs registered with xiph.org and the names of their applications.
+pub const REGISTERED_APPLICATIONS: &[([u8; 4], &str)] = &[
+    (*b"ATCH", "FlacFile"),
+    (*b"BSOL", "beSolo"),
+    (*b"BUGS", "Bugs Player"),
+    (*b"Cues", "GoldWave cue points"),
+    (*b"Fica", "CUE Splitter"),
+    (*b"Ftol", "flac-tools"),
+    (*b"MOTB", "MOTB MetaCzar"),
+    (*b"MPSE", "MP3 Stream Editor"),
+    (*b"MuML", "MusicML: Music Metadata Language"),
+    (*b"RIFF", "Sound Devices RIFF chunk storage"),
+    (*b"SFFL", "Sound Font FLAC"),
+    (*b"SONY", "Sony Creative Software"),
+    (*b"SQEZ", "flacsqueeze"),
+    (*b"TtWv", "TwistedWave"),
+    (*b"UITS", "UITS Embedding tools"),
+    (*b"aiff", "FLAC AIFF chunk storage"),
+    (*b"imag", "flac-image"),
+    (*b"peem", "Parseable Embedded Extensible Metadata"),
+    (*b"qfst", "QFLAC Studio"),
+    (*b"riff", "FLAC RIFF chunk storage"),
+    (*b"tune", "TagTuner"),
+    (*b"w64 ", "FLAC Wave64 chunk storage"),
+    (*b"xbat", "XBAT"),
+    (*b"xmcd", "xmcd"),
+];
+
+/// The payload of application blocks with a specific id. Implementing this trait allows the
+/// payload to be read from and written to a `Tag` as a typed value, see
+/// `Tag::get_application_payload`.
+///
+/// # Example
+/// ```
+/// use metaflac::block::ApplicationPayload;
+/// use metaflac::{Error, ErrorKind, Tag};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Rating(u8);
+///
+/// impl ApplicationPayload for Rating {
+///     const ID: [u8; 4] = *b"rate";
+///
+///     fn from_bytes(data: &[u8]) -> metaflac::Result<Rating> {
+///         match data {
+///             [rating] => Ok(Rating(*rating)),
+///             _ => Err(Error::new(ErrorKind::InvalidInput, "invalid rating")),
+///         }
+///     }
+///
+///     fn to_bytes(&self) -> Vec<u8> {
+///         vec![self.0]
+///     }
+/// }
+///
+/// let mut tag = Tag::new();
+/// tag.set_application_payload(&Rating(4));
+/// assert_eq!(tag.get_application_payload::<Rating>().unwrap().unwrap(), Rating(4));
+/// ```
+pub trait ApplicationPayload: Sized {
+    /// The id of the application blocks holding the payload.
+    const ID: [u8; 4];
+
+    /// Attempts to parse the data of an application block.
+    fn from_bytes(data: &[u8]) -> Result<Self>;
+
+    /// Returns the data of an application block holding the payload.
+    fn to_bytes(&self) -> Vec<u8>;
+}
+
 /// A structure representing an APPLICATION block.
 #[derive(Clone, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Application {
     /// Registered application ID.
-    pub id: Vec<u8>,
+    pub id: [u8; 4],
     /// Application data.
     pub data: Vec<u8>,
 }
@@ -333,20 +799,26 @@ impl Application {
     /// Returns a new `Application` with a zero id and no data.
     pub fn new() -> Application {
         Application {
-            id: Vec::new(),
+            id: [0; 4],
             data: Vec::new(),
         }
     }
 
-    /// Parses the bytes as an application block.
+    /// Returns a new `Application` holding the payload.
+    pub fn from_payload<P: ApplicationPayload>(payload: &P) -> Application {
+        Application {
+            id: P::ID,
+            data: payload.to_bytes(),
+        }
+    }
+
+    /// Parses the bytes as an application block. Missing bytes of the id are zero.
     pub fn from_bytes(bytes: &[u8]) -> Application {
         let mut application = Application::new();
-        let mut i = 0;
-
-        application.id = bytes[i..i + 4].to_vec();
-        i += 4;
+        let id_length = bytes.len().min(4);
 
-        application.data = bytes[i..].to_vec();
+        application.id[..id_length].copy_from_slice(&bytes[..id_length]);
+        application.data = bytes[id_length..].to_vec();
 
         application
     }
@@ -360,6 +832,33 @@ impl Application {
 
         bytes
     }
+
+    /// Returns the name of the application if its id is registered with xiph.org.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::Application;
+    ///
+    /// let mut application = Application::new();
+    /// application.id = *b"riff";
+    /// assert_eq!(application.registered_name(), Some("FLAC RIFF chunk storage"));
+    /// ```
+    pub fn registered_name(&self) -> Option<&'static str> {
+        REGISTERED_APPLICATIONS
+            .iter()
+            .find(|(id, _)| *id == self.id)
+            .map(|(_, name)| *name)
+    }
+
+    /// Attempts to parse the data as the payload. Returns `None` if the id of the block is not
+    /// the id of the payload.
+    pub fn payload<P: ApplicationPayload>(&self) -> Option<Result<P>> {
+        if self.id == P::ID {
+            Some(P::from_bytes(&self.data[..]))
+        } else {
+            None
+        }
+    }
 }
 
 impl Default for Application {
@@ -555,13 +1054,8 @@ impl CueSheet {
 
             bytes.extend(track.offset.to_be_bytes().iter());
             bytes.push(track.number);
-            bytes.extend(track.isrc.clone().into_bytes().into_iter());
-            bytes.extend(
-                repeat(0)
-                    .take(12 - track.isrc.len())
-                    .collect::<Vec<u8>>()
-                    .into_iter(),
-            );
+            bytes.extend(track.isrc.clone().into_bytes());
+            bytes.extend(repeat(0).take(12 - track.isrc.len()).collect::<Vec<u8>>());
 
             let mut flags = 0;
             if !track.is_audio {
@@ -624,7 +1118,7 @@ pub enum PictureType {
 }
 
 impl PictureType {
-    fn from_u32(n: u32) -> Option<PictureType> {
+    pub(crate) fn from_u32(n: u32) -> Option<PictureType> {
         match n {
             0 => Some(PictureType::Other),
             1 => Some(PictureType::Icon),
@@ -794,6 +1288,9 @@ pub struct SeekPoint {
 }
 
 impl SeekPoint {
+    /// The sample number used by placeholder points.
+    pub const PLACEHOLDER_SAMPLE_NUMBER: u64 = 0xFF_FF_FF_FF_FF_FF_FF_FF;
+
     /// Returns a new `SeekPoint` with all zero values.
     pub fn new() -> SeekPoint {
         SeekPoint {
@@ -803,6 +1300,93 @@ impl SeekPoint {
         }
     }
 
+    /// Attempts to create a `SeekPoint` for the frame starting at the specified sample number. An
+    /// `Error::InvalidInput` will be returned if the sample number is the placeholder sample
+    /// number, use `SeekPoint::placeholder` to create placeholder points.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::SeekPoint;
+    ///
+    /// let point = SeekPoint::from_values(4096, 1234, 4096).unwrap();
+    /// assert_eq!(point.sample_number(), 4096);
+    /// assert_eq!(point.offset(), 1234);
+    /// assert_eq!(point.num_samples(), 4096);
+    ///
+    /// assert!(SeekPoint::from_values(SeekPoint::PLACEHOLDER_SAMPLE_NUMBER, 0, 0).is_err());
+    /// ```
+    pub fn from_values(sample_number: u64, offset: u64, num_samples: u16) -> Result<SeekPoint> {
+        let mut seekpoint = SeekPoint::new();
+        seekpoint.set_sample_number(sample_number)?;
+        seekpoint.offset = offset;
+        seekpoint.num_samples = num_samples;
+        Ok(seekpoint)
+    }
+
+    /// Returns a new placeholder `SeekPoint`.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::SeekPoint;
+    ///
+    /// let point = SeekPoint::placeholder();
+    /// assert!(point.is_placeholder());
+    /// assert_eq!(point.sample_number(), SeekPoint::PLACEHOLDER_SAMPLE_NUMBER);
+    /// ```
+    pub fn placeholder() -> SeekPoint {
+        SeekPoint {
+            sample_number: SeekPoint::PLACEHOLDER_SAMPLE_NUMBER,
+            offset: 0,
+            num_samples: 0,
+        }
+    }
+
+    /// Returns true if the seek point is a placeholder point.
+    pub fn is_placeholder(&self) -> bool {
+        self.sample_number == SeekPoint::PLACEHOLDER_SAMPLE_NUMBER
+    }
+
+    /// Returns the sample number of the first sample in the target frame.
+    pub fn sample_number(&self) -> u64 {
+        self.sample_number
+    }
+
+    /// Sets the sample number of the first sample in the target frame. An `Error::InvalidInput`
+    /// will be returned if the sample number is the placeholder sample number.
+    pub fn set_sample_number(&mut self, sample_number: u64) -> Result<()> {
+        if sample_number == SeekPoint::PLACEHOLDER_SAMPLE_NUMBER {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "sample number is reserved for placeholder points",
+            ));
+        }
+
+        self.sample_number = sample_number;
+        Ok(())
+    }
+
+    /// Returns the offset (in bytes) from the first byte of the first frame header to the first
+    /// byte of the target frame's header.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Sets the offset (in bytes) from the first byte of the first frame header to the first byte
+    /// of the target frame's header.
+    pub fn set_offset(&mut self, offset: u64) {
+        self.offset = offset;
+    }
+
+    /// Returns the number of samples in the target frame.
+    pub fn num_samples(&self) -> u16 {
+        self.num_samples
+    }
+
+    /// Sets the number of samples in the target frame.
+    pub fn set_num_samples(&mut self, num_samples: u16) {
+        self.num_samples = num_samples;
+    }
+
     /// Parses the bytes as a seekpoint.
     pub fn from_bytes(bytes: &[u8]) -> SeekPoint {
         let mut seekpoint = SeekPoint::new();
@@ -838,6 +1422,22 @@ impl Default for SeekPoint {
 }
 //}}}
 
+/// Describes which seek points to generate for a seektable. The variants correspond to the
+/// arguments accepted by `metaflac --add-seekpoint`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SeekPointSpec {
+    /// A seek point for the frame containing the specified sample number (`#`).
+    Sample(u64),
+    /// A seek point for every N samples.
+    EverySamples(u64),
+    /// A seek point for every N seconds (`#s`).
+    EverySeconds(f64),
+    /// N seek points evenly spaced throughout the stream (`#x`).
+    Evenly(u32),
+    /// N placeholder points, which reserve space to be filled in later (`X`).
+    Placeholders(u32),
+}
+
 /// A structure representing a SEEKTABLE block.
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -874,11 +1474,157 @@ impl SeekTable {
         let mut bytes = Vec::new();
 
         for seekpoint in self.seekpoints.iter() {
-            bytes.extend(seekpoint.to_bytes().into_iter());
+            bytes.extend(seekpoint.to_bytes());
         }
 
         bytes
     }
+
+    /// Generates a seektable by scanning the frames in the audio data (the contents of a FLAC
+    /// stream following the metadata, as returned by `Tag::skip_metadata`).
+    ///
+    /// Each requested sample is mapped to the frame containing it, so the resulting seek points
+    /// always refer to the first sample of a frame. Samples past the end of the stream are
+    /// ignored. The seek points are sorted and deduplicated, with any placeholders at the end.
+    /// Frames of 65536 samples, which a seek point cannot describe, are given 65535 samples.
+    pub fn generate(
+        streaminfo: &StreamInfo,
+        audio: &[u8],
+        specs: &[SeekPointSpec],
+    ) -> Result<SeekTable> {
+        // (first sample, offset, block size) of every frame
+        let mut frames = Vec::new();
+        let mut total_samples = 0;
+        for frame in Frames::new(audio) {
+            frames.push((total_samples, frame.offset, frame.header.block_size));
+            total_samples += frame.header.block_size as u64;
+        }
+
+        let mut targets = Vec::new();
+        let mut num_placeholders = 0;
+        for spec in specs.iter() {
+            match *spec {
+                SeekPointSpec::Sample(sample) => targets.push(sample),
+                SeekPointSpec::EverySamples(interval) => {
+                    if interval == 0 {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            "seek point interval must be nonzero",
+                        ));
+                    }
+                    targets.extend((0..total_samples).step_by(step(interval)));
+                }
+                SeekPointSpec::EverySeconds(seconds) => {
+                    let interval = (seconds * streaminfo.sample_rate as f64) as u64;
+                    if interval == 0 {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            "seek point interval must be at least one sample",
+                        ));
+                    }
+                    targets.extend((0..total_samples).step_by(step(interval)));
+                }
+                SeekPointSpec::Evenly(num_points) => {
+                    let num_points = num_points as u128;
+                    targets.extend(
+                        (0..num_points).map(|i| (total_samples as u128 * i / num_points) as u64),
+                    );
+                }
+                SeekPointSpec::Placeholders(n) => num_placeholders += n,
+            }
+        }
+
+        let mut seektable = SeekTable::new();
+        for target in targets {
+            if target >= total_samples {
+                continue;
+            }
+
+            let index = match frames.binary_search_by_key(&target, |&(sample, _, _)| sample) {
+                Ok(index) => index,
+                Err(index) => index - 1,
+            };
+            let (sample_number, offset, num_samples) = frames[index];
+            seektable.seekpoints.push(SeekPoint {
+                sample_number,
+                offset,
+                num_samples: u16::try_from(num_samples).unwrap_or(u16::MAX),
+            });
+        }
+
+        for _ in 0..num_placeholders {
+            seektable.seekpoints.push(SeekPoint::placeholder());
+        }
+
+        seektable.sort_and_dedup();
+        Ok(seektable)
+    }
+
+    /// Checks that the seektable follows the rules of the specification: seek points must be in
+    /// ascending order of sample number, must be unique by sample number, and placeholder points
+    /// may only appear at the end of the table. In addition, the offset of every seek point must
+    /// lie within the audio data, which is `audio_length` bytes long.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::{SeekPoint, SeekTable};
+    ///
+    /// let mut seektable = SeekTable::new();
+    /// seektable.seekpoints.push(SeekPoint::from_values(0, 0, 4096).unwrap());
+    /// seektable.seekpoints.push(SeekPoint::from_values(4096, 1000, 4096).unwrap());
+    /// seektable.seekpoints.push(SeekPoint::placeholder());
+    /// assert!(seektable.validate(2000).is_ok());
+    /// assert!(seektable.validate(1000).is_err());
+    ///
+    /// seektable.seekpoints.swap(0, 1);
+    /// assert!(seektable.validate(2000).is_err());
+    /// ```
+    pub fn validate(&self, audio_length: u64) -> Result<()> {
+        let mut previous: Option<&SeekPoint> = None;
+        for point in self.seekpoints.iter() {
+            if let Some(previous) = previous {
+                if previous.is_placeholder() && !point.is_placeholder() {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "placeholder seek points must be at the end of the seektable",
+                    ));
+                }
+                if !point.is_placeholder() {
+                    if point.sample_number == previous.sample_number {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            "seek points must have unique sample numbers",
+                        ));
+                    }
+                    if point.sample_number < previous.sample_number {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            "seek points must be in ascending order of sample number",
+                        ));
+                    }
+                }
+            }
+
+            if !point.is_placeholder() && point.offset >= audio_length {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "seek point offset is past the end of the audio data",
+                ));
+            }
+
+            previous = Some(point);
+        }
+
+        Ok(())
+    }
+
+    /// Sorts the seek points in ascending order of sample number and removes seek points which
+    /// share a sample number. Placeholder points are kept and moved to the end of the table.
+    pub fn sort_and_dedup(&mut self) {
+        self.seekpoints.sort_by_key(|point| point.sample_number);
+        self.seekpoints
+            .dedup_by(|a, b| a.sample_number == b.sample_number && !a.is_placeholder());
+    }
 }
 
 impl Default for SeekTable {
@@ -886,277 +1632,2174 @@ impl Default for SeekTable {
         Self::new()
     }
 }
-//}}}
 
-// VorbisComment {{{
-/// A structure representing a VORBIS_COMMENT block.
-#[derive(Clone, Debug, Eq, PartialEq)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct VorbisComment {
-    /// The vendor string.
-    pub vendor_string: String,
-    /// A map of keys to a list of their values.
-    pub comments: HashMap<String, Vec<String>>,
+/// Returns a seek point interval as a step of `Iterator::step_by`. Intervals which do not fit in
+/// a `usize` step past the end of any stream.
+fn step(interval: u64) -> usize {
+    usize::try_from(interval).unwrap_or(usize::MAX)
 }
+//}}}
 
-impl VorbisComment {
-    /// Returns a new `VorbisComment` with an empty vendor string and no comments.
-    pub fn new() -> VorbisComment {
-        VorbisComment {
-            vendor_string: String::new(),
-            comments: HashMap::new(),
+// VorbisComment {{{
+// FieldName {{{
+/// A vorbis comment field name, which is checked to contain only ASCII characters 0x20 to 0x7D
+/// other than `=`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct FieldName(String);
+
+impl FieldName {
+    /// Attempts to create a field name. Returns an error if the name is empty or contains an
+    /// invalid character.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::FieldName;
+    ///
+    /// assert_eq!(FieldName::new("ARTIST").unwrap().as_str(), "ARTIST");
+    /// assert!(FieldName::new("FOO=BAR").is_err());
+    /// assert!(FieldName::new("TITLE\n").is_err());
+    /// ```
+    pub fn new<T: Into<String>>(name: T) -> Result<FieldName> {
+        let name = name.into();
+        if FieldName::is_valid(&name) {
+            Ok(FieldName(name))
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                "invalid vorbis comment field name",
+            ))
         }
     }
 
-    /// Attempts to parse the bytes as a vorbis comment block. Returns a `VorbisComment` on
-    /// success.
-    pub fn from_bytes(bytes: &[u8]) -> Result<VorbisComment> {
-        let mut vorbis = VorbisComment::new();
-        let mut i = 0;
+    /// Returns true if the name is a valid field name.
+    pub fn is_valid(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .bytes()
+                .all(|byte| (0x20..=0x7D).contains(&byte) && byte != b'=')
+    }
 
-        let vendor_length = u32::from_le_bytes((&bytes[i..i + 4]).try_into().unwrap()) as usize;
-        i += 4;
+    /// Returns the field name as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0[..]
+    }
+}
 
-        vorbis.vendor_string = String::from_utf8(bytes[i..i + vendor_length].to_vec())?;
-        i += vendor_length;
+impl fmt::Display for FieldName {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        out.write_str(&self.0[..])
+    }
+}
 
-        let num_comments = u32::from_le_bytes((&bytes[i..i + 4]).try_into().unwrap());
-        i += 4;
+impl From<FieldName> for String {
+    fn from(name: FieldName) -> String {
+        name.0
+    }
+}
+//}}}
 
-        for _ in 0..num_comments {
-            let comment_length =
-                u32::from_le_bytes((&bytes[i..i + 4]).try_into().unwrap()) as usize;
-            i += 4;
+// StandardField {{{
+macro_rules! standard_fields {
+    ($($(#[$doc:meta])* $variant:ident => $key:expr,)*) => {
+        /// Standard vorbis comment fields: the fields recommended by Xiph and widely used
+        /// de-facto fields.
+        #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub enum StandardField {
+            $($(#[$doc])* $variant,)*
+        }
 
-            let comments = String::from_utf8(bytes[i..i + comment_length].to_vec())?;
-            i += comment_length;
+        impl StandardField {
+            /// All standard fields.
+            pub const ALL: &'static [StandardField] = &[$(StandardField::$variant,)*];
 
-            let comments_split: Vec<&str> = comments.splitn(2, '=').collect();
-            let key = comments_split[0].to_ascii_uppercase();
-            let value = comments_split[1].to_owned();
+            /// The field names of the fields, in the order of `ALL`.
+            const KEYS: &'static [&'static str] = &[$($key,)*];
 
-            vorbis
-                .comments
-                .entry(key)
-                .or_insert_with(|| Vec::with_capacity(1))
-                .push(value);
+            /// Returns the field name of the field.
+            pub fn key(self) -> &'static str {
+                StandardField::KEYS[self as usize]
+            }
         }
+    };
+}
 
-        Ok(vorbis)
+standard_fields! {
+    /// TITLE: the track or work name.
+    Title => "TITLE",
+    /// TITLESORT: the title used for sorting.
+    TitleSort => "TITLESORT",
+    /// VERSION: the version of the track, such as a remix.
+    Version => "VERSION",
+    /// SUBTITLE: the subtitle of the track.
+    Subtitle => "SUBTITLE",
+    /// ALBUM: the collection name.
+    Album => "ALBUM",
+    /// ALBUMSORT: the collection name used for sorting.
+    AlbumSort => "ALBUMSORT",
+    /// TRACKNUMBER: the number of the track in the collection.
+    TrackNumber => "TRACKNUMBER",
+    /// TOTALTRACKS: the number of tracks in the collection.
+    TotalTracks => "TOTALTRACKS",
+    /// DISCNUMBER: the number of the disc in the collection.
+    DiscNumber => "DISCNUMBER",
+    /// DISCTOTAL: the number of discs in the collection.
+    DiscTotal => "DISCTOTAL",
+    /// ARTIST: the artist responsible for the work.
+    Artist => "ARTIST",
+    /// ARTISTSORT: the artist name used for sorting.
+    ArtistSort => "ARTISTSORT",
+    /// ALBUMARTIST: the artist of the collection.
+    AlbumArtist => "ALBUMARTIST",
+    /// ALBUMARTISTSORT: the album artist name used for sorting.
+    AlbumArtistSort => "ALBUMARTISTSORT",
+    /// PERFORMER: the artist who performed the work.
+    Performer => "PERFORMER",
+    /// COMPOSER: the composer of the work.
+    Composer => "COMPOSER",
+    /// COMPOSERSORT: the composer name used for sorting.
+    ComposerSort => "COMPOSERSORT",
+    /// CONDUCTOR: the conductor of the performance.
+    Conductor => "CONDUCTOR",
+    /// LYRICIST: the writer of the lyrics.
+    Lyricist => "LYRICIST",
+    /// ARRANGER: the arranger of the work.
+    Arranger => "ARRANGER",
+    /// REMIXER: the remixer of the track.
+    Remixer => "REMIXER",
+    /// COPYRIGHT: the copyright attribution.
+    Copyright => "COPYRIGHT",
+    /// LICENSE: the license information.
+    License => "LICENSE",
+    /// ORGANIZATION: the organization which produced the track.
+    Organization => "ORGANIZATION",
+    /// LABEL: the record label.
+    Label => "LABEL",
+    /// CATALOGNUMBER: the catalog number of the release.
+    CatalogNumber => "CATALOGNUMBER",
+    /// BARCODE: the barcode of the release.
+    Barcode => "BARCODE",
+    /// ISRC: the International Standard Recording Code of the track.
+    Isrc => "ISRC",
+    /// DESCRIPTION: a short description of the contents.
+    Description => "DESCRIPTION",
+    /// COMMENT: a free text comment.
+    Comment => "COMMENT",
+    /// GENRE: the genre of the music.
+    Genre => "GENRE",
+    /// MOOD: the mood of the music.
+    Mood => "MOOD",
+    /// BPM: the tempo in beats per minute.
+    Bpm => "BPM",
+    /// GROUPING: the content group of the track.
+    Grouping => "GROUPING",
+    /// COMPILATION: 1 if the track is part of a compilation.
+    Compilation => "COMPILATION",
+    /// DATE: the date on which the track was recorded or released.
+    Date => "DATE",
+    /// ORIGINALDATE: the date of the original release.
+    OriginalDate => "ORIGINALDATE",
+    /// LOCATION: the location where the track was recorded.
+    Location => "LOCATION",
+    /// CONTACT: contact information for the creators or distributors.
+    Contact => "CONTACT",
+    /// LANGUAGE: the language of the lyrics.
+    Language => "LANGUAGE",
+    /// MEDIA: the medium of the release.
+    Media => "MEDIA",
+    /// LYRICS: the lyrics of the track.
+    Lyrics => "LYRICS",
+    /// ENCODED-BY: the person or organization which encoded the track.
+    EncodedBy => "ENCODED-BY",
+    /// ENCODER: the software which encoded the track.
+    Encoder => "ENCODER",
+}
+
+impl StandardField {
+    /// Returns the standard field with the field name, ignoring case.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::StandardField;
+    ///
+    /// assert_eq!(StandardField::from_key("discnumber"), Some(StandardField::DiscNumber));
+    /// assert_eq!(StandardField::DiscNumber.key(), "DISCNUMBER");
+    /// assert_eq!(StandardField::from_key("MY_FIELD"), None);
+    /// ```
+    pub fn from_key(key: &str) -> Option<StandardField> {
+        StandardField::ALL
+            .iter()
+            .find(|field| field.key().eq_ignore_ascii_case(key))
+            .cloned()
     }
 
-    /// Returns a vector representation of the vorbis comment suitable for writing to a file.
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+    /// Returns the field holding the sort names of the field, if there is one.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::StandardField;
+    ///
+    /// assert_eq!(StandardField::Artist.sort_field(), Some(StandardField::ArtistSort));
+    /// assert_eq!(StandardField::Genre.sort_field(), None);
+    /// ```
+    pub fn sort_field(self) -> Option<StandardField> {
+        match self {
+            StandardField::Title => Some(StandardField::TitleSort),
+            StandardField::Album => Some(StandardField::AlbumSort),
+            StandardField::Artist => Some(StandardField::ArtistSort),
+            StandardField::AlbumArtist => Some(StandardField::AlbumArtistSort),
+            StandardField::Composer => Some(StandardField::ComposerSort),
+            _ => None,
+        }
+    }
 
-        let vendor_string = self.vendor_string.clone().into_bytes();
+    /// Returns the field names under which the field is commonly stored, starting with `key`.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::StandardField;
+    ///
+    /// assert_eq!(StandardField::Date.aliases(), &["DATE", "YEAR"]);
+    /// assert_eq!(StandardField::Title.aliases(), &["TITLE"]);
+    /// ```
+    pub fn aliases(self) -> &'static [&'static str] {
+        match self {
+            StandardField::TotalTracks => &["TOTALTRACKS", "TRACKTOTAL"],
+            StandardField::DiscTotal => &["DISCTOTAL", "TOTALDISCS"],
+            StandardField::AlbumArtist => &["ALBUMARTIST", "ALBUM ARTIST", "ALBUM_ARTIST"],
+            StandardField::Date => &["DATE", "YEAR"],
+            StandardField::OriginalDate => &["ORIGINALDATE", "ORIGINALYEAR"],
+            StandardField::Label => &["LABEL", "PUBLISHER"],
+            StandardField::Lyrics => &["LYRICS", "UNSYNCEDLYRICS"],
+            StandardField::EncodedBy => &["ENCODED-BY", "ENCODEDBY"],
+            _ => std::slice::from_ref(&StandardField::KEYS[self as usize]),
+        }
+    }
+}
 
-        bytes.extend((vendor_string.len() as u32).to_le_bytes().iter());
-        bytes.extend(vendor_string);
+impl fmt::Display for StandardField {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        out.write_str(self.key())
+    }
+}
+//}}}
 
-        bytes.extend(
-            (self
-                .comments
-                .values()
-                .fold(0u32, |acc, l| acc + l.len() as u32))
-            .to_le_bytes()
-            .iter(),
-        );
+// WriteConvention {{{
+/// A convention for writing standard fields which are stored under several field names. See
+/// `StandardField::aliases`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WriteConvention {
+    /// The field names written for fields, instead of `StandardField::key`.
+    pub keys: Vec<(StandardField, &'static str)>,
+    /// Whether the values stored under the other aliases of a field are removed when writing it.
+    pub remove_aliases: bool,
+    /// Whether TRACKNUMBER and DISCNUMBER values of the form `n/total` are split when
+    /// normalizing.
+    pub split_totals: bool,
+    /// Whether DATE and ORIGINALDATE values are rewritten in ISO 8601 form when normalizing.
+    /// Values which cannot be parsed as a `PartialDate` are kept.
+    pub iso_dates: bool,
+}
 
-        for (key, list) in self.comments.iter() {
-            for value in list.iter() {
-                let comment_string = format!("{}={}", key, value);
-                let comment = comment_string.into_bytes();
-                bytes.extend((comment.len() as u32).to_le_bytes().iter());
-                bytes.extend(comment.into_iter());
-            }
+impl WriteConvention {
+    /// Returns a convention which writes fields under `StandardField::key`, removes the other
+    /// aliases, splits totals and writes ISO 8601 dates.
+    pub fn new() -> WriteConvention {
+        WriteConvention {
+            keys: Vec::new(),
+            remove_aliases: true,
+            split_totals: true,
+            iso_dates: true,
         }
-
-        bytes
     }
 
-    /// Returns a reference to the vector of comments for the specified key.
-    pub fn get(&self, key: &str) -> Option<&Vec<String>> {
-        self.comments.get(key)
+    /// Returns the convention with the field written under the key. An `Error::InvalidInput` is
+    /// returned if the key is not one of the aliases of the field, since values written under
+    /// any other key would not be read back as the field.
+    pub fn with_key(mut self, field: StandardField, key: &'static str) -> Result<WriteConvention> {
+        if !field.aliases().contains(&key) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "key is not an alias of the field",
+            ));
+        }
+        self.keys.retain(|&(other, _)| other != field);
+        self.keys.push((field, key));
+        Ok(self)
     }
 
-    /// Sets the comments for the specified key. Any previous values under the key will be removed.
-    pub fn set<K: Into<String>, V: Into<String>>(&mut self, key: K, values: Vec<V>) {
-        let key_owned = key.into();
-        self.remove(&key_owned[..]);
-        self.comments
-            .insert(key_owned, values.into_iter().map(|s| s.into()).collect());
+    /// Returns the field name written for the field.
+    pub fn key(&self, field: StandardField) -> &'static str {
+        self.keys
+            .iter()
+            .find(|&&(other, _)| other == field)
+            .map_or(field.key(), |&(_, key)| key)
     }
+}
 
-    /// Removes the comments for the specified key.
+impl Default for WriteConvention {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+//}}}
+
+// MultiValuePolicy {{{
+/// How the values of a field are split when reading and joined when writing. See
+/// `MultiValuePolicy`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FieldPolicy {
+    /// The delimiters at which values are split into several values when reading. Values are
+    /// never split if this is empty.
+    pub delimiters: Vec<String>,
+    /// The separator used to join several values into one value when writing, or `None` to write
+    /// each value as a repeated field.
+    pub join: Option<String>,
+}
+
+impl FieldPolicy {
+    /// Returns a policy which splits values at `;` and writes repeated fields.
+    pub fn new() -> FieldPolicy {
+        FieldPolicy {
+            delimiters: vec![";".to_owned()],
+            join: None,
+        }
+    }
+
+    /// Returns a policy which never splits values and writes repeated fields.
+    pub fn never_split() -> FieldPolicy {
+        FieldPolicy {
+            delimiters: Vec::new(),
+            join: None,
+        }
+    }
+
+    /// Returns the values split at the delimiters, with surrounding whitespace and empty values
+    /// removed. The values are returned unchanged if the policy has no delimiters.
+    pub fn split<'a, T: AsRef<str>>(&self, values: &'a [T]) -> Vec<&'a str> {
+        let mut parts: Vec<&str> = values.iter().map(|value| value.as_ref()).collect();
+        if self.delimiters.iter().all(|d| d.is_empty()) {
+            return parts;
+        }
+        for delimiter in self.delimiters.iter().filter(|d| !d.is_empty()) {
+            parts = parts
+                .into_iter()
+                .flat_map(|part| part.split(&delimiter[..]))
+                .collect();
+        }
+        parts
+            .into_iter()
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .collect()
+    }
+
+    /// Returns the values to write, joined if the policy joins values.
+    pub fn join<T: AsRef<str>>(&self, values: &[T]) -> Vec<String> {
+        match self.join {
+            Some(ref separator) if !values.is_empty() => vec![values
+                .iter()
+                .map(|value| value.as_ref())
+                .collect::<Vec<&str>>()
+                .join(separator)],
+            _ => values
+                .iter()
+                .map(|value| value.as_ref().to_owned())
+                .collect(),
+        }
+    }
+}
+
+impl Default for FieldPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Policies for splitting and joining the values of fields which hold several values, such as
+/// `ARTIST=A; B` and repeated `ARTIST` fields.
+///
+/// # Example
+/// ```
+/// use metaflac::block::{FieldPolicy, MultiValuePolicy, VorbisComment};
+///
+/// let policy = MultiValuePolicy::new().with_field(
+///     "GENRE",
+///     FieldPolicy {
+///         delimiters: vec![";".to_owned(), "/".to_owned()],
+///         join: Some("; ".to_owned()),
+///     },
+/// );
+///
+/// let mut comments = VorbisComment::new();
+/// comments.set("ARTIST", vec!["A; B", "C"]);
+/// comments.set("GENRE", vec!["Rock/Pop", "Jazz"]);
+/// comments.set("TITLE", vec!["Love; Hate"]);
+/// assert_eq!(comments.get_split("ARTIST", &policy), vec!["A", "B", "C"]);
+///
+/// comments.apply_multi_value_policy(&policy);
+/// assert_eq!(comments.get("ARTIST").unwrap().len(), 3);
+/// assert_eq!(comments.get("GENRE"), Some(&vec!["Rock; Pop; Jazz".to_owned()]));
+/// assert_eq!(comments.get("TITLE"), Some(&vec!["Love; Hate".to_owned()]));
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MultiValuePolicy {
+    /// The policy of fields without their own policy.
+    pub default: FieldPolicy,
+    /// The policies of fields, by field name.
+    pub fields: Vec<(String, FieldPolicy)>,
+}
+
+impl MultiValuePolicy {
+    /// Returns a policy which never splits values, apart from the values of ARTIST, PERFORMER
+    /// and GENRE which are split at `;`, and writes repeated fields. Other fields are opted in
+    /// with `with_field`.
+    pub fn new() -> MultiValuePolicy {
+        let policy = MultiValuePolicy {
+            default: FieldPolicy::never_split(),
+            fields: Vec::new(),
+        };
+        [
+            StandardField::Artist,
+            StandardField::Performer,
+            StandardField::Genre,
+        ]
+        .iter()
+        .fold(policy, |policy, field| {
+            policy.with_field(field.key(), FieldPolicy::new())
+        })
+    }
+
+    /// Returns the policy with the field using the field policy.
+    pub fn with_field<K: Into<String>>(mut self, key: K, policy: FieldPolicy) -> MultiValuePolicy {
+        let key = key.into();
+        self.fields
+            .retain(|(other, _)| !other.eq_ignore_ascii_case(&key));
+        self.fields.push((key, policy));
+        self
+    }
+
+    /// Returns the policy with the values of the field never split.
+    pub fn never_split<K: Into<String>>(self, key: K) -> MultiValuePolicy {
+        self.with_field(key, FieldPolicy::never_split())
+    }
+
+    /// Returns the policy of the field. Field names are compared case insensitively.
+    pub fn field(&self, key: &str) -> &FieldPolicy {
+        self.fields
+            .iter()
+            .find(|(other, _)| other.eq_ignore_ascii_case(key))
+            .map_or(&self.default, |(_, policy)| policy)
+    }
+}
+
+impl Default for MultiValuePolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+//}}}
+
+// PartialDate {{{
+/// A date of year, month or day precision, with an optional time of day, as stored in the DATE
+/// and ORIGINALDATE fields.
+///
+/// Dates are ordered chronologically, with a less precise date ordered before the more precise
+/// dates it contains.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartialDate {
+    /// The year.
+    pub year: u16,
+    /// The month, from 1 to 12.
+    pub month: Option<u8>,
+    /// The day of the month, from 1 to 31.
+    pub day: Option<u8>,
+    /// The hour, from 0 to 23.
+    pub hour: Option<u8>,
+    /// The minute, from 0 to 59.
+    pub minute: Option<u8>,
+    /// The second, from 0 to 59.
+    pub second: Option<u8>,
+}
+
+impl PartialDate {
+    /// Returns a date of year precision.
+    pub fn new(year: u16) -> PartialDate {
+        PartialDate {
+            year,
+            month: None,
+            day: None,
+            hour: None,
+            minute: None,
+            second: None,
+        }
+    }
+
+    /// Attempts to parse a date. Accepts ISO 8601 dates (`2004`, `2004-05`, `2004-05-17`,
+    /// `20040517`) with an optional time (`2004-05-17T10:00`, `2004-05-17 10:00:30Z`), dates
+    /// separated by `/` or `.` in the same order, and day first dates separated by `.`
+    /// (`17.05.2004`). Time zones are ignored.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::PartialDate;
+    ///
+    /// let date = PartialDate::parse("17.05.2004").unwrap();
+    /// assert_eq!(date.year, 2004);
+    /// assert_eq!(date.month, Some(5));
+    /// assert_eq!(date.day, Some(17));
+    /// assert_eq!(date.to_string(), "2004-05-17");
+    ///
+    /// assert!(PartialDate::parse("2004-02-30").is_err());
+    /// ```
+    pub fn parse(text: &str) -> Result<PartialDate> {
+        PartialDate::parse_parts(text.trim())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid date"))
+    }
+
+    fn parse_parts(text: &str) -> Option<PartialDate> {
+        let (date, time) = match text.find(['T', ' ']) {
+            Some(i) => (&text[..i], Some(text[i + 1..].trim_start())),
+            None => (text, None),
+        };
+
+        let separator = date.chars().find(|c| !c.is_ascii_digit());
+        let parts: Vec<&str> = match separator {
+            Some(separator @ ('-' | '/' | '.')) => date.split(separator).collect(),
+            Some(_) => return None,
+            None if date.len() == 8 => vec![&date[..4], &date[4..6], &date[6..]],
+            None => vec![date],
+        };
+
+        let mut result = match parts[..] {
+            [year] if year.len() == 4 => PartialDate::new(parse_digits(year, 4)?),
+            [year, month] if year.len() == 4 => PartialDate {
+                month: Some(parse_digits(month, 2)? as u8),
+                ..PartialDate::new(parse_digits(year, 4)?)
+            },
+            [year, month, day] if year.len() == 4 => PartialDate {
+                month: Some(parse_digits(month, 2)? as u8),
+                day: Some(parse_digits(day, 2)? as u8),
+                ..PartialDate::new(parse_digits(year, 4)?)
+            },
+            [day, month, year] if year.len() == 4 && separator == Some('.') => PartialDate {
+                month: Some(parse_digits(month, 2)? as u8),
+                day: Some(parse_digits(day, 2)? as u8),
+                ..PartialDate::new(parse_digits(year, 4)?)
+            },
+            _ => return None,
+        };
+
+        if let Some(time) = time {
+            result.day?;
+            let time = time.trim_end_matches('Z');
+            let time = time.find(['+', '-']).map_or(time, |i| &time[..i]);
+            let parts: Vec<&str> = time.split(':').collect();
+            if !(2..=3).contains(&parts.len()) {
+                return None;
+            }
+            result.hour = Some(parse_digits(parts[0], 2)? as u8);
+            result.minute = Some(parse_digits(parts[1], 2)? as u8);
+            if let Some(second) = parts.get(2) {
+                result.second = Some(parse_digits(second, 2)? as u8);
+            }
+        }
+
+        if result.is_valid() {
+            Some(result)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the date exists and its precision has no gaps, e.g. a day without a
+    /// month.
+    pub fn is_valid(&self) -> bool {
+        let days = match self.month {
+            Some(1 | 3 | 5 | 7 | 8 | 10 | 12) => 31,
+            Some(4 | 6 | 9 | 11) => 30,
+            Some(2) if self.year % 4 == 0 && (self.year % 100 != 0 || self.year % 400 == 0) => 29,
+            Some(2) => 28,
+            Some(_) => return false,
+            None => 0,
+        };
+        let time = (self.hour, self.minute, self.second);
+        self.year <= 9999
+            && self.day.map_or(true, |day| (1..=days).contains(&day))
+            && (self.month.is_some() || self.day.is_none())
+            && (self.day.is_some() || self.hour.is_none())
+            && match time {
+                (None, None, None) => true,
+                (Some(hour), Some(minute), second) => {
+                    hour < 24 && minute < 60 && second.map_or(true, |second| second < 60)
+                }
+                _ => false,
+            }
+    }
+}
+
+impl fmt::Display for PartialDate {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        write!(out, "{:04}", self.year)?;
+        if let Some(month) = self.month {
+            write!(out, "-{:02}", month)?;
+            if let Some(day) = self.day {
+                write!(out, "-{:02}", day)?;
+                if let (Some(hour), Some(minute)) = (self.hour, self.minute) {
+                    write!(out, "T{:02}:{:02}", hour, minute)?;
+                    if let Some(second) = self.second {
+                        write!(out, ":{:02}", second)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for PartialDate {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<PartialDate> {
+        PartialDate::parse(text)
+    }
+}
+
+/// Parses up to `max_len` ASCII digits.
+fn parse_digits(text: &str, max_len: usize) -> Option<u16> {
+    if text.is_empty() || text.len() > max_len || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+//}}}
+
+// ReplayGain {{{
+/// ReplayGain 2.0 values, as stored in the REPLAYGAIN_* fields. Gains are in dB relative to the
+/// reference loudness and peaks are relative to full scale.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplayGain {
+    /// The gain of the track in dB.
+    pub track_gain: Option<f64>,
+    /// The sample peak of the track.
+    pub track_peak: Option<f64>,
+    /// The gain of the album in dB.
+    pub album_gain: Option<f64>,
+    /// The sample peak of the album.
+    pub album_peak: Option<f64>,
+    /// The loudness the gains are relative to, in LUFS. `REFERENCE_LOUDNESS` is assumed if this
+    /// is `None`.
+    pub reference_loudness: Option<f64>,
+}
+
+impl ReplayGain {
+    /// The reference loudness of ReplayGain 2.0 in LUFS.
+    pub const REFERENCE_LOUDNESS: f64 = -18.0;
+
+    /// The reference loudness of the R128_*_GAIN fields in LUFS.
+    pub const R128_REFERENCE_LOUDNESS: f64 = -23.0;
+
+    /// Returns ReplayGain values with no values set.
+    pub fn new() -> ReplayGain {
+        ReplayGain {
+            track_gain: None,
+            track_peak: None,
+            album_gain: None,
+            album_peak: None,
+            reference_loudness: None,
+        }
+    }
+
+    /// Attempts to parse a gain such as `-6.54 dB`. The unit is optional and a comma is accepted
+    /// as the decimal separator.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::ReplayGain;
+    ///
+    /// assert_eq!(ReplayGain::parse_gain("-6.54 dB"), Some(-6.54));
+    /// assert_eq!(ReplayGain::parse_gain("+1,5"), Some(1.5));
+    /// assert_eq!(ReplayGain::parse_gain("loud"), None);
+    /// ```
+    pub fn parse_gain(text: &str) -> Option<f64> {
+        parse_decimal(text, "dB")
+    }
+
+    /// Attempts to parse a peak such as `0.988312`.
+    pub fn parse_peak(text: &str) -> Option<f64> {
+        parse_decimal(text, "").filter(|&peak| peak >= 0.0)
+    }
+
+    /// Attempts to parse a reference loudness such as `-18.00 LUFS`. A positive value in dB is
+    /// read as a ReplayGain 1 sound pressure level, with 89 dB corresponding to -18 LUFS.
+    pub fn parse_reference_loudness(text: &str) -> Option<f64> {
+        parse_decimal(text, "LUFS").or_else(|| {
+            parse_decimal(text, "dB").map(|level| {
+                if level > 0.0 {
+                    level - 89.0 + ReplayGain::REFERENCE_LOUDNESS
+                } else {
+                    level
+                }
+            })
+        })
+    }
+
+    /// Returns the gain formatted as `-6.54 dB`.
+    pub fn format_gain(gain: f64) -> String {
+        format!("{:.2} dB", gain)
+    }
+
+    /// Returns the peak formatted as `0.988312`.
+    pub fn format_peak(peak: f64) -> String {
+        format!("{:.6}", peak)
+    }
+
+    /// Returns the reference loudness formatted as `-18.00 LUFS`.
+    pub fn format_reference_loudness(loudness: f64) -> String {
+        format!("{:.2} LUFS", loudness)
+    }
+
+    /// Converts a gain relative to the reference loudness into an R128 gain, a Q7.8 fixed point
+    /// number of dB relative to -23 LUFS. The gain is clamped to the representable range.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::ReplayGain;
+    ///
+    /// assert_eq!(ReplayGain::to_r128(-6.5, ReplayGain::REFERENCE_LOUDNESS), -2944);
+    /// assert_eq!(ReplayGain::from_r128(-2944, ReplayGain::REFERENCE_LOUDNESS), -6.5);
+    /// ```
+    pub fn to_r128(gain: f64, reference_loudness: f64) -> i16 {
+        let gain = gain + ReplayGain::R128_REFERENCE_LOUDNESS - reference_loudness;
+        (gain * 256.0)
+            .round()
+            .clamp(i16::MIN as f64, i16::MAX as f64) as i16
+    }
+
+    /// Converts an R128 gain into a gain relative to the reference loudness.
+    pub fn from_r128(value: i16, reference_loudness: f64) -> f64 {
+        value as f64 / 256.0 - ReplayGain::R128_REFERENCE_LOUDNESS + reference_loudness
+    }
+
+    /// Returns the reference loudness, or `REFERENCE_LOUDNESS` if none is set.
+    pub fn reference(&self) -> f64 {
+        self.reference_loudness
+            .unwrap_or(ReplayGain::REFERENCE_LOUDNESS)
+    }
+
+    /// Returns true if no values are set.
+    pub fn is_empty(&self) -> bool {
+        *self == ReplayGain::new()
+    }
+}
+
+impl Default for ReplayGain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parses a decimal number with an optional unit suffix and a `.` or `,` decimal separator.
+fn parse_decimal(text: &str, unit: &str) -> Option<f64> {
+    let text = text.trim();
+    let text = match text.len().checked_sub(unit.len()) {
+        Some(i) if !unit.is_empty() && text.is_char_boundary(i) => {
+            if text[i..].eq_ignore_ascii_case(unit) {
+                text[..i].trim_end()
+            } else {
+                text
+            }
+        }
+        _ => text,
+    };
+    if !text
+        .bytes()
+        .all(|b| b.is_ascii_digit() || b"+-.,".contains(&b))
+    {
+        return None;
+    }
+    text.replace(',', ".")
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+}
+//}}}
+
+// Identifiers {{{
+/// A field holding a MusicBrainz or AcoustID identifier.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IdentifierField {
+    /// MUSICBRAINZ_TRACKID, the recording id.
+    TrackId,
+    /// MUSICBRAINZ_RELEASETRACKID, the track id.
+    ReleaseTrackId,
+    /// MUSICBRAINZ_ALBUMID, the release id.
+    AlbumId,
+    /// MUSICBRAINZ_RELEASEGROUPID.
+    ReleaseGroupId,
+    /// MUSICBRAINZ_ARTISTID, one value per artist.
+    ArtistId,
+    /// MUSICBRAINZ_ALBUMARTISTID, one value per album artist.
+    AlbumArtistId,
+    /// MUSICBRAINZ_WORKID, one value per work.
+    WorkId,
+    /// ACOUSTID_ID.
+    AcoustId,
+}
+
+impl IdentifierField {
+    /// All identifier fields.
+    pub const ALL: &'static [IdentifierField] = &[
+        IdentifierField::TrackId,
+        IdentifierField::ReleaseTrackId,
+        IdentifierField::AlbumId,
+        IdentifierField::ReleaseGroupId,
+        IdentifierField::ArtistId,
+        IdentifierField::AlbumArtistId,
+        IdentifierField::WorkId,
+        IdentifierField::AcoustId,
+    ];
+
+    /// Returns the field name of the field.
+    pub fn key(self) -> &'static str {
+        match self {
+            IdentifierField::TrackId => "MUSICBRAINZ_TRACKID",
+            IdentifierField::ReleaseTrackId => "MUSICBRAINZ_RELEASETRACKID",
+            IdentifierField::AlbumId => "MUSICBRAINZ_ALBUMID",
+            IdentifierField::ReleaseGroupId => "MUSICBRAINZ_RELEASEGROUPID",
+            IdentifierField::ArtistId => "MUSICBRAINZ_ARTISTID",
+            IdentifierField::AlbumArtistId => "MUSICBRAINZ_ALBUMARTISTID",
+            IdentifierField::WorkId => "MUSICBRAINZ_WORKID",
+            IdentifierField::AcoustId => "ACOUSTID_ID",
+        }
+    }
+
+    /// Returns true if the field may hold several identifiers.
+    pub fn is_multi_valued(self) -> bool {
+        matches!(
+            self,
+            IdentifierField::ArtistId | IdentifierField::AlbumArtistId | IdentifierField::WorkId
+        )
+    }
+}
+
+impl fmt::Display for IdentifierField {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        out.write_str(self.key())
+    }
+}
+
+/// A MusicBrainz or AcoustID identifier, which is checked to be a UUID in the hyphenated
+/// `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx` form and stored in lower case.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Identifier(String);
+
+impl Identifier {
+    /// Attempts to parse an identifier. Surrounding whitespace and upper case hexadecimal digits
+    /// are accepted.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::Identifier;
+    ///
+    /// let id = Identifier::new(" 5B11F4CE-A62D-471E-81FC-A69A8278C7DA ").unwrap();
+    /// assert_eq!(id.as_str(), "5b11f4ce-a62d-471e-81fc-a69a8278c7da");
+    /// assert!(Identifier::new("5b11f4cea62d471e81fca69a8278c7da").is_err());
+    /// ```
+    pub fn new(text: &str) -> Result<Identifier> {
+        let text = text.trim();
+        if Identifier::is_valid(text) {
+            Ok(Identifier(text.to_ascii_lowercase()))
+        } else {
+            Err(Error::new(ErrorKind::InvalidInput, "invalid identifier"))
+        }
+    }
+
+    /// Returns true if the text is a UUID in the hyphenated form.
+    pub fn is_valid(text: &str) -> bool {
+        text.len() == 36
+            && text.bytes().enumerate().all(|(i, byte)| match i {
+                8 | 13 | 18 | 23 => byte == b'-',
+                _ => byte.is_ascii_hexdigit(),
+            })
+    }
+
+    /// Returns the identifier as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0[..]
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        out.write_str(&self.0[..])
+    }
+}
+
+impl std::str::FromStr for Identifier {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Identifier> {
+        Identifier::new(text)
+    }
+}
+
+/// A problem with an identifier field, see `VorbisComment::identifier_issues`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IdentifierIssue {
+    /// A required field has no values.
+    Missing(IdentifierField),
+    /// A value of the field is not a valid identifier. Contains the value.
+    Malformed(IdentifierField, String),
+    /// A field which holds a single identifier has several values.
+    Multiple(IdentifierField),
+}
+//}}}
+
+/// A structure representing a VORBIS_COMMENT block.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VorbisComment {
+    /// The vendor string.
+    pub vendor_string: String,
+    /// A map of keys to a list of their values.
+    pub comments: HashMap<String, Vec<String>>,
+}
+
+impl VorbisComment {
+    /// Returns a new `VorbisComment` with an empty vendor string and no comments.
+    pub fn new() -> VorbisComment {
+        VorbisComment {
+            vendor_string: String::new(),
+            comments: HashMap::new(),
+        }
+    }
+
+    /// Attempts to parse the bytes as a vorbis comment block. Returns a `VorbisComment` on
+    /// success. Field names are upper-cased. Comments with invalid field names are kept, but are
+    /// refused by `to_bytes`; see `from_bytes_lenient`, `invalid_keys` and
+    /// `Tag::read_from_lenient`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<VorbisComment> {
+        VorbisComment::parse(bytes, false).map(|(vorbis, _)| vorbis)
+    }
+
+    /// Attempts to parse the bytes as a vorbis comment block, leaving out the comments with an
+    /// invalid field name or without a `=` separator. Returns the `VorbisComment` and the comments
+    /// which were left out.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::VorbisComment;
+    ///
+    /// let mut bytes = vec![0, 0, 0, 0, 2, 0, 0, 0];
+    /// for comment in &["TITLE=Title", "T\u{C4}G=value"] {
+    ///     bytes.extend(&(comment.len() as u32).to_le_bytes());
+    ///     bytes.extend(comment.as_bytes());
+    /// }
+    ///
+    /// let (comments, invalid) = VorbisComment::from_bytes_lenient(&bytes).unwrap();
+    /// assert_eq!(comments.title(), Some(&vec!["Title".to_owned()]));
+    /// assert_eq!(invalid, vec!["T\u{C4}G=value"]);
+    /// ```
+    pub fn from_bytes_lenient(bytes: &[u8]) -> Result<(VorbisComment, Vec<String>)> {
+        VorbisComment::parse(bytes, true)
+    }
+
+    fn parse(bytes: &[u8], lenient: bool) -> Result<(VorbisComment, Vec<String>)> {
+        let mut vorbis = VorbisComment::new();
+        let mut invalid = Vec::new();
+        let mut i = 0;
+
+        let vendor_length = u32::from_le_bytes((&bytes[i..i + 4]).try_into().unwrap()) as usize;
+        i += 4;
+
+        vorbis.vendor_string = String::from_utf8(bytes[i..i + vendor_length].to_vec())?;
+        i += vendor_length;
+
+        let num_comments = u32::from_le_bytes((&bytes[i..i + 4]).try_into().unwrap());
+        i += 4;
+
+        for _ in 0..num_comments {
+            let comment_length =
+                u32::from_le_bytes((&bytes[i..i + 4]).try_into().unwrap()) as usize;
+            i += 4;
+
+            let comments = String::from_utf8(bytes[i..i + comment_length].to_vec())?;
+            i += comment_length;
+
+            let comments_split: Vec<&str> = comments.splitn(2, '=').collect();
+            if comments_split.len() != 2 || (lenient && !FieldName::is_valid(comments_split[0])) {
+                if lenient {
+                    invalid.push(comments);
+                    continue;
+                }
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "vorbis comment does not contain a '=' separator",
+                ));
+            }
+            let key = comments_split[0].to_ascii_uppercase();
+            let value = comments_split[1].to_owned();
+
+            vorbis
+                .comments
+                .entry(key)
+                .or_insert_with(|| Vec::with_capacity(1))
+                .push(value);
+        }
+
+        Ok((vorbis, invalid))
+    }
+
+    /// Attempts to return a vector representation of the vorbis comment suitable for writing to a
+    /// file. Returns an `ErrorKind::InvalidFieldNames` error holding the invalid keys if any field
+    /// name is invalid, see `remove_invalid_keys`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let invalid = self.invalid_keys();
+        if !invalid.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidFieldNames(invalid.into_iter().map(str::to_owned).collect()),
+                "vorbis comment contains an invalid field name",
+            ));
+        }
+
+        let mut bytes = Vec::new();
+
+        let vendor_string = self.vendor_string.clone().into_bytes();
+
+        bytes.extend((vendor_string.len() as u32).to_le_bytes().iter());
+        bytes.extend(vendor_string);
+
+        bytes.extend(
+            (self
+                .comments
+                .values()
+                .fold(0u32, |acc, l| acc + l.len() as u32))
+            .to_le_bytes()
+            .iter(),
+        );
+
+        for (key, list) in self.comments.iter() {
+            for value in list.iter() {
+                let comment_string = format!("{}={}", key, value);
+                let comment = comment_string.into_bytes();
+                bytes.extend((comment.len() as u32).to_le_bytes().iter());
+                bytes.extend(comment);
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    /// Returns the keys which are not valid field names, in sorted order.
+    pub fn invalid_keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = self
+            .comments
+            .keys()
+            .map(|key| &key[..])
+            .filter(|key| !FieldName::is_valid(key))
+            .collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    /// Removes the comments whose keys are not valid field names, so that the vorbis comment can
+    /// be written. Returns the removed comments in the form `KEY=value`, in the order of their
+    /// keys.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::VorbisComment;
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set("T\u{C4}G", vec!["value"]);
+    /// comments.set("TITLE", vec!["Title"]);
+    /// assert!(comments.to_bytes().is_err());
+    ///
+    /// assert_eq!(comments.remove_invalid_keys(), vec!["T\u{C4}G=value"]);
+    /// assert!(comments.to_bytes().is_ok());
+    /// ```
+    pub fn remove_invalid_keys(&mut self) -> Vec<String> {
+        let keys: Vec<String> = self.invalid_keys().into_iter().map(str::to_owned).collect();
+        let mut removed = Vec::new();
+        for key in keys {
+            if let Some(values) = self.comments.remove(&key) {
+                removed.extend(values.iter().map(|value| format!("{}={}", key, value)));
+            }
+        }
+        removed
+    }
+
+    /// Returns a reference to the vector of comments for the specified key.
+    pub fn get(&self, key: &str) -> Option<&Vec<String>> {
+        self.comments.get(key)
+    }
+
+    /// Sets the comments for the specified key. Any previous values under the key will be removed.
+    pub fn set<K: Into<String>, V: Into<String>>(&mut self, key: K, values: Vec<V>) {
+        let key_owned = key.into();
+        self.remove(&key_owned[..]);
+        self.comments
+            .insert(key_owned, values.into_iter().map(|s| s.into()).collect());
+    }
+
+    /// Sets the comments for the specified key after checking that it is a valid field name. Any
+    /// previous values under the key will be removed.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::VorbisComment;
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// assert!(comments.try_set("ARTIST", vec!["Artist"]).is_ok());
+    /// assert!(comments.try_set("FOO=BAR", vec!["value"]).is_err());
+    /// assert!(comments.get("FOO=BAR").is_none());
+    /// ```
+    pub fn try_set<K: Into<String>, V: Into<String>>(
+        &mut self,
+        key: K,
+        values: Vec<V>,
+    ) -> Result<()> {
+        let key = FieldName::new(key)?;
+        self.set(key, values);
+        Ok(())
+    }
+
+    /// Removes the comments for the specified key.
     pub fn remove(&mut self, key: &str) {
         self.comments.remove(key);
     }
 
-    /// Removes any matching key/value pairs.
-    pub fn remove_pair(&mut self, key: &str, value: &str) {
-        if let Some(list) = self.comments.get_mut(key) {
-            list.retain(|s| &s[..] != value);
-        }
+    /// Returns the entry of the key for in-place modification of its values. The key is converted
+    /// to upper case, as when reading comments. An `Error::InvalidInput` is returned if the key is
+    /// not a valid field name, see `FieldName`.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::VorbisComment;
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.entry("artist")?.push_value("A").push_value("C");
+    /// comments.entry("Artist")?.insert_at(1, "B");
+    /// let artists = comments.get("ARTIST").unwrap();
+    /// assert_eq!(artists, &["A", "B", "C"]);
+    ///
+    /// comments.entry("ARTIST")?.retain_values(|value| value == "D");
+    /// assert!(comments.get("ARTIST").is_none());
+    /// assert!(comments.entry("FOO=BAR").is_err());
+    /// # Ok::<(), metaflac::Error>(())
+    /// ```
+    pub fn entry<K: Into<String>>(&mut self, key: K) -> Result<Entry<'_>> {
+        Ok(Entry {
+            key: FieldName::new(key.into().to_ascii_uppercase())?.into(),
+            comment: self,
+        })
+    }
+
+    /// Removes any matching key/value pairs.
+    pub fn remove_pair(&mut self, key: &str, value: &str) {
+        if let Some(list) = self.comments.get_mut(key) {
+            list.retain(|s| &s[..] != value);
+        }
+
+        let mut num_values = 0;
+        if let Some(values) = self.get(key) {
+            num_values = values.len();
+        }
+        if num_values == 0 {
+            self.remove(key)
+        }
+    }
+
+    // Getters/Setters {{{
+    /// Returns a reference to the vector of values with the ARTIST key.
+    pub fn artist(&self) -> Option<&Vec<String>> {
+        self.get("ARTIST")
+    }
+
+    /// Sets the values for the ARTIST key. Any ARTISTSORT comments are kept, see
+    /// `remove_sort_field`.
+    pub fn set_artist<T: Into<String>>(&mut self, artists: Vec<T>) {
+        self.set("ARTIST", artists);
+    }
+
+    /// Removes all values with the ARTIST key. This will result in any ARTISTSORT comments being
+    /// removed as well.
+    pub fn remove_artist(&mut self) {
+        self.remove("ARTISTSORT");
+        self.remove("ARTIST");
+    }
+
+    /// Returns a reference to the vector of values with the ALBUM key.
+    pub fn album(&self) -> Option<&Vec<String>> {
+        self.get("ALBUM")
+    }
+
+    /// Sets the values for the ALBUM key. Any ALBUMSORT comments are kept, see
+    /// `remove_sort_field`.
+    pub fn set_album<T: Into<String>>(&mut self, albums: Vec<T>) {
+        self.set("ALBUM", albums);
+    }
+
+    /// Removes all values with the ALBUM key. This will result in any ALBUMSORT comments being
+    /// removed as well.
+    pub fn remove_album(&mut self) {
+        self.remove("ALBUMSORT");
+        self.remove("ALBUM");
+    }
+
+    /// Returns a reference to the vector of values with the GENRE key.
+    pub fn genre(&self) -> Option<&Vec<String>> {
+        self.get("GENRE")
+    }
+
+    /// Sets the values for the GENRE key.
+    pub fn set_genre<T: Into<String>>(&mut self, genres: Vec<T>) {
+        self.set("GENRE", genres);
+    }
+
+    /// Removes all values with the GENRE key.
+    pub fn remove_genre(&mut self) {
+        self.remove("GENRE");
+    }
+
+    /// Returns reference to the vector of values with the TITLE key.
+    pub fn title(&self) -> Option<&Vec<String>> {
+        self.get("TITLE")
+    }
+
+    /// Sets the values for the TITLE key. Any TITLESORT comments are kept, see
+    /// `remove_sort_field`.
+    pub fn set_title<T: Into<String>>(&mut self, title: Vec<T>) {
+        self.set("TITLE", title);
+    }
+
+    /// Removes all values with the TITLE key. This will result in any TITLESORT comments being
+    /// removed as well.
+    pub fn remove_title(&mut self) {
+        self.remove("TITLESORT");
+        self.remove("TITLE");
+    }
+
+    /// Attempts to convert the first TRACKNUMBER comment to a `u32`. A value of the form
+    /// `n/total` is read as `n`.
+    pub fn track(&self) -> Option<u32> {
+        self.get("TRACKNUMBER")
+            .and_then(|values| parse_number(values, 0))
+    }
+
+    /// Sets the TRACKNUMBER comment.
+    pub fn set_track(&mut self, track: u32) {
+        self.set("TRACKNUMBER", vec![format!("{}", track)]);
+    }
+
+    /// Removes all values with the TRACKNUMBER key.
+    pub fn remove_track(&mut self) {
+        self.remove("TRACKNUMBER");
+    }
+
+    /// Attempts to convert the first TOTALTRACKS or TRACKTOTAL comment to a `u32`, falling back to
+    /// the total of a TRACKNUMBER value of the form `n/total`.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::VorbisComment;
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set("TRACKNUMBER", vec!["3/12"]);
+    /// assert_eq!(comments.track(), Some(3));
+    /// assert_eq!(comments.total_tracks(), Some(12));
+    ///
+    /// comments.set("TRACKTOTAL", vec!["13"]);
+    /// assert_eq!(comments.total_tracks(), Some(13));
+    /// ```
+    pub fn total_tracks(&self) -> Option<u32> {
+        self.total(StandardField::TrackNumber, StandardField::TotalTracks)
+    }
+
+    /// Sets the TOTALTRACKS comment, removing the values of its aliases.
+    pub fn set_total_tracks(&mut self, total_tracks: u32) {
+        self.set_field(
+            StandardField::TotalTracks,
+            vec![format!("{}", total_tracks)],
+        );
+    }
+
+    /// Removes all values with the TOTALTRACKS key or its aliases.
+    pub fn remove_total_tracks(&mut self) {
+        self.remove_field(StandardField::TotalTracks);
+    }
+
+    /// Attempts to convert the first DISCNUMBER comment to a `u32`. A value of the form `n/total`
+    /// is read as `n`.
+    pub fn disc(&self) -> Option<u32> {
+        self.get("DISCNUMBER")
+            .and_then(|values| parse_number(values, 0))
+    }
+
+    /// Attempts to convert the first DISCTOTAL or TOTALDISCS comment to a `u32`, falling back to
+    /// the total of a DISCNUMBER value of the form `n/total`.
+    pub fn total_discs(&self) -> Option<u32> {
+        self.total(StandardField::DiscNumber, StandardField::DiscTotal)
+    }
+
+    /// Returns the first DATE or YEAR value which can be parsed as a `PartialDate`.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::VorbisComment;
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set("DATE", vec!["unknown", "2004-05-17T10:00"]);
+    /// assert_eq!(comments.date().unwrap().to_string(), "2004-05-17T10:00");
+    /// assert_eq!(comments.release_year(), Some(2004));
+    /// ```
+    pub fn date(&self) -> Option<PartialDate> {
+        self.parse_date(StandardField::Date)
+    }
+
+    /// Sets the DATE comment in ISO 8601 form, removing the values of its aliases.
+    pub fn set_date(&mut self, date: PartialDate) {
+        self.set_field(StandardField::Date, vec![date.to_string()]);
+    }
+
+    /// Returns the first ORIGINALDATE or ORIGINALYEAR value which can be parsed as a
+    /// `PartialDate`.
+    pub fn original_date(&self) -> Option<PartialDate> {
+        self.parse_date(StandardField::OriginalDate)
+    }
+
+    /// Sets the ORIGINALDATE comment in ISO 8601 form, removing the values of its aliases.
+    pub fn set_original_date(&mut self, date: PartialDate) {
+        self.set_field(StandardField::OriginalDate, vec![date.to_string()]);
+    }
+
+    /// Returns the year of the release date, falling back to the year of the original release
+    /// date.
+    pub fn release_year(&self) -> Option<u16> {
+        self.date()
+            .or_else(|| self.original_date())
+            .map(|date| date.year)
+    }
+
+    fn parse_date(&self, field: StandardField) -> Option<PartialDate> {
+        self.get_field(field)?
+            .iter()
+            .find_map(|value| PartialDate::parse(value).ok())
+    }
+
+    fn total(&self, number: StandardField, total: StandardField) -> Option<u32> {
+        self.get_field(total)
+            .and_then(|values| parse_number(values, 0))
+            .or_else(|| {
+                self.get_field(number)
+                    .and_then(|values| parse_number(values, 1))
+            })
+    }
+
+    /// Returns a reference to the vector of values with the ALBUMARTIST key.
+    pub fn album_artist(&self) -> Option<&Vec<String>> {
+        self.get("ALBUMARTIST")
+    }
+
+    /// Sets the values for the ALBUMARTIST key. Any ALBUMARTISTSORT comments are kept, see
+    /// `remove_sort_field`.
+    pub fn set_album_artist<T: Into<String>>(&mut self, album_artists: Vec<T>) {
+        self.set("ALBUMARTIST", album_artists);
+    }
+
+    /// Removes all values with the ALBUMARTIST key. This will result in any ALBUMARTISTSORT
+    /// comments being removed as well.
+    pub fn remove_album_artist(&mut self) {
+        self.remove("ALBUMARTISTSORT");
+        self.remove("ALBUMARTIST");
+    }
+
+    /// Returns a reference to the vector of values with the ARTISTSORT key.
+    pub fn artist_sort(&self) -> Option<&Vec<String>> {
+        self.get("ARTISTSORT")
+    }
+
+    /// Sets the values for the ARTISTSORT key.
+    pub fn set_artist_sort<T: Into<String>>(&mut self, artists: Vec<T>) {
+        self.set("ARTISTSORT", artists);
+    }
+
+    /// Removes all values with the ARTISTSORT key.
+    pub fn remove_artist_sort(&mut self) {
+        self.remove("ARTISTSORT");
+    }
+
+    /// Returns a reference to the vector of values with the ALBUMSORT key.
+    pub fn album_sort(&self) -> Option<&Vec<String>> {
+        self.get("ALBUMSORT")
+    }
+
+    /// Sets the values for the ALBUMSORT key.
+    pub fn set_album_sort<T: Into<String>>(&mut self, albums: Vec<T>) {
+        self.set("ALBUMSORT", albums);
+    }
+
+    /// Removes all values with the ALBUMSORT key.
+    pub fn remove_album_sort(&mut self) {
+        self.remove("ALBUMSORT");
+    }
+
+    /// Returns a reference to the vector of values with the TITLESORT key.
+    pub fn title_sort(&self) -> Option<&Vec<String>> {
+        self.get("TITLESORT")
+    }
+
+    /// Sets the values for the TITLESORT key.
+    pub fn set_title_sort<T: Into<String>>(&mut self, title: Vec<T>) {
+        self.set("TITLESORT", title);
+    }
+
+    /// Removes all values with the TITLESORT key.
+    pub fn remove_title_sort(&mut self) {
+        self.remove("TITLESORT");
+    }
+
+    /// Returns a reference to the vector of values with the ALBUMARTISTSORT key.
+    pub fn album_artist_sort(&self) -> Option<&Vec<String>> {
+        self.get("ALBUMARTISTSORT")
+    }
+
+    /// Sets the values for the ALBUMARTISTSORT key.
+    pub fn set_album_artist_sort<T: Into<String>>(&mut self, album_artists: Vec<T>) {
+        self.set("ALBUMARTISTSORT", album_artists);
+    }
+
+    /// Removes all values with the ALBUMARTISTSORT key.
+    pub fn remove_album_artist_sort(&mut self) {
+        self.remove("ALBUMARTISTSORT");
+    }
+
+    /// Returns a reference to the vector of values with the COMPOSERSORT key.
+    pub fn composer_sort(&self) -> Option<&Vec<String>> {
+        self.get("COMPOSERSORT")
+    }
+
+    /// Sets the values for the COMPOSERSORT key.
+    pub fn set_composer_sort<T: Into<String>>(&mut self, composers: Vec<T>) {
+        self.set("COMPOSERSORT", composers);
+    }
+
+    /// Removes all values with the COMPOSERSORT key.
+    pub fn remove_composer_sort(&mut self) {
+        self.remove("COMPOSERSORT");
+    }
+
+    /// Removes the sort names of the field, such as ARTISTSORT for ARTIST, which may no longer
+    /// match after the field has been changed.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::{StandardField, VorbisComment};
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set_artist_sort(vec!["Beatles, The"]);
+    ///
+    /// comments.set_artist(vec!["The Beatles"]);
+    /// assert!(comments.artist_sort().is_some());
+    ///
+    /// comments.set_artist(vec!["The Rolling Stones"]);
+    /// comments.remove_sort_field(StandardField::Artist);
+    /// assert!(comments.artist_sort().is_none());
+    /// ```
+    pub fn remove_sort_field(&mut self, field: StandardField) {
+        if let Some(sort_field) = field.sort_field() {
+            self.remove_field(sort_field);
+        }
+    }
+
+    /// Returns a reference to the vector of values with the LYRICS key.
+    pub fn lyrics(&self) -> Option<&Vec<String>> {
+        self.get("LYRICS")
+    }
+
+    /// Sets the values for the LYRICS key.
+    pub fn set_lyrics<T: Into<String>>(&mut self, lyrics: Vec<T>) {
+        self.set("LYRICS", lyrics);
+    }
+
+    /// Removes all values with the LYRICS key.
+    pub fn remove_lyrics(&mut self) {
+        self.remove("LYRICS");
+    }
+    // }}}
+
+    // Pictures {{{
+    /// Attempts to decode the pictures stored in the comments. Each METADATA_BLOCK_PICTURE value
+    /// is a base64 encoded picture block. Each legacy COVERART value is base64 encoded image data,
+    /// decoded as a front cover with the MIME type of the COVERARTMIME value at the same index.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::{Picture, PictureType, VorbisComment};
+    ///
+    /// let mut picture = Picture::new();
+    /// picture.picture_type = PictureType::CoverFront;
+    /// picture.mime_type = "image/png".to_owned();
+    /// picture.data = vec![0x89, 0x50, 0x4E, 0x47];
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.push_encoded_picture(&picture);
+    /// assert_eq!(comments.encoded_pictures().unwrap(), vec![picture]);
+    /// ```
+    pub fn encoded_pictures(&self) -> Result<Vec<Picture>> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "vorbis comment contains an invalid picture",
+            )
+        };
+
+        let mut pictures = Vec::new();
+        for value in self.get("METADATA_BLOCK_PICTURE").into_iter().flatten() {
+            let bytes = crate::base64::decode(value).ok_or_else(invalid)?;
+            if !is_picture_length(&bytes[..]) {
+                return Err(invalid());
+            }
+            pictures.push(Picture::from_bytes(&bytes[..])?);
+        }
+
+        let mime_types = self.get("COVERARTMIME");
+        for (i, value) in self.get("COVERART").into_iter().flatten().enumerate() {
+            let mut picture = Picture::new();
+            picture.picture_type = PictureType::CoverFront;
+            picture.mime_type = mime_types
+                .and_then(|mime_types| mime_types.get(i))
+                .cloned()
+                .unwrap_or_default();
+            picture.data = crate::base64::decode(value).ok_or_else(invalid)?;
+            pictures.push(picture);
+        }
+
+        Ok(pictures)
+    }
+
+    /// Adds the picture as a base64 encoded METADATA_BLOCK_PICTURE value.
+    pub fn push_encoded_picture(&mut self, picture: &Picture) {
+        self.comments
+            .entry("METADATA_BLOCK_PICTURE".to_owned())
+            .or_default()
+            .push(crate::base64::encode(&picture.to_bytes()[..]));
+    }
+
+    /// Removes all values with the METADATA_BLOCK_PICTURE, COVERART and COVERARTMIME keys.
+    pub fn remove_encoded_pictures(&mut self) {
+        self.remove("METADATA_BLOCK_PICTURE");
+        self.remove("COVERART");
+        self.remove("COVERARTMIME");
+    }
+    // }}}
+
+    // Standard fields {{{
+    /// Returns a reference to the vector of values of the standard field.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::{StandardField, VorbisComment};
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set_field(StandardField::DiscNumber, vec!["2"]);
+    /// assert_eq!(comments.get_field(StandardField::DiscNumber), Some(&vec!["2".to_owned()]));
+    /// assert_eq!(comments.get("DISCNUMBER"), Some(&vec!["2".to_owned()]));
+    ///
+    /// comments.remove_field(StandardField::DiscNumber);
+    /// assert!(comments.get_field(StandardField::DiscNumber).is_none());
+    /// ```
+    pub fn get_field(&self, field: StandardField) -> Option<&Vec<String>> {
+        self.find_field(field).map(|(_, values)| values)
+    }
+
+    /// Returns the field name and values of the first alias of the standard field which has
+    /// values.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::{StandardField, VorbisComment};
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set("YEAR", vec!["1999"]);
+    /// assert_eq!(
+    ///     comments.find_field(StandardField::Date),
+    ///     Some(("YEAR", &vec!["1999".to_owned()]))
+    /// );
+    /// ```
+    pub fn find_field(&self, field: StandardField) -> Option<(&'static str, &Vec<String>)> {
+        field
+            .aliases()
+            .iter()
+            .find_map(|&key| self.get(key).map(|values| (key, values)))
+    }
 
-        let mut num_values = 0;
-        if let Some(values) = self.get(key) {
-            num_values = values.len();
+    /// Sets the values of the standard field under `StandardField::key`, removing the values of
+    /// its aliases.
+    pub fn set_field<T: Into<String>>(&mut self, field: StandardField, values: Vec<T>) {
+        self.set_field_with(field, values, &WriteConvention::new());
+    }
+
+    /// Sets the values of the standard field following the convention.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::{StandardField, VorbisComment, WriteConvention};
+    ///
+    /// let convention = WriteConvention::new()
+    ///     .with_key(StandardField::TotalTracks, "TRACKTOTAL")
+    ///     .unwrap();
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set("TOTALTRACKS", vec!["10"]);
+    /// comments.set_field_with(StandardField::TotalTracks, vec!["12"], &convention);
+    /// assert_eq!(comments.get("TRACKTOTAL"), Some(&vec!["12".to_owned()]));
+    /// assert!(comments.get("TOTALTRACKS").is_none());
+    /// ```
+    pub fn set_field_with<T: Into<String>>(
+        &mut self,
+        field: StandardField,
+        values: Vec<T>,
+        convention: &WriteConvention,
+    ) {
+        if convention.remove_aliases {
+            for key in field.aliases() {
+                self.remove(key);
+            }
         }
-        if num_values == 0 {
-            self.remove(key)
+        self.set(convention.key(field), values);
+    }
+
+    /// Removes all values of the standard field and its aliases.
+    pub fn remove_field(&mut self, field: StandardField) {
+        for key in field.aliases() {
+            self.remove(key);
         }
     }
 
-    // Getters/Setters {{{
-    /// Returns a reference to the vector of values with the ARTIST key.
-    pub fn artist(&self) -> Option<&Vec<String>> {
-        self.get("ARTIST")
+    /// Moves the values of standard fields stored under aliases to the field names of the
+    /// convention. The values of the field name of the convention are kept if it has values.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::{StandardField, VorbisComment, WriteConvention};
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set("YEAR", vec!["1999"]);
+    /// comments.set("TRACKNUMBER", vec!["3/12"]);
+    /// comments.normalize_fields(&WriteConvention::new());
+    ///
+    /// assert_eq!(comments.get("DATE"), Some(&vec!["1999".to_owned()]));
+    /// assert_eq!(comments.get("TRACKNUMBER"), Some(&vec!["3".to_owned()]));
+    /// assert_eq!(comments.get("TOTALTRACKS"), Some(&vec!["12".to_owned()]));
+    /// assert!(comments.get("YEAR").is_none());
+    /// ```
+    pub fn normalize_fields(&mut self, convention: &WriteConvention) {
+        for &field in StandardField::ALL {
+            let key = convention.key(field);
+            if self.get(key).is_none() {
+                if let Some((_, values)) = self.find_field(field) {
+                    let values = values.clone();
+                    self.set(key, values);
+                }
+            }
+            if convention.remove_aliases {
+                for alias in field.aliases().iter().filter(|&&alias| alias != key) {
+                    self.remove(alias);
+                }
+            }
+        }
+
+        if convention.split_totals {
+            for &(number, total) in &[
+                (StandardField::TrackNumber, StandardField::TotalTracks),
+                (StandardField::DiscNumber, StandardField::DiscTotal),
+            ] {
+                let key = convention.key(number);
+                let (numbers, totals): (Vec<String>, Vec<String>) = match self.get(key) {
+                    Some(values) if values.iter().any(|value| value.contains('/')) => values
+                        .iter()
+                        .map(|value| {
+                            let mut split = value.splitn(2, '/');
+                            let n = split.next().unwrap_or("").trim().to_owned();
+                            (n, split.next().unwrap_or("").trim().to_owned())
+                        })
+                        .unzip(),
+                    _ => continue,
+                };
+                self.set(key, numbers);
+                let totals: Vec<String> = totals.into_iter().filter(|t| !t.is_empty()).collect();
+                if self.get_field(total).is_none() && !totals.is_empty() {
+                    self.set(convention.key(total), totals);
+                }
+            }
+        }
+
+        if convention.iso_dates {
+            for &field in &[StandardField::Date, StandardField::OriginalDate] {
+                let key = convention.key(field);
+                let values: Vec<String> = match self.get(key) {
+                    Some(values) => values
+                        .iter()
+                        .map(|value| {
+                            PartialDate::parse(value).map_or(value.clone(), |date| date.to_string())
+                        })
+                        .collect(),
+                    None => continue,
+                };
+                self.set(key, values);
+            }
+        }
     }
 
-    /// Sets the values for the ARTIST key. This will result in any ARTISTSORT comment being
-    /// removed.
-    pub fn set_artist<T: Into<String>>(&mut self, artists: Vec<T>) {
-        self.remove("ARTISTSORT");
-        self.set("ARTIST", artists);
+    /// Returns an iterator over the standard fields which have values, in the order of
+    /// `StandardField::ALL`.
+    pub fn standard_fields(&self) -> impl Iterator<Item = (StandardField, &Vec<String>)> {
+        StandardField::ALL
+            .iter()
+            .filter_map(move |&field| self.get_field(field).map(|values| (field, values)))
     }
 
-    /// Removes all values with the ARTIST key. This will result in any ARTISTSORT comments being
-    /// removed as well.
-    pub fn remove_artist(&mut self) {
-        self.remove("ARTISTSORT");
-        self.remove("ARTIST");
+    /// Returns an iterator over the fields which are not standard fields or their aliases,
+    /// sorted by key.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::{StandardField, VorbisComment};
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set("TITLE", vec!["Title"]);
+    /// comments.set("MY_FIELD", vec!["value"]);
+    ///
+    /// let standard: Vec<_> = comments.standard_fields().map(|(field, _)| field).collect();
+    /// assert_eq!(standard, vec![StandardField::Title]);
+    /// let custom: Vec<_> = comments.custom_fields().map(|(key, _)| key).collect();
+    /// assert_eq!(custom, vec!["MY_FIELD"]);
+    /// ```
+    pub fn custom_fields(&self) -> impl Iterator<Item = (&str, &Vec<String>)> {
+        let mut fields: Vec<(&str, &Vec<String>)> = self
+            .comments
+            .iter()
+            .filter(|(key, _)| {
+                !StandardField::ALL
+                    .iter()
+                    .any(|field| field.aliases().contains(&&key[..]))
+            })
+            .map(|(key, values)| (&key[..], values))
+            .collect();
+        fields.sort_unstable_by_key(|&(key, _)| key);
+        fields.into_iter()
     }
+    // }}}
 
-    /// Returns a reference to the vector of values with the ALBUM key.
-    pub fn album(&self) -> Option<&Vec<String>> {
-        self.get("ALBUM")
+    // Multiple values {{{
+    /// Returns the values of the field split according to the policy, or an empty vector if the
+    /// field has no values.
+    pub fn get_split(&self, key: &str, policy: &MultiValuePolicy) -> Vec<&str> {
+        match self.get(key) {
+            Some(values) => policy.field(key).split(values),
+            None => Vec::new(),
+        }
     }
 
-    /// Sets the values for the ALBUM key. This will result in any ALBUMSORT comments being
-    /// removed.
-    pub fn set_album<T: Into<String>>(&mut self, albums: Vec<T>) {
-        self.remove("ALBUMSORT");
-        self.set("ALBUM", albums);
+    /// Sets the values of the field, joined according to the policy.
+    pub fn set_with_policy<K: Into<String>, V: AsRef<str>>(
+        &mut self,
+        key: K,
+        values: &[V],
+        policy: &MultiValuePolicy,
+    ) {
+        let key = key.into();
+        let values = policy.field(&key).join(values);
+        self.set(key, values);
     }
 
-    /// Removes all values with the ALBUM key. This will result in any ALBUMSORT comments being
-    /// removed as well.
-    pub fn remove_album(&mut self) {
-        self.remove("ALBUMSORT");
-        self.remove("ALBUM");
+    /// Splits and joins the values of all fields according to the policy.
+    pub fn apply_multi_value_policy(&mut self, policy: &MultiValuePolicy) {
+        for (key, values) in self.comments.iter_mut() {
+            let field = policy.field(key);
+            let split: Vec<String> = field.split(values).into_iter().map(str::to_owned).collect();
+            *values = field.join(&split);
+        }
+        self.comments.retain(|_, values| !values.is_empty());
     }
+    // }}}
 
-    /// Returns a reference to the vector of values with the GENRE key.
-    pub fn genre(&self) -> Option<&Vec<String>> {
-        self.get("GENRE")
+    // ReplayGain {{{
+    /// Returns the ReplayGain values, or `None` if none are set. Gains missing from the
+    /// REPLAYGAIN_* fields are converted from the R128_*_GAIN fields. Values which cannot be
+    /// parsed are ignored.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::VorbisComment;
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set("REPLAYGAIN_TRACK_GAIN", vec!["-6,54"]);
+    /// comments.set("R128_ALBUM_GAIN", vec!["-1280"]);
+    ///
+    /// let gain = comments.replay_gain().unwrap();
+    /// assert_eq!(gain.track_gain, Some(-6.54));
+    /// assert_eq!(gain.album_gain, Some(0.0));
+    /// ```
+    pub fn replay_gain(&self) -> Option<ReplayGain> {
+        let value = |key: &str, parse: fn(&str) -> Option<f64>| {
+            self.get(key)
+                .and_then(|values| values.iter().find_map(|value| parse(value)))
+        };
+        let r128 = |key: &str, reference: f64| {
+            self.get(key)
+                .and_then(|values| values.iter().find_map(|value| value.trim().parse().ok()))
+                .map(|value| ReplayGain::from_r128(value, reference))
+        };
+
+        let mut gain = ReplayGain {
+            track_gain: value("REPLAYGAIN_TRACK_GAIN", ReplayGain::parse_gain),
+            track_peak: value("REPLAYGAIN_TRACK_PEAK", ReplayGain::parse_peak),
+            album_gain: value("REPLAYGAIN_ALBUM_GAIN", ReplayGain::parse_gain),
+            album_peak: value("REPLAYGAIN_ALBUM_PEAK", ReplayGain::parse_peak),
+            reference_loudness: value(
+                "REPLAYGAIN_REFERENCE_LOUDNESS",
+                ReplayGain::parse_reference_loudness,
+            ),
+        };
+        let reference = gain.reference();
+        gain.track_gain = gain
+            .track_gain
+            .or_else(|| r128("R128_TRACK_GAIN", reference));
+        gain.album_gain = gain
+            .album_gain
+            .or_else(|| r128("R128_ALBUM_GAIN", reference));
+
+        if gain.is_empty() {
+            None
+        } else {
+            Some(gain)
+        }
     }
 
-    /// Sets the values for the GENRE key.
-    pub fn set_genre<T: Into<String>>(&mut self, genres: Vec<T>) {
-        self.set("GENRE", genres);
+    /// Sets the REPLAYGAIN_* fields, removing all ReplayGain and R128 gain fields first.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::{ReplayGain, VorbisComment};
+    ///
+    /// let mut gain = ReplayGain::new();
+    /// gain.track_gain = Some(-6.5);
+    /// gain.track_peak = Some(0.9883);
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set_replay_gain(&gain);
+    /// assert_eq!(comments.get("REPLAYGAIN_TRACK_GAIN"), Some(&vec!["-6.50 dB".to_owned()]));
+    /// assert_eq!(comments.get("REPLAYGAIN_TRACK_PEAK"), Some(&vec!["0.988300".to_owned()]));
+    /// ```
+    pub fn set_replay_gain(&mut self, gain: &ReplayGain) {
+        self.remove_replay_gain();
+        let fields = [
+            (
+                "REPLAYGAIN_TRACK_GAIN",
+                gain.track_gain.map(ReplayGain::format_gain),
+            ),
+            (
+                "REPLAYGAIN_TRACK_PEAK",
+                gain.track_peak.map(ReplayGain::format_peak),
+            ),
+            (
+                "REPLAYGAIN_ALBUM_GAIN",
+                gain.album_gain.map(ReplayGain::format_gain),
+            ),
+            (
+                "REPLAYGAIN_ALBUM_PEAK",
+                gain.album_peak.map(ReplayGain::format_peak),
+            ),
+            (
+                "REPLAYGAIN_REFERENCE_LOUDNESS",
+                gain.reference_loudness
+                    .map(ReplayGain::format_reference_loudness),
+            ),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                self.set(key, vec![value]);
+            }
+        }
     }
 
-    /// Removes all values with the GENRE key.
-    pub fn remove_genre(&mut self) {
-        self.remove("GENRE");
+    /// Sets the R128_TRACK_GAIN and R128_ALBUM_GAIN fields used by Opus streams, removing all
+    /// ReplayGain and R128 gain fields first. Peaks can not be stored in R128 fields.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::{ReplayGain, VorbisComment};
+    ///
+    /// let mut gain = ReplayGain::new();
+    /// gain.album_gain = Some(-1.0);
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set_r128_gain(&gain);
+    /// assert_eq!(comments.get("R128_ALBUM_GAIN"), Some(&vec!["-1536".to_owned()]));
+    /// ```
+    pub fn set_r128_gain(&mut self, gain: &ReplayGain) {
+        self.remove_replay_gain();
+        let reference = gain.reference();
+        for (key, value) in [
+            ("R128_TRACK_GAIN", gain.track_gain),
+            ("R128_ALBUM_GAIN", gain.album_gain),
+        ] {
+            if let Some(value) = value {
+                self.set(key, vec![ReplayGain::to_r128(value, reference).to_string()]);
+            }
+        }
     }
 
-    /// Returns reference to the vector of values with the TITLE key.
-    pub fn title(&self) -> Option<&Vec<String>> {
-        self.get("TITLE")
+    /// Removes all ReplayGain and R128 gain fields.
+    pub fn remove_replay_gain(&mut self) {
+        for key in &[
+            "REPLAYGAIN_TRACK_GAIN",
+            "REPLAYGAIN_TRACK_PEAK",
+            "REPLAYGAIN_ALBUM_GAIN",
+            "REPLAYGAIN_ALBUM_PEAK",
+            "REPLAYGAIN_REFERENCE_LOUDNESS",
+            "R128_TRACK_GAIN",
+            "R128_ALBUM_GAIN",
+        ] {
+            self.remove(key);
+        }
     }
+    // }}}
 
-    /// Sets the values for the TITLE key. This will result in any TITLESORT comments being
-    /// removed.
-    pub fn set_title<T: Into<String>>(&mut self, title: Vec<T>) {
-        self.remove("TITLESORT");
-        self.set("TITLE", title);
+    // Identifiers {{{
+    /// Returns the identifier of the field, or `None` if the field has no values. Returns an
+    /// error if the first value is not a valid identifier.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::{IdentifierField, VorbisComment};
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set("MUSICBRAINZ_TRACKID", vec!["5b11f4ce-a62d-471e-81fc-a69a8278c7da"]);
+    /// comments.set("MUSICBRAINZ_ALBUMID", vec!["unknown"]);
+    ///
+    /// let id = comments.identifier(IdentifierField::TrackId).unwrap().unwrap();
+    /// assert_eq!(id.as_str(), "5b11f4ce-a62d-471e-81fc-a69a8278c7da");
+    /// assert!(comments.identifier(IdentifierField::AlbumId).unwrap().is_err());
+    /// assert!(comments.identifier(IdentifierField::ReleaseGroupId).is_none());
+    /// ```
+    pub fn identifier(&self, field: IdentifierField) -> Option<Result<Identifier>> {
+        self.get(field.key())
+            .and_then(|values| values.first())
+            .map(|value| Identifier::new(value))
     }
 
-    /// Removes all values with the TITLE key. This will result in any TITLESORT comments being
-    /// removed as well.
-    pub fn remove_title(&mut self) {
-        self.remove("TITLESORT");
-        self.remove("TITLE");
+    /// Returns all identifiers of the field. Values holding several identifiers separated by
+    /// `/`, `;` or `,` are split, as written by some taggers for multiple artists. Returns an
+    /// error if any value is not a valid identifier.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::{IdentifierField, VorbisComment};
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set(
+    ///     "MUSICBRAINZ_ARTISTID",
+    ///     vec!["a74b1b7f-71a5-4011-9441-d0b5e4122711; 8bfac288-ccc5-448d-9573-c33ea2aa5c30"],
+    /// );
+    /// assert_eq!(comments.identifiers(IdentifierField::ArtistId).unwrap().len(), 2);
+    /// ```
+    pub fn identifiers(&self, field: IdentifierField) -> Result<Vec<Identifier>> {
+        self.get(field.key())
+            .map_or(&[][..], |values| &values[..])
+            .iter()
+            .flat_map(|value| split_identifiers(value))
+            .map(Identifier::new)
+            .collect()
     }
 
-    /// Attempts to convert the first TRACKNUMBER comment to a `u32`.
-    pub fn track(&self) -> Option<u32> {
-        self.get("TRACKNUMBER").and_then(|s| {
-            if !s.is_empty() {
-                s[0].parse::<u32>().ok()
-            } else {
-                None
-            }
-        })
+    /// Sets the field to the identifier.
+    pub fn set_identifier(&mut self, field: IdentifierField, identifier: &Identifier) {
+        self.set(field.key(), vec![identifier.as_str()]);
     }
 
-    /// Sets the TRACKNUMBER comment.
-    pub fn set_track(&mut self, track: u32) {
-        self.set("TRACKNUMBER", vec![format!("{}", track)]);
+    /// Sets the field to the identifiers, storing one identifier per value.
+    pub fn set_identifiers(&mut self, field: IdentifierField, identifiers: &[Identifier]) {
+        self.set(
+            field.key(),
+            identifiers.iter().map(Identifier::as_str).collect(),
+        );
     }
 
-    /// Removes all values with the TRACKNUMBER key.
-    pub fn remove_track(&mut self) {
-        self.remove("TRACKNUMBER");
+    /// Removes all values of the field.
+    pub fn remove_identifier(&mut self, field: IdentifierField) {
+        self.remove(field.key());
     }
 
-    /// Attempts to convert the first TOTALTRACKS comment to a `u32`.
-    pub fn total_tracks(&self) -> Option<u32> {
-        self.get("TOTALTRACKS").and_then(|s| {
-            if !s.is_empty() {
-                s[0].parse::<u32>().ok()
-            } else {
-                None
+    /// Returns the problems with the identifier fields: required fields which have no values,
+    /// values of any identifier field which are not valid identifiers, and single valued fields
+    /// with several values.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::{IdentifierField, IdentifierIssue, VorbisComment};
+    ///
+    /// let mut comments = VorbisComment::new();
+    /// comments.set("ACOUSTID_ID", vec!["0"]);
+    ///
+    /// assert_eq!(
+    ///     comments.identifier_issues(&[IdentifierField::TrackId]),
+    ///     vec![
+    ///         IdentifierIssue::Missing(IdentifierField::TrackId),
+    ///         IdentifierIssue::Malformed(IdentifierField::AcoustId, "0".to_owned()),
+    ///     ]
+    /// );
+    /// ```
+    pub fn identifier_issues(&self, required: &[IdentifierField]) -> Vec<IdentifierIssue> {
+        let mut issues = Vec::new();
+        for &field in IdentifierField::ALL {
+            let values = match self.get(field.key()) {
+                Some(values) => values,
+                None => {
+                    if required.contains(&field) {
+                        issues.push(IdentifierIssue::Missing(field));
+                    }
+                    continue;
+                }
+            };
+
+            let mut count = 0;
+            for value in values.iter().flat_map(|value| split_identifiers(value)) {
+                count += 1;
+                if !Identifier::is_valid(value) {
+                    issues.push(IdentifierIssue::Malformed(field, value.to_owned()));
+                }
             }
-        })
+            if count > 1 && !field.is_multi_valued() {
+                issues.push(IdentifierIssue::Multiple(field));
+            }
+        }
+        issues
     }
+    // }}}
+}
 
-    /// Sets the TOTALTRACKS comment.
-    pub fn set_total_tracks(&mut self, total_tracks: u32) {
-        self.set("TOTALTRACKS", vec![format!("{}", total_tracks)]);
+/// Splits a value holding several identifiers separated by `/`, `;` or `,`. Empty values and
+/// parts are kept, so that they are reported as malformed.
+fn split_identifiers(value: &str) -> impl Iterator<Item = &str> {
+    value.split(['/', ';', ',']).map(str::trim)
+}
+
+// Entry {{{
+/// The values of a key of a vorbis comment, see `VorbisComment::entry`. The key is removed when
+/// its last value is removed.
+#[derive(Debug)]
+pub struct Entry<'a> {
+    comment: &'a mut VorbisComment,
+    key: String,
+}
+
+impl<'a> Entry<'a> {
+    /// Returns the upper-cased key of the entry.
+    pub fn key(&self) -> &str {
+        &self.key[..]
     }
 
-    /// Removes all values with the TOTALTRACKS key.
-    pub fn remove_total_tracks(&mut self) {
-        self.remove("TOTALTRACKS");
+    /// Returns the values of the key, or `None` if the key has no values.
+    pub fn values(&self) -> Option<&Vec<String>> {
+        self.comment.get(&self.key)
     }
 
-    /// Returns a reference to the vector of values with the ALBUMARTIST key.
-    pub fn album_artist(&self) -> Option<&Vec<String>> {
-        self.get("ALBUMARTIST")
+    /// Inserts the value if the key has no values, and returns the entry for further
+    /// modification.
+    pub fn or_insert<V: Into<String>>(self, value: V) -> Entry<'a> {
+        let values = self.comment.comments.entry(self.key.clone()).or_default();
+        if values.is_empty() {
+            values.push(value.into());
+        }
+        self
     }
 
-    /// Sets the values for the ALBUMARTIST key. This will result in any ALBUMARTISTSORT comments
-    /// being removed.
-    pub fn set_album_artist<T: Into<String>>(&mut self, album_artists: Vec<T>) {
-        self.remove("ALBUMARTISTSORT");
-        self.set("ALBUMARTIST", album_artists);
+    /// Appends a value to the values of the key.
+    pub fn push_value<V: Into<String>>(&mut self, value: V) -> &mut Entry<'a> {
+        self.comment
+            .comments
+            .entry(self.key.clone())
+            .or_default()
+            .push(value.into());
+        self
     }
 
-    /// Removes all values with the ALBUMARTIST key. This will result in any ALBUMARTISTSORT
-    /// comments being removed as well.
-    pub fn remove_album_artist(&mut self) {
-        self.remove("ALBUMARTISTSORT");
-        self.remove("ALBUMARTIST");
+    /// Inserts a value at the index of the values of the key, or appends it if the index is past
+    /// the last value.
+    pub fn insert_at<V: Into<String>>(&mut self, index: usize, value: V) -> &mut Entry<'a> {
+        let values = self.comment.comments.entry(self.key.clone()).or_default();
+        values.insert(index.min(values.len()), value.into());
+        self
     }
 
-    /// Returns a reference to the vector of values with the LYRICS key.
-    pub fn lyrics(&self) -> Option<&Vec<String>> {
-        self.get("LYRICS")
+    /// Replaces all values equal to `old` by `new`. Returns the number of replaced values.
+    pub fn replace_value<V: Into<String>>(&mut self, old: &str, new: V) -> usize {
+        let mut count = 0;
+        if let Some(values) = self.comment.comments.get_mut(&self.key) {
+            let new = new.into();
+            for value in values.iter_mut().filter(|value| *value == old) {
+                value.clone_from(&new);
+                count += 1;
+            }
+        }
+        count
     }
 
-    /// Sets the values for the LYRICS key.
-    pub fn set_lyrics<T: Into<String>>(&mut self, lyrics: Vec<T>) {
-        self.set("LYRICS", lyrics);
+    /// Keeps only the values for which the predicate returns true, removing the key if no values
+    /// are left.
+    pub fn retain_values<F: FnMut(&str) -> bool>(&mut self, mut predicate: F) -> &mut Entry<'a> {
+        if let Some(values) = self.comment.comments.get_mut(&self.key) {
+            values.retain(|value| predicate(value));
+            if values.is_empty() {
+                self.comment.comments.remove(&self.key);
+            }
+        }
+        self
     }
 
-    /// Removes all values with the LYRICS key.
-    pub fn remove_lyrics(&mut self) {
-        self.remove("LYRICS");
+    /// Removes all values equal to the value, removing the key if no values are left.
+    pub fn remove_value(&mut self, value: &str) -> &mut Entry<'a> {
+        self.retain_values(|other| other != value)
     }
-    // }}}
+
+    /// Removes the key and returns its values.
+    pub fn remove(self) -> Option<Vec<String>> {
+        self.comment.comments.remove(&self.key)
+    }
+}
+//}}}
+
+/// Attempts to parse a part of the first value, split at `/`, as a `u32`.
+fn parse_number(values: &[String], part: usize) -> Option<u32> {
+    values
+        .first()?
+        .split('/')
+        .nth(part)
+        .and_then(|number| number.trim().parse::<u32>().ok())
+}
+
+/// Returns true if the bytes are long enough to hold the picture block they describe.
+fn is_picture_length(bytes: &[u8]) -> bool {
+    let read_u32 = |i: usize| {
+        bytes
+            .get(i..i + 4)
+            .map(|field| u32::from_be_bytes([field[0], field[1], field[2], field[3]]) as usize)
+    };
+
+    let length = read_u32(4).and_then(|mime_length| {
+        let i = 8 + mime_length;
+        let i = i + 4 + read_u32(i)? + 16;
+        Some(i + 4 + read_u32(i)?)
+    });
+    matches!(length, Some(length) if length <= bytes.len())
 }
 
 impl Default for VorbisComment {
@@ -1171,6 +3814,7 @@ pub struct Blocks<R> {
     ident_read: bool,
     finished: bool,
     reader: R,
+    registry: BlockRegistry,
 }
 
 impl<R> Blocks<R>
@@ -1179,10 +3823,17 @@ where
 {
     /// Create new iterator over FLAC stream's blocks
     pub fn new(reader: R) -> Self {
+        Blocks::with_registry(reader, BlockRegistry::new())
+    }
+
+    /// Create new iterator over FLAC stream's blocks which parses custom block types with the
+    /// codecs of the registry
+    pub fn with_registry(reader: R, registry: BlockRegistry) -> Self {
         Blocks {
             ident_read: false,
             finished: false,
             reader,
+            registry,
         }
     }
 }
@@ -1204,7 +3855,7 @@ where
         }
 
         if !self.finished {
-            match Block::read_from(&mut self.reader) {
+            match Block::read_from_with_registry(&mut self.reader, &self.registry) {
                 Ok((is_last, length, block)) => {
                     self.finished = is_last;
                     Some(Ok((length, block)))
@@ -1262,3 +3913,623 @@ pub(crate) fn read_ident<R: Read>(mut reader: R) -> Result<()> {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::tests::constant_frame;
+    use crate::frame::{ChannelAssignment, FrameHeader};
+
+    #[test]
+    fn invalid_block_type() {
+        let bytes = [0xFF, 0, 0, 1, 0];
+        let err = Block::read_from(&mut &bytes[..]).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::InvalidInput));
+        assert_eq!(err.description, "block type 127 is invalid");
+
+        let block = Block::Unknown((127, vec![0]));
+        assert!(block.write_to(true, &mut Vec::new()).is_err());
+
+        let (_, _, block) = Block::read_from(&mut &[0xFE, 0, 0, 1, 0][..]).unwrap();
+        assert_eq!(block.block_type(), BlockType::Unknown(126));
+    }
+
+    #[test]
+    fn custom_block_without_codec() {
+        #[derive(Clone, Debug)]
+        struct Empty;
+
+        struct EmptyCodec(u8);
+
+        impl BlockCodec for EmptyCodec {
+            fn block_type(&self) -> u8 {
+                self.0
+            }
+
+            fn decode(&self, _: &[u8]) -> Result<Box<dyn CustomBlock>> {
+                Ok(Box::new(Empty))
+            }
+
+            fn encode(&self, _: &dyn CustomBlock) -> Result<Vec<u8>> {
+                Ok(Vec::new())
+            }
+        }
+
+        let mut registry = BlockRegistry::new();
+        assert!(registry.register(Arc::new(EmptyCodec(4))).is_err());
+        assert!(registry.register(Arc::new(EmptyCodec(127))).is_err());
+        registry.register(Arc::new(EmptyCodec(7))).unwrap();
+
+        let block = Block::Custom((7, Box::new(Empty)));
+        assert!(block.write_to(true, &mut Vec::new()).is_err());
+        let mut bytes = Vec::new();
+        assert_eq!(
+            block
+                .write_to_with_registry(true, &mut bytes, &registry)
+                .unwrap(),
+            4
+        );
+        assert_eq!(bytes, [0x87, 0, 0, 0]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn custom_block_serde() {
+        #[derive(Clone, Debug)]
+        struct Empty;
+
+        let block = Block::Custom((7, Box::new(Empty)));
+        assert!(serde_json::to_string(&block).is_err());
+        let block = Block::Unknown((7, Vec::new()));
+        assert!(serde_json::to_string(&block).is_ok());
+    }
+
+    #[test]
+    fn application_round_trip() {
+        let application = Application::from_bytes(b"TtWvdata");
+        assert_eq!(&application.id, b"TtWv");
+        assert_eq!(application.data, b"data");
+        assert_eq!(application.registered_name(), Some("TwistedWave"));
+        assert_eq!(application.to_bytes(), b"TtWvdata");
+
+        let truncated = Application::from_bytes(b"ab");
+        assert_eq!(&truncated.id, b"ab\0\0");
+        assert_eq!(truncated.registered_name(), None);
+    }
+
+    #[test]
+    fn invalid_field_names() {
+        let mut comments = VorbisComment::new();
+        comments.set("FOO=BAR", vec!["value"]);
+        comments.set("TITLE", vec!["Title"]);
+        assert_eq!(comments.invalid_keys(), vec!["FOO=BAR"]);
+        match comments.to_bytes().unwrap_err().kind {
+            ErrorKind::InvalidFieldNames(keys) => assert_eq!(keys, vec!["FOO=BAR"]),
+            kind => panic!("unexpected error kind {:?}", kind),
+        }
+        assert!(Block::VorbisComment(comments.clone())
+            .write_to(true, &mut Vec::new())
+            .is_err());
+
+        comments.remove("FOO=BAR");
+        let bytes = comments.to_bytes().unwrap();
+        assert_eq!(VorbisComment::from_bytes(&bytes[..]).unwrap(), comments);
+
+        // comments without a separator are an error unless reading leniently
+        let bytes = [
+            0, 0, 0, 0, 1, 0, 0, 0, 5, 0, 0, 0, b'T', b'I', b'T', b'L', b'E',
+        ];
+        assert!(VorbisComment::from_bytes(&bytes[..]).is_err());
+        let (comments, invalid) = VorbisComment::from_bytes_lenient(&bytes[..]).unwrap();
+        assert!(comments.comments.is_empty());
+        assert_eq!(invalid, vec!["TITLE"]);
+    }
+
+    #[test]
+    fn standard_field_keys() {
+        for (i, field) in StandardField::ALL.iter().enumerate() {
+            assert!(FieldName::is_valid(field.key()));
+            assert_eq!(StandardField::from_key(field.key()), Some(*field));
+            assert!(!StandardField::ALL[..i]
+                .iter()
+                .any(|other| other.key() == field.key()));
+            assert_eq!(field.aliases()[0], field.key());
+            assert!(field.aliases().iter().all(|key| FieldName::is_valid(key)));
+            if let Some(sort_field) = field.sort_field() {
+                assert_eq!(sort_field.key(), format!("{}SORT", field.key()));
+            }
+        }
+    }
+
+    #[test]
+    fn sort_fields() {
+        let mut comments = VorbisComment::new();
+        comments.set_title_sort(vec!["Title"]);
+        comments.set_album_sort(vec!["Album"]);
+        comments.set_artist_sort(vec!["Artist"]);
+        comments.set_album_artist_sort(vec!["Album Artist"]);
+        comments.set_composer_sort(vec!["Composer"]);
+
+        comments.set_title(vec!["A Title"]);
+        comments.set_album(vec!["An Album"]);
+        comments.set_artist(vec!["An Artist"]);
+        comments.set_album_artist(vec!["An Album Artist"]);
+        assert_eq!(comments.title_sort(), Some(&vec!["Title".to_owned()]));
+        assert_eq!(comments.album_sort(), Some(&vec!["Album".to_owned()]));
+        assert_eq!(comments.artist_sort(), Some(&vec!["Artist".to_owned()]));
+        assert_eq!(
+            comments.album_artist_sort(),
+            Some(&vec!["Album Artist".to_owned()])
+        );
+        assert_eq!(comments.composer_sort(), Some(&vec!["Composer".to_owned()]));
+        assert_eq!(comments.custom_fields().count(), 0);
+
+        comments.remove_sort_field(StandardField::Title);
+        comments.remove_sort_field(StandardField::Genre);
+        comments.remove_album_sort();
+        comments.remove_artist_sort();
+        comments.remove_album_artist_sort();
+        comments.remove_composer_sort();
+        assert_eq!(comments.comments.len(), 4);
+    }
+
+    #[test]
+    fn partial_dates() {
+        let cases = [
+            ("2004", "2004"),
+            (" 2004-05 ", "2004-05"),
+            ("2004/05/17", "2004-05-17"),
+            ("20040517", "2004-05-17"),
+            ("17.05.2004", "2004-05-17"),
+            ("2004.5.7", "2004-05-07"),
+            ("2004-05-17T10:00", "2004-05-17T10:00"),
+            ("2004-05-17 10:00:30Z", "2004-05-17T10:00:30"),
+            ("2004-05-17T10:00+02:00", "2004-05-17T10:00"),
+            ("2000-02-29", "2000-02-29"),
+        ];
+        for &(text, iso) in &cases {
+            assert_eq!(PartialDate::parse(text).unwrap().to_string(), iso);
+        }
+
+        for &text in &[
+            "",
+            "04",
+            "2004-13",
+            "1900-02-29",
+            "2004-05-17T24:00",
+            "2004-05T10:00",
+            "17/05/2004",
+            "05.2004",
+            "2004-05-17T10",
+            "May 2004",
+        ] {
+            assert!(PartialDate::parse(text).is_err(), "{}", text);
+        }
+
+        let year: PartialDate = "2004".parse().unwrap();
+        let month: PartialDate = "2004-01".parse().unwrap();
+        let next: PartialDate = "2005".parse().unwrap();
+        assert!(year < month && month < next);
+
+        let mut comments = VorbisComment::new();
+        comments.set("YEAR", vec!["1999"]);
+        comments.set("ORIGINALDATE", vec!["01.02.1970", "unknown"]);
+        assert_eq!(comments.date(), Some(PartialDate::new(1999)));
+        comments.normalize_fields(&WriteConvention::new());
+        assert_eq!(comments.get("DATE"), Some(&vec!["1999".to_owned()]));
+        assert_eq!(
+            comments.get("ORIGINALDATE"),
+            Some(&vec!["1970-02-01".to_owned(), "unknown".to_owned()])
+        );
+
+        comments.remove_field(StandardField::Date);
+        assert_eq!(comments.release_year(), Some(1970));
+        comments.set_date(PartialDate {
+            month: Some(3),
+            ..PartialDate::new(2001)
+        });
+        assert_eq!(comments.get("DATE"), Some(&vec!["2001-03".to_owned()]));
+    }
+
+    #[test]
+    fn replay_gain() {
+        assert_eq!(ReplayGain::parse_gain(" -6.54dB "), Some(-6.54));
+        assert_eq!(ReplayGain::parse_gain("+2.00 DB"), Some(2.0));
+        assert_eq!(ReplayGain::parse_gain("inf"), None);
+        assert_eq!(ReplayGain::parse_gain("-6.54 LUFS"), None);
+        assert_eq!(ReplayGain::parse_peak("-0.5"), None);
+        assert_eq!(ReplayGain::parse_reference_loudness("89 dB"), Some(-18.0));
+        assert_eq!(
+            ReplayGain::parse_reference_loudness("-23 LUFS"),
+            Some(-23.0)
+        );
+        assert_eq!(ReplayGain::to_r128(1000.0, -18.0), i16::MAX);
+        assert_eq!(ReplayGain::to_r128(-6.0, -23.0), -1536);
+
+        let mut comments = VorbisComment::new();
+        assert_eq!(comments.replay_gain(), None);
+        comments.set("REPLAYGAIN_TRACK_GAIN", vec!["unknown", "-3 dB"]);
+        comments.set("REPLAYGAIN_REFERENCE_LOUDNESS", vec!["-23.00 LUFS"]);
+        comments.set("R128_TRACK_GAIN", vec!["0"]);
+        comments.set("R128_ALBUM_GAIN", vec!["256"]);
+        let gain = comments.replay_gain().unwrap();
+        assert_eq!(gain.track_gain, Some(-3.0));
+        assert_eq!(gain.album_gain, Some(1.0));
+        assert_eq!(gain.reference(), -23.0);
+
+        comments.set_replay_gain(&gain);
+        assert!(comments.get("R128_ALBUM_GAIN").is_none());
+        assert_eq!(comments.replay_gain(), Some(gain));
+        assert_eq!(comments.comments.len(), 3);
+
+        comments.set_r128_gain(&gain);
+        assert_eq!(
+            comments.get("R128_TRACK_GAIN"),
+            Some(&vec!["-768".to_owned()])
+        );
+        assert_eq!(comments.comments.len(), 2);
+        assert_eq!(comments.replay_gain().unwrap().album_gain, Some(6.0));
+
+        comments.remove_replay_gain();
+        assert!(comments.comments.is_empty());
+    }
+
+    #[test]
+    fn identifiers() {
+        let artist = "a74b1b7f-71a5-4011-9441-d0b5e4122711";
+        let other = "8bfac288-ccc5-448d-9573-c33ea2aa5c30";
+        assert!(Identifier::is_valid(artist));
+        assert!(!Identifier::is_valid(
+            "a74b1b7f-71a5-4011-9441-d0b5e412271g"
+        ));
+        assert!(!Identifier::is_valid(
+            "a74b1b7f-71a5-4011-9441d-0b5e4122711"
+        ));
+        assert!(!Identifier::is_valid(
+            "{a74b1b7f-71a5-4011-9441-d0b5e41227}"
+        ));
+
+        let mut comments = VorbisComment::new();
+        comments.set(
+            "MUSICBRAINZ_ARTISTID",
+            vec![format!("{} / {}", artist, other)],
+        );
+        comments.set("MUSICBRAINZ_ALBUMID", vec![artist, other]);
+        comments.set("MUSICBRAINZ_WORKID", vec!["", "x"]);
+        assert_eq!(
+            comments.identifiers(IdentifierField::ArtistId).unwrap(),
+            vec![
+                artist.parse().unwrap(),
+                other.parse::<Identifier>().unwrap()
+            ]
+        );
+        assert!(comments.identifiers(IdentifierField::WorkId).is_err());
+        assert_eq!(
+            comments.identifiers(IdentifierField::AcoustId).unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            comments.identifier_issues(&[IdentifierField::ArtistId, IdentifierField::TrackId]),
+            vec![
+                IdentifierIssue::Missing(IdentifierField::TrackId),
+                IdentifierIssue::Multiple(IdentifierField::AlbumId),
+                IdentifierIssue::Malformed(IdentifierField::WorkId, String::new()),
+                IdentifierIssue::Malformed(IdentifierField::WorkId, "x".to_owned()),
+            ]
+        );
+
+        let ids = comments.identifiers(IdentifierField::ArtistId).unwrap();
+        comments.set_identifiers(IdentifierField::ArtistId, &ids);
+        assert_eq!(comments.get("MUSICBRAINZ_ARTISTID").unwrap().len(), 2);
+        comments.set_identifier(IdentifierField::AlbumId, &ids[0]);
+        comments.remove_identifier(IdentifierField::WorkId);
+        assert!(comments.identifier_issues(&[]).is_empty());
+
+        comments.set("MUSICBRAINZ_WORKID", vec![""]);
+        assert!(comments.identifiers(IdentifierField::WorkId).is_err());
+        assert_eq!(
+            comments.identifier_issues(&[]),
+            vec![IdentifierIssue::Malformed(
+                IdentifierField::WorkId,
+                String::new()
+            )]
+        );
+    }
+
+    #[test]
+    fn multi_value_policy() {
+        let policy = FieldPolicy {
+            delimiters: vec![";".to_owned(), " / ".to_owned()],
+            join: Some(" / ".to_owned()),
+        };
+        assert_eq!(
+            policy.split(&["A;B / C", " ; ", "AC/DC"]),
+            ["A", "B", "C", "AC/DC"]
+        );
+        assert_eq!(policy.join(&["A", "B"]), ["A / B"]);
+        assert_eq!(policy.join::<&str>(&[]), Vec::<String>::new());
+        assert_eq!(
+            FieldPolicy::never_split().split(&["A; B", " ", ""]),
+            ["A; B", " ", ""]
+        );
+
+        let policy = MultiValuePolicy::new()
+            .with_field("artist", policy)
+            .never_split("Genre");
+        assert_eq!(policy.field("ARTIST").join.as_deref(), Some(" / "));
+        assert!(policy.field("GENRE").delimiters.is_empty());
+        assert!(policy.field("title").delimiters.is_empty());
+        assert!(policy.field("UNSYNCEDLYRICS").delimiters.is_empty());
+        assert_eq!(policy.field("PERFORMER"), &FieldPolicy::new());
+
+        let mut comments = VorbisComment::new();
+        comments.set_with_policy("ARTIST", &["A", "B"], &policy);
+        comments.set_with_policy("PERFORMER", &["A", "B"], &policy);
+        comments.set("MOOD", vec![";", " "]);
+        assert_eq!(comments.get("ARTIST"), Some(&vec!["A / B".to_owned()]));
+        assert_eq!(comments.get("PERFORMER").unwrap().len(), 2);
+        assert_eq!(comments.get_split("ARTIST", &policy), ["A", "B"]);
+        assert!(comments.get_split("GENRE", &policy).is_empty());
+
+        comments.apply_multi_value_policy(&MultiValuePolicy::new());
+        assert_eq!(comments.get("ARTIST"), Some(&vec!["A / B".to_owned()]));
+        assert_eq!(
+            comments.get("MOOD"),
+            Some(&vec![";".to_owned(), " ".to_owned()])
+        );
+
+        // fields which are not opted in are kept as they are
+        comments.set("ENCODER", vec![" x; y "]);
+        comments.set("COMMENT", vec![""]);
+        comments.set("PERFORMER", vec![" ; "]);
+        comments.apply_multi_value_policy(&MultiValuePolicy::new());
+        assert_eq!(comments.get("ENCODER"), Some(&vec![" x; y ".to_owned()]));
+        assert_eq!(comments.get("COMMENT"), Some(&vec![String::new()]));
+        assert!(comments.get("PERFORMER").is_none());
+    }
+
+    #[test]
+    fn entries() {
+        let mut comments = VorbisComment::new();
+        assert_eq!(comments.entry("genre").unwrap().key(), "GENRE");
+        assert!(comments.entry("genre").unwrap().values().is_none());
+
+        comments
+            .entry("genre")
+            .unwrap()
+            .or_insert("Rock")
+            .push_value("Pop");
+        comments.entry("GENRE").unwrap().or_insert("Jazz");
+        comments
+            .entry("Genre")
+            .unwrap()
+            .insert_at(0, "Pop")
+            .insert_at(9, "Rock");
+        assert_eq!(
+            comments.get("GENRE"),
+            Some(&vec![
+                "Pop".to_owned(),
+                "Rock".to_owned(),
+                "Pop".to_owned(),
+                "Rock".to_owned()
+            ])
+        );
+
+        assert_eq!(
+            comments
+                .entry("genre")
+                .unwrap()
+                .replace_value("Pop", "Jazz"),
+            2
+        );
+        assert_eq!(
+            comments.entry("mood").unwrap().replace_value("Pop", "Jazz"),
+            0
+        );
+        assert!(comments.get("MOOD").is_none());
+
+        comments.entry("genre").unwrap().remove_value("Jazz");
+        assert_eq!(comments.entry("genre").unwrap().values().unwrap().len(), 2);
+        comments.entry("genre").unwrap().remove_value("Rock");
+        assert!(comments.comments.is_empty());
+
+        comments.entry("mood").unwrap().retain_values(|_| false);
+        assert!(comments.comments.is_empty());
+
+        comments.entry("mood").unwrap().push_value("Calm");
+        assert_eq!(
+            comments.entry("mood").unwrap().remove(),
+            Some(vec!["Calm".to_owned()])
+        );
+        assert!(comments.comments.is_empty());
+        assert!(comments.entry("T\u{C4}G").is_err());
+    }
+
+    #[test]
+    fn field_aliases() {
+        let mut comments = VorbisComment::new();
+        comments.set("TRACKNUMBER", vec![" 3 / 12 "]);
+        comments.set("DISCNUMBER", vec!["1"]);
+        comments.set("TOTALDISCS", vec!["2"]);
+        comments.set("ALBUM ARTIST", vec!["Various"]);
+        assert_eq!(comments.track(), Some(3));
+        assert_eq!(comments.total_tracks(), Some(12));
+        assert_eq!(comments.disc(), Some(1));
+        assert_eq!(comments.total_discs(), Some(2));
+        assert_eq!(
+            comments.get_field(StandardField::AlbumArtist),
+            Some(&vec!["Various".to_owned()])
+        );
+        assert_eq!(comments.custom_fields().count(), 0);
+
+        // aliases are kept unless the convention removes them
+        let convention = WriteConvention {
+            remove_aliases: false,
+            split_totals: false,
+            ..WriteConvention::new()
+        };
+        comments.normalize_fields(&convention);
+        assert_eq!(
+            comments.get("TRACKNUMBER"),
+            Some(&vec![" 3 / 12 ".to_owned()])
+        );
+        assert_eq!(comments.get("DISCTOTAL"), Some(&vec!["2".to_owned()]));
+        assert_eq!(comments.get("TOTALDISCS"), Some(&vec!["2".to_owned()]));
+
+        let convention = WriteConvention::new()
+            .with_key(StandardField::TotalTracks, "TRACKTOTAL")
+            .unwrap();
+        assert!(WriteConvention::new()
+            .with_key(StandardField::TotalTracks, "TRACK=TOTAL")
+            .is_err());
+        // keys which are not aliases would not be read back as the field
+        assert!(WriteConvention::new()
+            .with_key(StandardField::TotalTracks, "NUMTRACKS")
+            .is_err());
+        assert!(WriteConvention::new()
+            .with_key(StandardField::TrackNumber, "TRACK")
+            .is_err());
+        comments.normalize_fields(&convention);
+        assert_eq!(comments.get("TRACKNUMBER"), Some(&vec!["3".to_owned()]));
+        assert_eq!(comments.get("TRACKTOTAL"), Some(&vec!["12".to_owned()]));
+        assert!(comments.get("TOTALTRACKS").is_none());
+        assert!(comments.get("TOTALDISCS").is_none());
+        assert!(comments.get("ALBUM ARTIST").is_none());
+        assert_eq!(
+            comments.get("ALBUMARTIST"),
+            Some(&vec!["Various".to_owned()])
+        );
+
+        comments.remove_total_tracks();
+        assert_eq!(comments.total_tracks(), None);
+        comments.set_total_tracks(10);
+        assert_eq!(comments.get("TOTALTRACKS"), Some(&vec!["10".to_owned()]));
+    }
+
+    #[test]
+    fn split_totals() {
+        // every value is split, and the totals are written under the key of the convention
+        let mut comments = VorbisComment::new();
+        comments.set("TRACKNUMBER", vec!["3/12", "4/12"]);
+        comments.set("DISCNUMBER", vec!["1/2"]);
+        let convention = WriteConvention::new()
+            .with_key(StandardField::DiscTotal, "TOTALDISCS")
+            .unwrap();
+        comments.normalize_fields(&convention);
+        assert_eq!(
+            comments.get("TRACKNUMBER"),
+            Some(&vec!["3".to_owned(), "4".to_owned()])
+        );
+        assert_eq!(
+            comments.get("TOTALTRACKS"),
+            Some(&vec!["12".to_owned(), "12".to_owned()])
+        );
+        assert_eq!(comments.get("DISCNUMBER"), Some(&vec!["1".to_owned()]));
+        assert_eq!(comments.get("TOTALDISCS"), Some(&vec!["2".to_owned()]));
+        assert_eq!(comments.total_discs(), Some(2));
+
+        // existing totals are kept
+        comments.set("TRACKNUMBER", vec!["5/10", "6"]);
+        comments.normalize_fields(&convention);
+        assert_eq!(
+            comments.get("TRACKNUMBER"),
+            Some(&vec!["5".to_owned(), "6".to_owned()])
+        );
+        assert_eq!(comments.total_tracks(), Some(12));
+    }
+
+    #[test]
+    fn encoded_pictures() {
+        let mut comments = VorbisComment::new();
+        comments.set("COVERART", vec!["/9j/", "iVBO"]);
+        comments.set("COVERARTMIME", vec!["image/jpeg"]);
+
+        let pictures = comments.encoded_pictures().unwrap();
+        assert_eq!(pictures.len(), 2);
+        assert_eq!(pictures[0].mime_type, "image/jpeg");
+        assert_eq!(pictures[0].data, [0xFF, 0xD8, 0xFF]);
+        assert_eq!(pictures[0].picture_type, PictureType::CoverFront);
+        assert_eq!(pictures[1].mime_type, "");
+        assert_eq!(pictures[1].data, [0x89, 0x50, 0x4E]);
+
+        comments.remove_encoded_pictures();
+        assert!(comments.comments.is_empty());
+
+        // truncated picture blocks are rejected
+        let mut picture = Picture::new();
+        picture.data = vec![1, 2, 3];
+        let bytes = picture.to_bytes();
+        let encoded = crate::base64::encode(&bytes[..bytes.len() - 1]);
+        comments.set("METADATA_BLOCK_PICTURE", vec![encoded]);
+        assert!(comments.encoded_pictures().is_err());
+    }
+
+    #[test]
+    fn generate_seektable() {
+        let mut header = FrameHeader::new();
+        header.block_size = 4096;
+        header.channel_assignment = ChannelAssignment::Independent(2);
+
+        let mut audio = Vec::new();
+        for number in 0..10 {
+            header.number = number;
+            audio.extend(constant_frame(&header));
+        }
+        let frame_length = audio.len() as u64 / 10;
+
+        let mut streaminfo = StreamInfo::new();
+        streaminfo.sample_rate = 8192;
+
+        let seektable = SeekTable::generate(
+            &streaminfo,
+            &audio[..],
+            &[
+                SeekPointSpec::Placeholders(2),
+                SeekPointSpec::EverySeconds(2.0),
+                SeekPointSpec::Sample(5000),
+                SeekPointSpec::Sample(100_000),
+            ],
+        )
+        .unwrap();
+
+        let sample_numbers: Vec<_> = seektable
+            .seekpoints
+            .iter()
+            .map(|p| p.sample_number)
+            .collect();
+        assert_eq!(
+            sample_numbers,
+            vec![
+                0,
+                4096,
+                16384,
+                32768,
+                SeekPoint::PLACEHOLDER_SAMPLE_NUMBER,
+                SeekPoint::PLACEHOLDER_SAMPLE_NUMBER
+            ]
+        );
+        assert_eq!(seektable.seekpoints[1].offset, frame_length);
+        assert_eq!(seektable.seekpoints[3].offset, 8 * frame_length);
+        assert_eq!(seektable.seekpoints[3].num_samples, 4096);
+
+        let seektable =
+            SeekTable::generate(&streaminfo, &audio[..], &[SeekPointSpec::Evenly(4)]).unwrap();
+        assert_eq!(seektable.seekpoints.len(), 4);
+        assert_eq!(seektable.seekpoints[2].sample_number, 20480);
+
+        let seektable = SeekTable::generate(
+            &streaminfo,
+            &audio[..],
+            &[SeekPointSpec::EverySamples(u64::MAX)],
+        )
+        .unwrap();
+        assert_eq!(seektable.seekpoints.len(), 1);
+
+        header.block_size = 65536;
+        header.number = 0;
+        let audio = constant_frame(&header);
+        let seektable =
+            SeekTable::generate(&streaminfo, &audio[..], &[SeekPointSpec::Sample(0)]).unwrap();
+        assert_eq!(seektable.seekpoints[0].num_samples, 65535);
+    }
+}
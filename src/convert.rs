@@ -0,0 +1,721 @@
+use crate::block::StreamInfo;
+use crate::decoder;
+use crate::encoder::{self, EncoderConfig};
+use crate::error::{Error, ErrorKind, Result};
+use crate::foreign::{ForeignFormat, ForeignMetadata, WaveFormat};
+use crate::tag::Tag;
+
+use std::io::{Read, Write};
+
+/// The GUID of `KSDATAFORMAT_SUBTYPE_PCM`, the sub-format of `WAVE_FORMAT_EXTENSIBLE` PCM audio.
+const PCM_SUBFORMAT: [u8; 16] = [
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+/// The largest number of bytes which a canonical WAV or AIFF file holds in addition to the audio
+/// data and which are counted by the size in its file header.
+const MAX_CANONICAL_OVERHEAD: u64 = 64;
+
+/// Attempts to read a WAV file from the reader and write it to the writer as a FLAC stream,
+/// using the specified compression level (see `EncoderConfig`). Returns the streaminfo block of
+/// the written stream.
+///
+/// All chunks of the WAV file other than the audio data are kept in application blocks with the
+/// id `riff`, in the same way as `flac --keep-foreign-metadata`, so that `flac_to_wav` can
+/// restore the original file byte for byte.
+///
+/// # Example
+/// ```
+/// use metaflac::convert;
+/// use metaflac::encoder::{self, EncoderConfig};
+/// use metaflac::Tag;
+///
+/// let samples: Vec<i32> = (0..4410).map(|i| (i % 100) * 300 - 15000).collect();
+/// let mut flac = Vec::new();
+/// encoder::encode(&EncoderConfig::new(44100, 1, 16), &samples, &Tag::new(), &mut flac).unwrap();
+///
+/// let mut wav = Vec::new();
+/// convert::flac_to_wav(&mut &flac[..], &mut wav).unwrap();
+/// assert_eq!(&wav[..4], b"RIFF");
+///
+/// let mut copy = Vec::new();
+/// let streaminfo = convert::wav_to_flac(&mut &wav[..], &mut copy, 5).unwrap();
+/// assert_eq!(streaminfo.total_samples, 4410);
+///
+/// let mut restored = Vec::new();
+/// convert::flac_to_wav(&mut &copy[..], &mut restored).unwrap();
+/// assert_eq!(restored, wav);
+/// ```
+pub fn wav_to_flac(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    compression_level: u8,
+) -> Result<StreamInfo> {
+    to_flac(reader, writer, compression_level, ForeignFormat::Riff)
+}
+
+/// Attempts to read an AIFF or uncompressed AIFF-C file from the reader and write it to the
+/// writer as a FLAC stream. The chunks of the file are kept in application blocks with the id
+/// `aiff`. See `wav_to_flac` for details.
+pub fn aiff_to_flac(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    compression_level: u8,
+) -> Result<StreamInfo> {
+    to_flac(reader, writer, compression_level, ForeignFormat::Aiff)
+}
+
+/// Attempts to decode the FLAC stream from the reader and write it to the writer as a WAV file.
+/// Returns the streaminfo block of the stream.
+///
+/// If the stream contains `riff` application blocks, the original WAV file is restored from them.
+/// Otherwise a canonical WAV file is written, which uses `WAVE_FORMAT_EXTENSIBLE` for more than
+/// two channels or sample sizes other than 8 and 16 bits.
+pub fn flac_to_wav(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<StreamInfo> {
+    from_flac(reader, writer, ForeignFormat::Riff)
+}
+
+/// Attempts to decode the FLAC stream from the reader and write it to the writer as an AIFF file.
+/// If the stream contains `aiff` application blocks, the original AIFF file is restored from
+/// them. See `flac_to_wav` for details.
+pub fn flac_to_aiff(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<StreamInfo> {
+    from_flac(reader, writer, ForeignFormat::Aiff)
+}
+
+/// Attempts to read a Sony Wave64 file from the reader and write it to the writer as a FLAC
+/// stream. The chunks of the file are kept in application blocks with the id `w64 `. See
+/// `wav_to_flac` for details.
+pub fn w64_to_flac(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    compression_level: u8,
+) -> Result<StreamInfo> {
+    to_flac(reader, writer, compression_level, ForeignFormat::Wave64)
+}
+
+/// Attempts to decode the FLAC stream from the reader and write it to the writer as a Sony Wave64
+/// file. If the stream contains `w64 ` application blocks, the original file is restored from
+/// them. See `flac_to_wav` for details.
+pub fn flac_to_w64(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<StreamInfo> {
+    from_flac(reader, writer, ForeignFormat::Wave64)
+}
+
+fn to_flac(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    compression_level: u8,
+    format: ForeignFormat,
+) -> Result<StreamInfo> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    let (metadata, audio) = ForeignMetadata::split(&bytes[..])?;
+    if metadata.format != format {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            match format {
+                ForeignFormat::Riff => "input is not a WAV file",
+                ForeignFormat::Aiff => "input is not an AIFF file",
+                ForeignFormat::Wave64 => "input is not a Wave64 file",
+            },
+        ));
+    }
+    let mut layout = PcmLayout::from_metadata(&metadata)?;
+    let samples = layout.decode(audio)?;
+
+    let mut config = EncoderConfig::new(
+        layout.sample_rate,
+        layout.num_channels,
+        layout.bits_per_sample,
+    );
+    config.compression_level = compression_level;
+
+    let mut tag = Tag::new();
+    metadata.push_to_tag(&mut tag);
+    encoder::encode(&config, &samples[..], &tag, writer)
+}
+
+fn from_flac(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: ForeignFormat,
+) -> Result<StreamInfo> {
+    let (tag, samples) = decoder::read_from(reader)?;
+    let streaminfo = match tag.get_streaminfo() {
+        Some(streaminfo) => streaminfo.clone(),
+        None => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "stream does not contain a streaminfo block",
+            ));
+        }
+    };
+
+    let metadata = ForeignMetadata::from_tag(&tag).filter(|metadata| metadata.format == format);
+    let bytes = match metadata {
+        Some(metadata) => {
+            let mut layout = PcmLayout::from_metadata(&metadata)?;
+            if layout.num_channels != streaminfo.num_channels
+                || layout.sample_rate != streaminfo.sample_rate
+                || streaminfo.bits_per_sample as usize > layout.container_bytes * 8
+            {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "foreign metadata does not match the streaminfo block",
+                ));
+            }
+            layout.bits_per_sample = streaminfo.bits_per_sample;
+            metadata.rebuild(&layout.encode(&samples[..])[..])?
+        }
+        None => {
+            let layout = PcmLayout::from_streaminfo(&streaminfo, format);
+            let audio = layout.encode(&samples[..]);
+            layout
+                .canonical_metadata(format, audio.len())?
+                .rebuild(&audio[..])?
+        }
+    };
+    writer.write_all(&bytes[..])?;
+
+    Ok(streaminfo)
+}
+
+/// The layout of PCM samples in a WAV or AIFF file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct PcmLayout {
+    sample_rate: u32,
+    num_channels: u8,
+    /// The number of significant bits, which are stored in the most significant bits of the
+    /// container.
+    bits_per_sample: u8,
+    /// The number of bytes holding each sample.
+    container_bytes: usize,
+    big_endian: bool,
+    /// Whether 8-bit samples are stored as unsigned values, as they are in WAV files.
+    unsigned: bool,
+}
+
+impl PcmLayout {
+    fn from_metadata(metadata: &ForeignMetadata) -> Result<PcmLayout> {
+        match metadata.format {
+            ForeignFormat::Riff | ForeignFormat::Wave64 => match metadata.chunk(b"fmt ") {
+                Some(fmt) => PcmLayout::from_fmt(fmt),
+                None => Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "WAV file does not contain a fmt chunk",
+                )),
+            },
+            ForeignFormat::Aiff => match metadata.chunk(b"COMM") {
+                Some(comm) => {
+                    let aifc = metadata.blocks[0].get(8..12) == Some(&b"AIFC"[..]);
+                    PcmLayout::from_comm(comm, aifc)
+                }
+                None => Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "AIFF file does not contain a COMM chunk",
+                )),
+            },
+        }
+    }
+
+    fn from_fmt(fmt: &[u8]) -> Result<PcmLayout> {
+        let invalid = |description| Error::new(ErrorKind::InvalidInput, description);
+        let format = match WaveFormat::from_bytes(fmt) {
+            Some(format) => format,
+            None => return Err(invalid("fmt chunk is too short")),
+        };
+
+        let pcm = match format.format_tag {
+            1 => true,
+            0xFFFE => format.sub_format == Some(PCM_SUBFORMAT),
+            _ => false,
+        };
+        if !pcm {
+            return Err(invalid("WAV file does not contain PCM audio"));
+        }
+
+        let num_channels = format.num_channels;
+        let sample_rate = format.sample_rate;
+        let block_align = format.block_align as usize;
+        let bits_per_sample = match format.valid_bits_per_sample {
+            Some(bits) if bits != 0 => bits,
+            _ => format.bits_per_sample,
+        };
+
+        if num_channels == 0 || num_channels > 8 || block_align % num_channels as usize != 0 {
+            return Err(invalid("unsupported number of channels"));
+        }
+        let container_bytes = block_align / num_channels as usize;
+        if container_bytes == 0
+            || container_bytes > 4
+            || bits_per_sample as usize > container_bytes * 8
+        {
+            return Err(invalid("unsupported bits per sample"));
+        }
+
+        Ok(PcmLayout {
+            sample_rate,
+            num_channels: num_channels as u8,
+            bits_per_sample: bits_per_sample as u8,
+            container_bytes,
+            big_endian: false,
+            unsigned: container_bytes == 1,
+        })
+    }
+
+    fn from_comm(comm: &[u8], aifc: bool) -> Result<PcmLayout> {
+        let invalid = |description| Error::new(ErrorKind::InvalidInput, description);
+        if comm.len() < 18 || (aifc && comm.len() < 22) {
+            return Err(invalid("COMM chunk is too short"));
+        }
+
+        let num_channels = u16::from_be_bytes([comm[0], comm[1]]);
+        let bits_per_sample = u16::from_be_bytes([comm[6], comm[7]]);
+        let sample_rate = match read_extended(&comm[8..18]) {
+            Some(sample_rate) => sample_rate,
+            None => return Err(invalid("unsupported sample rate")),
+        };
+
+        let big_endian = if aifc {
+            match &comm[18..22] {
+                b"NONE" | b"twos" => true,
+                b"sowt" => false,
+                _ => return Err(invalid("AIFF-C file is compressed")),
+            }
+        } else {
+            true
+        };
+
+        if num_channels == 0 || num_channels > 8 {
+            return Err(invalid("unsupported number of channels"));
+        }
+        if bits_per_sample == 0 || bits_per_sample > 32 {
+            return Err(invalid("unsupported bits per sample"));
+        }
+
+        Ok(PcmLayout {
+            sample_rate,
+            num_channels: num_channels as u8,
+            bits_per_sample: bits_per_sample as u8,
+            container_bytes: (bits_per_sample as usize).div_ceil(8),
+            big_endian,
+            unsigned: false,
+        })
+    }
+
+    fn from_streaminfo(streaminfo: &StreamInfo, format: ForeignFormat) -> PcmLayout {
+        let container_bytes = (streaminfo.bits_per_sample as usize).div_ceil(8);
+        PcmLayout {
+            sample_rate: streaminfo.sample_rate,
+            num_channels: streaminfo.num_channels,
+            bits_per_sample: streaminfo.bits_per_sample,
+            container_bytes,
+            big_endian: format == ForeignFormat::Aiff,
+            unsigned: format == ForeignFormat::Riff && container_bytes == 1,
+        }
+    }
+
+    fn container_bits(&self) -> u32 {
+        self.container_bytes as u32 * 8
+    }
+
+    /// Returns the interleaved samples of the PCM data. If any of the bits below the significant
+    /// bits are set, the bits per sample is raised to the container size so that no information
+    /// is lost.
+    fn decode(&mut self, audio: &[u8]) -> Result<Vec<i32>> {
+        if audio.len() % (self.container_bytes * self.num_channels as usize) != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "audio data is not a whole number of samples",
+            ));
+        }
+
+        let container_bits = self.container_bits();
+        let mut samples: Vec<i32> = audio
+            .chunks(self.container_bytes)
+            .map(|bytes| {
+                let mut value = 0u32;
+                for i in 0..bytes.len() {
+                    let byte = if self.big_endian {
+                        bytes[i]
+                    } else {
+                        bytes[bytes.len() - 1 - i]
+                    };
+                    value = (value << 8) | byte as u32;
+                }
+                let value = (value << (32 - container_bits)) as i32;
+                if self.unsigned {
+                    value ^ i32::MIN
+                } else {
+                    value
+                }
+            })
+            .collect();
+
+        let used_bits = samples.iter().fold(0, |bits, &s| bits | s);
+        if used_bits.trailing_zeros() < 32 - self.bits_per_sample as u32 {
+            self.bits_per_sample = container_bits as u8;
+        }
+        let shift = 32 - self.bits_per_sample as u32;
+
+        for sample in samples.iter_mut() {
+            *sample >>= shift;
+        }
+        Ok(samples)
+    }
+
+    /// Returns the PCM data of the interleaved samples.
+    fn encode(&self, samples: &[i32]) -> Vec<u8> {
+        let shift = 32 - self.bits_per_sample as u32;
+        let mut audio = Vec::with_capacity(samples.len() * self.container_bytes);
+        for &sample in samples.iter() {
+            let mut value = sample << shift;
+            if self.unsigned {
+                value ^= i32::MIN;
+            }
+            let bytes = value.to_be_bytes();
+            let bytes = &bytes[..self.container_bytes];
+            if self.big_endian {
+                audio.extend(bytes);
+            } else {
+                audio.extend(bytes.iter().rev());
+            }
+        }
+        audio
+    }
+
+    /// Returns the foreign metadata of a canonical file holding PCM data of the specified
+    /// length. An `Error::InvalidInput` is returned if the size of a WAV or AIFF file would not
+    /// fit its 32-bit size fields.
+    fn canonical_metadata(
+        &self,
+        format: ForeignFormat,
+        audio_length: usize,
+    ) -> Result<ForeignMetadata> {
+        if format != ForeignFormat::Wave64
+            && audio_length as u64 + MAX_CANONICAL_OVERHEAD > u32::MAX as u64
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "audio data is too long for a WAV or AIFF file",
+            ));
+        }
+
+        let mut metadata = ForeignMetadata::new(format, audio_length as u64);
+        let block_align = self.container_bytes as u16 * self.num_channels as u16;
+
+        let (id, chunk) = match format {
+            ForeignFormat::Riff | ForeignFormat::Wave64 => {
+                let extensible = self.num_channels > 2
+                    || (self.bits_per_sample != 8 && self.bits_per_sample != 16);
+
+                let mut fmt = Vec::new();
+                fmt.extend(&(if extensible { 0xFFFEu16 } else { 1 }).to_le_bytes());
+                fmt.extend(&(self.num_channels as u16).to_le_bytes());
+                fmt.extend(&self.sample_rate.to_le_bytes());
+                fmt.extend(&(self.sample_rate * block_align as u32).to_le_bytes());
+                fmt.extend(&block_align.to_le_bytes());
+                fmt.extend(&(self.container_bits() as u16).to_le_bytes());
+                if extensible {
+                    fmt.extend(&22u16.to_le_bytes());
+                    fmt.extend(&(self.bits_per_sample as u16).to_le_bytes());
+                    fmt.extend(&channel_mask(self.num_channels).to_le_bytes());
+                    fmt.extend(&PCM_SUBFORMAT);
+                }
+                (b"fmt ", fmt)
+            }
+            ForeignFormat::Aiff => {
+                let mut comm = Vec::new();
+                comm.extend(&(self.num_channels as u16).to_be_bytes());
+                comm.extend(&((audio_length / block_align as usize) as u32).to_be_bytes());
+                comm.extend(&(self.bits_per_sample as u16).to_be_bytes());
+                comm.extend(&write_extended(self.sample_rate));
+                (b"COMM", comm)
+            }
+        };
+
+        // fmt and COMM are never the audio data chunk
+        metadata.set_chunk(id, &chunk[..]).unwrap();
+        Ok(metadata)
+    }
+}
+
+/// Returns the default speaker positions of the FLAC channel order.
+fn channel_mask(num_channels: u8) -> u32 {
+    match num_channels {
+        1 => 0x4,
+        2 => 0x3,
+        3 => 0x7,
+        4 => 0x33,
+        5 => 0x37,
+        6 => 0x3F,
+        7 => 0x70F,
+        _ => 0x63F,
+    }
+}
+
+/// Reads an integral sample rate from an 80-bit IEEE 754 extended precision number.
+fn read_extended(bytes: &[u8]) -> Option<u32> {
+    let exponent = u16::from_be_bytes([bytes[0], bytes[1]]);
+    let mantissa = bytes[2..10]
+        .iter()
+        .fold(0u64, |mantissa, &byte| (mantissa << 8) | byte as u64);
+    // negative values and values below 1 are not valid sample rates
+    if exponent & 0x8000 != 0 || exponent < 16383 {
+        return None;
+    }
+
+    let shift = 16383 + 63 - exponent as i32;
+    if !(0..64).contains(&shift) {
+        return None;
+    }
+    let value = mantissa >> shift;
+    if value > u32::MAX as u64 {
+        None
+    } else {
+        Some(value as u32)
+    }
+}
+
+/// Writes an integral sample rate as an 80-bit IEEE 754 extended precision number.
+fn write_extended(value: u32) -> [u8; 10] {
+    let mut bytes = [0; 10];
+    if value == 0 {
+        return bytes;
+    }
+
+    let exponent = 31 - value.leading_zeros();
+    let mantissa = (value as u64) << (63 - exponent);
+    bytes[..2].copy_from_slice(&(16383 + exponent as u16).to_be_bytes());
+    bytes[2..].copy_from_slice(&mantissa.to_be_bytes());
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8; 4], data: &[u8], big_endian: bool) -> Vec<u8> {
+        let size = data.len() as u32;
+        let mut chunk = id.to_vec();
+        chunk.extend(&if big_endian {
+            size.to_be_bytes()
+        } else {
+            size.to_le_bytes()
+        });
+        chunk.extend(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn wav(fmt: &[u8], audio: &[u8]) -> Vec<u8> {
+        let mut body = b"WAVE".to_vec();
+        body.extend(chunk(b"fmt ", fmt, false));
+        body.extend(chunk(b"LIST", b"INFOISFT\x05\x00\x00\x00test\x00", false));
+        body.extend(chunk(b"data", audio, false));
+        body.extend(chunk(b"id3 ", b"ID3", false));
+        chunk(b"RIFF", &body[..], false)
+    }
+
+    fn pcm_fmt(num_channels: u16, sample_rate: u32, container_bits: u16) -> Vec<u8> {
+        let block_align = num_channels * container_bits / 8;
+        let mut fmt = Vec::new();
+        fmt.extend(&1u16.to_le_bytes());
+        fmt.extend(&num_channels.to_le_bytes());
+        fmt.extend(&sample_rate.to_le_bytes());
+        fmt.extend(&(sample_rate * block_align as u32).to_le_bytes());
+        fmt.extend(&block_align.to_le_bytes());
+        fmt.extend(&container_bits.to_le_bytes());
+        fmt
+    }
+
+    fn round_trip_wav(wav: &[u8]) -> (StreamInfo, Vec<u8>) {
+        let mut flac = Vec::new();
+        let streaminfo = wav_to_flac(&mut &wav[..], &mut flac, 5).unwrap();
+        let mut restored = Vec::new();
+        flac_to_wav(&mut &flac[..], &mut restored).unwrap();
+        (streaminfo, restored)
+    }
+
+    #[test]
+    fn wav_round_trip() {
+        let audio: Vec<u8> = (0..3000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let original = wav(&pcm_fmt(2, 96000, 24), &audio[..]);
+
+        let (streaminfo, restored) = round_trip_wav(&original[..]);
+        assert_eq!(streaminfo.sample_rate, 96000);
+        assert_eq!(streaminfo.num_channels, 2);
+        assert_eq!(streaminfo.bits_per_sample, 24);
+        assert_eq!(streaminfo.total_samples, 500);
+        assert_eq!(restored, original);
+    }
+
+    #[test]
+    fn wav_8_bit_odd_length() {
+        let audio: Vec<u8> = (0..1001u32).map(|i| (i * 13 % 256) as u8).collect();
+        let original = wav(&pcm_fmt(1, 8000, 8), &audio[..]);
+
+        let (streaminfo, restored) = round_trip_wav(&original[..]);
+        assert_eq!(streaminfo.bits_per_sample, 8);
+        assert_eq!(streaminfo.total_samples, 1001);
+        assert_eq!(restored, original);
+
+        // the decoded samples are signed
+        let mut flac = Vec::new();
+        wav_to_flac(&mut &original[..], &mut flac, 0).unwrap();
+        let (_, samples) = decoder::read_from(&mut &flac[..]).unwrap();
+        assert_eq!(samples[0], -128);
+        assert_eq!(samples[10], 130 - 128);
+    }
+
+    #[test]
+    fn wav_significant_bits() {
+        let mut fmt = pcm_fmt(1, 44100, 24);
+        fmt[0..2].copy_from_slice(&0xFFFEu16.to_le_bytes());
+        fmt.extend(&22u16.to_le_bytes());
+        fmt.extend(&20u16.to_le_bytes());
+        fmt.extend(&4u32.to_le_bytes());
+        fmt.extend(&PCM_SUBFORMAT);
+
+        let clean: Vec<u8> = (0..300u32)
+            .flat_map(|i| ((i * 4099) << 4).to_le_bytes()[..3].to_vec())
+            .collect();
+        let original = wav(&fmt[..], &clean[..]);
+        let (streaminfo, restored) = round_trip_wav(&original[..]);
+        assert_eq!(streaminfo.bits_per_sample, 20);
+        assert_eq!(restored, original);
+
+        // samples which use the padding bits are kept at the container size
+        let mut noisy = clean.clone();
+        noisy[3] |= 1;
+        let original = wav(&fmt[..], &noisy[..]);
+        let (streaminfo, restored) = round_trip_wav(&original[..]);
+        assert_eq!(streaminfo.bits_per_sample, 24);
+        assert_eq!(restored, original);
+    }
+
+    #[test]
+    fn aiff_round_trip() {
+        for &(form_type, compression) in
+            [(b"AIFF", &b""[..]), (b"AIFC", &b"sowt\x00\x00"[..])].iter()
+        {
+            let audio: Vec<u8> = (0..4000u32).map(|i| (i * 31 % 253) as u8).collect();
+
+            let mut comm = Vec::new();
+            comm.extend(&2u16.to_be_bytes());
+            comm.extend(&1000u32.to_be_bytes());
+            comm.extend(&16u16.to_be_bytes());
+            comm.extend(&write_extended(44100));
+            comm.extend(compression);
+
+            let mut ssnd = Vec::new();
+            ssnd.extend(&4u32.to_be_bytes());
+            ssnd.extend(&0u32.to_be_bytes());
+            ssnd.extend(&[0xAA; 4]);
+            ssnd.extend(&audio[..]);
+
+            let mut body = form_type.to_vec();
+            body.extend(chunk(b"COMM", &comm[..], true));
+            body.extend(chunk(b"SSND", &ssnd[..], true));
+            body.extend(chunk(b"NAME", b"Noise", true));
+            let original = chunk(b"FORM", &body[..], true);
+
+            let mut flac = Vec::new();
+            let streaminfo = aiff_to_flac(&mut &original[..], &mut flac, 8).unwrap();
+            assert_eq!(streaminfo.sample_rate, 44100);
+            assert_eq!(streaminfo.total_samples, 1000);
+
+            let mut restored = Vec::new();
+            flac_to_aiff(&mut &flac[..], &mut restored).unwrap();
+            assert_eq!(restored, original);
+
+            // the riff metadata is not used for other formats
+            let mut wav = Vec::new();
+            flac_to_wav(&mut &flac[..], &mut wav).unwrap();
+            assert_eq!(wav.len(), 44 + audio.len());
+        }
+    }
+
+    #[test]
+    fn canonical_files() {
+        let samples: Vec<i32> = (0..6 * 700).map(|i| (i * 977 % 4001) - 2000).collect();
+        let mut flac = Vec::new();
+        encoder::encode(
+            &EncoderConfig::new(48000, 6, 12),
+            &samples[..],
+            &Tag::new(),
+            &mut flac,
+        )
+        .unwrap();
+
+        let mut wav = Vec::new();
+        flac_to_wav(&mut &flac[..], &mut wav).unwrap();
+        let mut aiff = Vec::new();
+        flac_to_aiff(&mut &flac[..], &mut aiff).unwrap();
+        let mut w64 = Vec::new();
+        flac_to_w64(&mut &flac[..], &mut w64).unwrap();
+
+        for &(bytes, format) in [
+            (&wav, ForeignFormat::Riff),
+            (&aiff, ForeignFormat::Aiff),
+            (&w64, ForeignFormat::Wave64),
+        ]
+        .iter()
+        {
+            let mut copy = Vec::new();
+            let streaminfo = match format {
+                ForeignFormat::Riff => wav_to_flac(&mut &bytes[..], &mut copy, 5).unwrap(),
+                ForeignFormat::Aiff => aiff_to_flac(&mut &bytes[..], &mut copy, 5).unwrap(),
+                ForeignFormat::Wave64 => w64_to_flac(&mut &bytes[..], &mut copy, 5).unwrap(),
+            };
+            assert_eq!(streaminfo.bits_per_sample, 12);
+            assert_eq!(streaminfo.num_channels, 6);
+            let (_, decoded) = decoder::read_from(&mut &copy[..]).unwrap();
+            assert_eq!(decoded, samples);
+        }
+    }
+
+    #[test]
+    fn oversized_and_truncated_metadata() {
+        let mut streaminfo = StreamInfo::new();
+        streaminfo.sample_rate = 44100;
+        streaminfo.num_channels = 2;
+        streaminfo.bits_per_sample = 16;
+        let layout = PcmLayout::from_streaminfo(&streaminfo, ForeignFormat::Riff);
+        for &format in [ForeignFormat::Riff, ForeignFormat::Aiff].iter() {
+            assert!(layout.canonical_metadata(format, 1 << 32).is_err());
+            assert!(layout
+                .canonical_metadata(format, u32::MAX as usize - 64)
+                .is_ok());
+        }
+        assert!(layout
+            .canonical_metadata(ForeignFormat::Wave64, 1 << 32)
+            .is_ok());
+
+        // a truncated FORM header is read as plain AIFF instead of panicking
+        let mut comm = Vec::new();
+        comm.extend(&1u16.to_be_bytes());
+        comm.extend(&0u32.to_be_bytes());
+        comm.extend(&16u16.to_be_bytes());
+        comm.extend(&write_extended(44100));
+        let metadata = ForeignMetadata {
+            format: ForeignFormat::Aiff,
+            blocks: vec![b"FORM".to_vec(), chunk(b"COMM", &comm[..], true)],
+        };
+        let layout = PcmLayout::from_metadata(&metadata).unwrap();
+        assert!(layout.big_endian);
+    }
+
+    #[test]
+    fn extended_sample_rates() {
+        for &rate in [1, 8000, 44100, 192000, 1048575].iter() {
+            assert_eq!(read_extended(&write_extended(rate)), Some(rate));
+        }
+        assert_eq!(
+            write_extended(44100),
+            [0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0]
+        );
+    }
+}
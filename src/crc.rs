@@ -0,0 +1,70 @@
+//! Checksums used by the FLAC frame format and the Ogg container.
+
+/// Updates a CRC-8 (polynomial 0x07, initial value 0) with a single byte. Used for frame
+/// headers.
+pub(crate) fn crc8_update(crc: u8, byte: u8) -> u8 {
+    let mut crc = crc ^ byte;
+    for _ in 0..8 {
+        crc = if crc & 0x80 != 0 {
+            (crc << 1) ^ 0x07
+        } else {
+            crc << 1
+        };
+    }
+    crc
+}
+
+/// Returns the CRC-8 of the bytes.
+pub(crate) fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, &byte| crc8_update(crc, byte))
+}
+
+/// Updates a CRC-16 (polynomial 0x8005, initial value 0) with a single byte. Used for whole
+/// frames.
+pub(crate) fn crc16_update(crc: u16, byte: u8) -> u16 {
+    let mut crc = crc ^ ((byte as u16) << 8);
+    for _ in 0..8 {
+        crc = if crc & 0x8000 != 0 {
+            (crc << 1) ^ 0x8005
+        } else {
+            crc << 1
+        };
+    }
+    crc
+}
+
+/// Returns the CRC-16 of the bytes.
+pub(crate) fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |crc, &byte| crc16_update(crc, byte))
+}
+
+/// Lookup table of the CRC-32 used by Ogg pages.
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Returns the CRC-32 (polynomial 0x04C11DB7, initial value 0, not reflected) of the bytes. Used
+/// for Ogg pages.
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |crc, &byte| {
+        (crc << 8) ^ CRC32_TABLE[((crc >> 24) as u8 ^ byte) as usize]
+    })
+}
@@ -0,0 +1,420 @@
+use crate::bitstream::BitReader;
+use crate::block::StreamInfo;
+use crate::crc::crc16;
+use crate::error::{Error, ErrorKind, Result};
+use crate::frame::{ChannelAssignment, FrameHeader};
+use crate::tag::Tag;
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// The decoded contents of an audio frame.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DecodedFrame {
+    /// The parsed frame header.
+    pub header: FrameHeader,
+    /// Length of the frame in bytes, including the header and the CRC-16 footer.
+    pub length: usize,
+    /// The samples of each channel.
+    pub channels: Vec<Vec<i32>>,
+}
+
+impl DecodedFrame {
+    /// Returns the interleaved samples of all channels.
+    pub fn interleaved(&self) -> Vec<i32> {
+        let block_size = self.header.block_size as usize;
+        let mut samples = Vec::with_capacity(block_size * self.channels.len());
+        for n in 0..block_size {
+            for channel in self.channels.iter() {
+                samples.push(channel[n]);
+            }
+        }
+        samples
+    }
+}
+
+/// Attempts to decode the frame at the start of the bytes. Sample rates and bits per sample
+/// which are not declared in the frame header are taken from the streaminfo block.
+pub fn decode_frame(streaminfo: &StreamInfo, bytes: &[u8]) -> Result<DecodedFrame> {
+    let (header, header_length) = FrameHeader::from_bytes(bytes)?;
+    let bits_per_sample = header.bits_per_sample.unwrap_or(streaminfo.bits_per_sample) as u32;
+    if bits_per_sample == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "frame does not declare the bits per sample",
+        ));
+    }
+
+    let mut reader = BitReader::new(&bytes[header_length..]);
+    let block_size = header.block_size as usize;
+
+    let mut channels = Vec::with_capacity(header.num_channels() as usize);
+    for channel in 0..header.num_channels() {
+        // the side channel has an extra bit
+        let side = match header.channel_assignment {
+            ChannelAssignment::LeftSide | ChannelAssignment::MidSide => channel == 1,
+            ChannelAssignment::RightSide => channel == 0,
+            ChannelAssignment::Independent(_) => false,
+        };
+        let bits = bits_per_sample + side as u32;
+        channels.push(decode_subframe(&mut reader, block_size, bits)?);
+    }
+
+    reader.align();
+    let length = header_length + reader.byte_position() + 2;
+    if length > bytes.len() {
+        return Err(Error::new(ErrorKind::InvalidInput, "frame is truncated"));
+    }
+    if crc16(&bytes[..length]) != 0 {
+        return Err(Error::new(ErrorKind::InvalidInput, "frame CRC mismatch"));
+    }
+
+    let channels = match header.channel_assignment {
+        ChannelAssignment::Independent(_) => channels
+            .into_iter()
+            .map(|samples| samples.into_iter().map(|s| s as i32).collect())
+            .collect(),
+        ChannelAssignment::LeftSide => {
+            let (left, side) = (&channels[0], &channels[1]);
+            let right = left.iter().zip(side).map(|(l, s)| (l - s) as i32).collect();
+            vec![left.iter().map(|&l| l as i32).collect(), right]
+        }
+        ChannelAssignment::RightSide => {
+            let (side, right) = (&channels[0], &channels[1]);
+            let left = side
+                .iter()
+                .zip(right)
+                .map(|(s, r)| (s + r) as i32)
+                .collect();
+            vec![left, right.iter().map(|&r| r as i32).collect()]
+        }
+        ChannelAssignment::MidSide => {
+            let (mid, side) = (&channels[0], &channels[1]);
+            let mut left = Vec::with_capacity(block_size);
+            let mut right = Vec::with_capacity(block_size);
+            for (&m, &s) in mid.iter().zip(side) {
+                let m = (m << 1) | (s & 1);
+                left.push(((m + s) >> 1) as i32);
+                right.push(((m - s) >> 1) as i32);
+            }
+            vec![left, right]
+        }
+    };
+
+    Ok(DecodedFrame {
+        header,
+        length,
+        channels,
+    })
+}
+
+/// Attempts to decode all frames of the audio data (the contents of a FLAC stream following the
+/// metadata, as returned by `Tag::skip_metadata`). Returns the interleaved samples of all
+/// channels. Decoding stops at trailing data which does not begin with a frame sync code, such
+/// as an ID3v1 tag.
+pub fn decode_audio(streaminfo: &StreamInfo, audio: &[u8]) -> Result<Vec<i32>> {
+    // the total samples may be wrong, so only trust it as far as the audio could hold them
+    let declared = streaminfo
+        .total_samples
+        .saturating_mul(streaminfo.num_channels as u64);
+    let mut samples = Vec::with_capacity(declared.min(audio.len() as u64) as usize);
+    for frame in frames(streaminfo, audio) {
+        samples.extend(frame?.interleaved());
+    }
+    Ok(samples)
+}
+
+/// Returns an iterator which decodes the frames of the audio data one at a time. See
+/// `decode_audio`. The iterator ends after the first error.
+pub fn frames<'a>(streaminfo: &'a StreamInfo, audio: &'a [u8]) -> Frames<'a> {
+    Frames {
+        streaminfo,
+        audio,
+        position: 0,
+    }
+}
+
+/// An iterator over the decoded frames of audio data, see `frames`.
+#[derive(Clone, Debug)]
+pub struct Frames<'a> {
+    streaminfo: &'a StreamInfo,
+    audio: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Result<DecodedFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = &self.audio[self.position..];
+        if !is_frame_sync(bytes) {
+            return None;
+        }
+
+        match decode_frame(self.streaminfo, bytes) {
+            Ok(frame) => {
+                self.position += frame.length;
+                Some(Ok(frame))
+            }
+            Err(err) => {
+                self.position = self.audio.len();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Returns true if the bytes begin with a frame sync code.
+pub(crate) fn is_frame_sync(bytes: &[u8]) -> bool {
+    bytes.len() >= 2 && bytes[0] == 0xFF && bytes[1] & 0xFE == 0xF8
+}
+
+/// Attempts to read a FLAC stream from the reader and decode its audio. Returns the tag and the
+/// interleaved samples of all channels.
+pub fn read_from(reader: &mut dyn Read) -> Result<(Tag, Vec<i32>)> {
+    let tag = Tag::read_from(reader)?;
+    let streaminfo = match tag.get_streaminfo() {
+        Some(streaminfo) => streaminfo,
+        None => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "stream does not contain a streaminfo block",
+            ));
+        }
+    };
+
+    let mut audio = Vec::new();
+    reader.read_to_end(&mut audio)?;
+    let samples = decode_audio(streaminfo, &audio[..])?;
+    Ok((tag, samples))
+}
+
+/// Attempts to read the FLAC file at the specified path and decode its audio. Returns the tag and
+/// the interleaved samples of all channels.
+pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<(Tag, Vec<i32>)> {
+    let file = File::open(path)?;
+    let mut reader = BufReader::new(file);
+    read_from(&mut reader)
+}
+
+/// Returns true if the MD5 signature in the streaminfo block matches the interleaved samples.
+/// Streams which do not have an MD5 signature (all zeroes) are always considered to match.
+pub fn verify_md5(streaminfo: &StreamInfo, samples: &[i32]) -> bool {
+    if streaminfo.md5.iter().all(|&b| b == 0) {
+        return true;
+    }
+
+    pcm_md5(streaminfo.bits_per_sample, samples)[..] == streaminfo.md5[..]
+}
+
+/// Returns the MD5 signature of interleaved samples, which are hashed as little-endian signed
+/// integers of the smallest whole number of bytes holding the bits per sample.
+pub(crate) fn pcm_md5(bits_per_sample: u8, samples: &[i32]) -> [u8; 16] {
+    let num_bytes = (bits_per_sample as usize).div_ceil(8);
+    let mut context = md5::Context::new();
+    let mut buffer = Vec::with_capacity(4096 * num_bytes);
+    for chunk in samples.chunks(4096) {
+        buffer.clear();
+        for sample in chunk.iter() {
+            buffer.extend(&sample.to_le_bytes()[..num_bytes]);
+        }
+        context.consume(&buffer[..]);
+    }
+    context.compute().0
+}
+
+fn decode_subframe(reader: &mut BitReader, block_size: usize, bits: u32) -> Result<Vec<i64>> {
+    if reader.read_bits(1)? != 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "invalid subframe header padding",
+        ));
+    }
+
+    let subframe_type = reader.read_bits(6)? as u8;
+
+    let wasted_bits = if reader.read_bits(1)? == 1 {
+        reader.read_unary()? + 1
+    } else {
+        0
+    };
+    if wasted_bits >= bits {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "subframe has too many wasted bits",
+        ));
+    }
+    let bits = bits - wasted_bits;
+
+    let mut samples = match subframe_type {
+        0 => vec![reader.read_signed(bits)?; block_size],
+        1 => {
+            let mut samples = Vec::with_capacity(block_size);
+            for _ in 0..block_size {
+                samples.push(reader.read_signed(bits)?);
+            }
+            samples
+        }
+        8..=12 => {
+            let order = (subframe_type - 8) as usize;
+            let mut samples = read_warmup(reader, block_size, order, bits)?;
+            read_residual(reader, block_size, order, &mut samples)?;
+            restore_fixed(&mut samples, order, bits)?;
+            samples
+        }
+        32..=63 => {
+            let order = (subframe_type - 31) as usize;
+            let mut samples = read_warmup(reader, block_size, order, bits)?;
+
+            let precision = reader.read_bits(4)? as u32 + 1;
+            if precision == 16 {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "invalid LPC coefficient precision",
+                ));
+            }
+            let shift = reader.read_signed(5)?;
+            if shift < 0 {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "negative LPC shift is not supported",
+                ));
+            }
+            let mut coefficients = Vec::with_capacity(order);
+            for _ in 0..order {
+                coefficients.push(reader.read_signed(precision)?);
+            }
+
+            read_residual(reader, block_size, order, &mut samples)?;
+            restore_lpc(&mut samples, &coefficients, shift as u32, bits)?;
+            samples
+        }
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "reserved subframe type",
+            ));
+        }
+    };
+
+    if wasted_bits > 0 {
+        for sample in samples.iter_mut() {
+            *sample <<= wasted_bits;
+        }
+    }
+
+    Ok(samples)
+}
+
+fn read_warmup(
+    reader: &mut BitReader,
+    block_size: usize,
+    order: usize,
+    bits: u32,
+) -> Result<Vec<i64>> {
+    if order > block_size {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "predictor order exceeds the block size",
+        ));
+    }
+
+    let mut samples = Vec::with_capacity(block_size);
+    for _ in 0..order {
+        samples.push(reader.read_signed(bits)?);
+    }
+    Ok(samples)
+}
+
+/// Reads the residual of a predicted subframe and appends it to the warm-up samples.
+fn read_residual(
+    reader: &mut BitReader,
+    block_size: usize,
+    order: usize,
+    samples: &mut Vec<i64>,
+) -> Result<()> {
+    let (parameter_bits, escape) = match reader.read_bits(2)? {
+        0 => (4, 0xF),
+        1 => (5, 0x1F),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "reserved residual coding method",
+            ));
+        }
+    };
+
+    let partition_order = reader.read_bits(4)? as u32;
+    let num_partitions = 1 << partition_order;
+    if block_size % num_partitions != 0 || (block_size >> partition_order) < order {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "invalid residual partition order",
+        ));
+    }
+
+    for partition in 0..num_partitions {
+        let mut num_samples = block_size >> partition_order;
+        if partition == 0 {
+            num_samples -= order;
+        }
+
+        let parameter = reader.read_bits(parameter_bits)? as u32;
+        if parameter == escape {
+            let bits = reader.read_bits(5)? as u32;
+            for _ in 0..num_samples {
+                samples.push(reader.read_signed(bits)?);
+            }
+        } else {
+            for _ in 0..num_samples {
+                let quotient = reader.read_unary()? as u64;
+                let value = (quotient << parameter) | reader.read_bits(parameter)?;
+                samples.push(((value >> 1) as i64) ^ -((value & 1) as i64));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn restore_fixed(samples: &mut [i64], order: usize, bits: u32) -> Result<()> {
+    for i in order..samples.len() {
+        let prediction = match order {
+            0 => 0,
+            1 => samples[i - 1],
+            2 => 2 * samples[i - 1] - samples[i - 2],
+            3 => 3 * samples[i - 1] - 3 * samples[i - 2] + samples[i - 3],
+            _ => 4 * samples[i - 1] - 6 * samples[i - 2] + 4 * samples[i - 3] - samples[i - 4],
+        };
+        samples[i] = restore_sample(samples[i], prediction, bits)?;
+    }
+    Ok(())
+}
+
+fn restore_lpc(samples: &mut [i64], coefficients: &[i64], shift: u32, bits: u32) -> Result<()> {
+    let order = coefficients.len();
+    for i in order..samples.len() {
+        let prediction: i64 = coefficients
+            .iter()
+            .enumerate()
+            .map(|(j, c)| c * samples[i - 1 - j])
+            .sum();
+        samples[i] = restore_sample(samples[i], prediction >> shift, bits)?;
+    }
+    Ok(())
+}
+
+/// Adds the prediction to the residual, returning an error if the sample does not fit the bits.
+/// Checking every restored sample keeps the predictions of the following samples within the
+/// range of an i64.
+fn restore_sample(residual: i64, prediction: i64, bits: u32) -> Result<i64> {
+    let limit = 1i64 << (bits - 1);
+    match residual.checked_add(prediction) {
+        Some(sample) if (-limit..limit).contains(&sample) => Ok(sample),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "predicted sample does not fit the bits per sample",
+        )),
+    }
+}
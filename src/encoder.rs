@@ -0,0 +1,904 @@
+use crate::bitstream::BitWriter;
+use crate::block::StreamInfo;
+use crate::crc::crc16;
+use crate::decoder::pcm_md5;
+use crate::error::{Error, ErrorKind, Result};
+use crate::frame::{BlockingStrategy, ChannelAssignment, FrameHeader};
+use crate::tag::Tag;
+
+use std::f64::consts::PI;
+use std::io::Write;
+
+/// Settings used to encode a FLAC stream.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct EncoderConfig {
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+    /// Number of channels. FLAC supports from 1 to 8 channels.
+    pub num_channels: u8,
+    /// Bits per sample. FLAC supports from 4 to 32 bits per sample.
+    pub bits_per_sample: u8,
+    /// Compression level, from 0 (fastest) to 8 (smallest). The levels resemble those of the
+    /// `flac` command line tool.
+    pub compression_level: u8,
+}
+
+impl EncoderConfig {
+    /// Returns a new `EncoderConfig` using the default compression level of 5.
+    pub fn new(sample_rate: u32, num_channels: u8, bits_per_sample: u8) -> EncoderConfig {
+        EncoderConfig {
+            sample_rate,
+            num_channels,
+            bits_per_sample,
+            compression_level: 5,
+        }
+    }
+}
+
+/// The encoding parameters of a compression level.
+struct Level {
+    block_size: usize,
+    /// Whether stereo streams are decorrelated.
+    stereo: bool,
+    max_fixed_order: usize,
+    /// The maximum LPC order, or 0 to only use fixed predictors.
+    max_lpc_order: usize,
+    /// Whether every LPC order is tried instead of the estimated best order.
+    exhaustive: bool,
+    max_partition_order: u32,
+}
+
+const LEVELS: [Level; 9] = [
+    Level {
+        block_size: 1152,
+        stereo: false,
+        max_fixed_order: 2,
+        max_lpc_order: 0,
+        exhaustive: false,
+        max_partition_order: 3,
+    },
+    Level {
+        block_size: 1152,
+        stereo: true,
+        max_fixed_order: 2,
+        max_lpc_order: 0,
+        exhaustive: false,
+        max_partition_order: 3,
+    },
+    Level {
+        block_size: 1152,
+        stereo: true,
+        max_fixed_order: 4,
+        max_lpc_order: 0,
+        exhaustive: false,
+        max_partition_order: 3,
+    },
+    Level {
+        block_size: 4096,
+        stereo: false,
+        max_fixed_order: 4,
+        max_lpc_order: 6,
+        exhaustive: false,
+        max_partition_order: 4,
+    },
+    Level {
+        block_size: 4096,
+        stereo: true,
+        max_fixed_order: 4,
+        max_lpc_order: 8,
+        exhaustive: false,
+        max_partition_order: 4,
+    },
+    Level {
+        block_size: 4096,
+        stereo: true,
+        max_fixed_order: 4,
+        max_lpc_order: 8,
+        exhaustive: false,
+        max_partition_order: 5,
+    },
+    Level {
+        block_size: 4096,
+        stereo: true,
+        max_fixed_order: 4,
+        max_lpc_order: 8,
+        exhaustive: false,
+        max_partition_order: 6,
+    },
+    Level {
+        block_size: 4096,
+        stereo: true,
+        max_fixed_order: 4,
+        max_lpc_order: 12,
+        exhaustive: false,
+        max_partition_order: 6,
+    },
+    Level {
+        block_size: 4096,
+        stereo: true,
+        max_fixed_order: 4,
+        max_lpc_order: 12,
+        exhaustive: true,
+        max_partition_order: 6,
+    },
+];
+
+/// Attempts to encode interleaved samples as a FLAC stream and write it to the writer. Each
+/// sample must lie within the range of the configured bits per sample, so 24-bit audio is passed
+/// as `i32` values between -8388608 and 8388607.
+///
+/// The streaminfo block is computed from the audio, including its MD5 signature. All other
+/// metadata blocks are taken from the tag. Returns the streaminfo block which was written.
+///
+/// # Example
+/// ```
+/// use metaflac::encoder::{self, EncoderConfig};
+/// use metaflac::Tag;
+///
+/// let samples: Vec<i32> = (0..44100).map(|n| ((n % 100) * 100) as i32).collect();
+///
+/// let mut tag = Tag::new();
+/// tag.set_vorbis("TITLE", vec!["Sawtooth"]);
+///
+/// let mut flac = Vec::new();
+/// let config = EncoderConfig::new(44100, 1, 16);
+/// let streaminfo = encoder::encode(&config, &samples, &tag, &mut flac).unwrap();
+/// assert_eq!(streaminfo.total_samples, 44100);
+///
+/// let tag = Tag::read_from(&mut &flac[..]).unwrap();
+/// assert_eq!(tag.get_vorbis("TITLE").unwrap().next(), Some("Sawtooth"));
+/// assert_eq!(tag.get_streaminfo(), Some(&streaminfo));
+/// ```
+pub fn encode(
+    config: &EncoderConfig,
+    samples: &[i32],
+    tag: &Tag,
+    writer: &mut dyn Write,
+) -> Result<StreamInfo> {
+    validate(config, samples)?;
+
+    let level = &LEVELS[config.compression_level as usize];
+    let num_channels = config.num_channels as usize;
+    let bits_per_sample = config.bits_per_sample as u32;
+
+    let mut audio = Vec::new();
+    let mut min_frame_size = u32::MAX;
+    let mut max_frame_size = 0;
+    for (number, block) in samples.chunks(level.block_size * num_channels).enumerate() {
+        let block_size = block.len() / num_channels;
+        let channels: Vec<Vec<i64>> = (0..num_channels)
+            .map(|channel| {
+                block
+                    .iter()
+                    .skip(channel)
+                    .step_by(num_channels)
+                    .map(|&s| s as i64)
+                    .collect()
+            })
+            .collect();
+
+        let (channel_assignment, subframes) = if num_channels == 2 && level.stereo {
+            encode_stereo(&channels[0], &channels[1], bits_per_sample, level)
+        } else {
+            let subframes = channels
+                .iter()
+                .map(|samples| encode_subframe(samples, bits_per_sample, level))
+                .collect();
+            (
+                ChannelAssignment::Independent(num_channels as u8),
+                subframes,
+            )
+        };
+
+        let header = FrameHeader {
+            blocking_strategy: BlockingStrategy::Fixed,
+            block_size: block_size as u32,
+            sample_rate: Some(config.sample_rate),
+            channel_assignment,
+            bits_per_sample: Some(config.bits_per_sample),
+            number: number as u64,
+        };
+
+        let mut frame = header.to_bytes()?;
+        let mut writer = BitWriter::new();
+        for subframe in subframes.iter() {
+            subframe.write_to(&mut writer);
+        }
+        frame.extend(writer.into_bytes());
+        let crc = crc16(&frame[..]);
+        frame.extend(crc.to_be_bytes().iter());
+
+        min_frame_size = min_frame_size.min(frame.len() as u32);
+        max_frame_size = max_frame_size.max(frame.len() as u32);
+        audio.extend(frame);
+    }
+
+    let mut streaminfo = StreamInfo::new();
+    streaminfo.min_block_size = level.block_size as u16;
+    streaminfo.max_block_size = level.block_size as u16;
+    streaminfo.min_frame_size = if max_frame_size == 0 {
+        0
+    } else {
+        min_frame_size
+    };
+    streaminfo.max_frame_size = max_frame_size;
+    streaminfo.sample_rate = config.sample_rate;
+    streaminfo.num_channels = config.num_channels;
+    streaminfo.bits_per_sample = config.bits_per_sample;
+    streaminfo.total_samples = (samples.len() / num_channels) as u64;
+    streaminfo.md5 = pcm_md5(config.bits_per_sample, samples).to_vec();
+
+    let mut tag = tag.clone();
+    tag.set_streaminfo(streaminfo.clone());
+    tag.write_to(writer)?;
+    writer.write_all(&audio[..])?;
+
+    Ok(streaminfo)
+}
+
+/// Attempts to encode interleaved 16-bit samples as a FLAC stream and write it to the writer. See
+/// `encode` for details. An `Error::InvalidInput` will be returned if the configuration does not
+/// use 16 bits per sample.
+pub fn encode_i16(
+    config: &EncoderConfig,
+    samples: &[i16],
+    tag: &Tag,
+    writer: &mut dyn Write,
+) -> Result<StreamInfo> {
+    if config.bits_per_sample != 16 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "16-bit samples require 16 bits per sample",
+        ));
+    }
+
+    let samples: Vec<i32> = samples.iter().map(|&s| s as i32).collect();
+    encode(config, &samples[..], tag, writer)
+}
+
+fn validate(config: &EncoderConfig, samples: &[i32]) -> Result<()> {
+    if config.compression_level as usize >= LEVELS.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "compression level must be between 0 and 8",
+        ));
+    }
+    if config.num_channels == 0 || config.num_channels > 8 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "number of channels must be between 1 and 8",
+        ));
+    }
+    if config.bits_per_sample < 4 || config.bits_per_sample > 32 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "bits per sample must be between 4 and 32",
+        ));
+    }
+    if config.sample_rate == 0 || config.sample_rate > 0xF_FF_FF {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "sample rate must be between 1 and 1048575 Hz",
+        ));
+    }
+    if samples.len() % config.num_channels as usize != 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "number of samples is not a multiple of the number of channels",
+        ));
+    }
+    if (samples.len() / config.num_channels as usize) as u64 > 0xF_FF_FF_FF_FF {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "too many samples for a FLAC stream",
+        ));
+    }
+
+    let max = (1i64 << (config.bits_per_sample - 1)) - 1;
+    let min = -(1i64 << (config.bits_per_sample - 1));
+    if samples
+        .iter()
+        .any(|&s| (s as i64) < min || (s as i64) > max)
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "sample out of range for the bits per sample",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Encodes a stereo block using whichever channel assignment results in the smallest frame.
+fn encode_stereo(
+    left: &[i64],
+    right: &[i64],
+    bits_per_sample: u32,
+    level: &Level,
+) -> (ChannelAssignment, Vec<Subframe>) {
+    let side: Vec<i64> = left.iter().zip(right).map(|(l, r)| l - r).collect();
+    let mid: Vec<i64> = left.iter().zip(right).map(|(l, r)| (l + r) >> 1).collect();
+
+    let left = encode_subframe(left, bits_per_sample, level);
+    let right = encode_subframe(right, bits_per_sample, level);
+    let side = encode_subframe(&side[..], bits_per_sample + 1, level);
+    let mid = encode_subframe(&mid[..], bits_per_sample, level);
+
+    let candidates = [
+        (ChannelAssignment::Independent(2), left.size + right.size),
+        (ChannelAssignment::LeftSide, left.size + side.size),
+        (ChannelAssignment::RightSide, side.size + right.size),
+        (ChannelAssignment::MidSide, mid.size + side.size),
+    ];
+    let best = candidates.iter().min_by_key(|&&(_, size)| size).unwrap().0;
+
+    let subframes = match best {
+        ChannelAssignment::LeftSide => vec![left, side],
+        ChannelAssignment::RightSide => vec![side, right],
+        ChannelAssignment::MidSide => vec![mid, side],
+        ChannelAssignment::Independent(_) => vec![left, right],
+    };
+    (best, subframes)
+}
+
+// Subframe {{{
+enum Predictor {
+    Constant,
+    Verbatim,
+    Fixed,
+    Lpc {
+        precision: u32,
+        shift: u32,
+        coefficients: Vec<i64>,
+    },
+}
+
+struct Residual {
+    values: Vec<i64>,
+    partition_order: u32,
+    parameters: Vec<u32>,
+    /// Whether 5-bit rice parameters are used.
+    rice2: bool,
+    /// The size of the coded residual in bits.
+    size: u64,
+}
+
+/// An encoded subframe, ready to be written.
+struct Subframe {
+    predictor: Predictor,
+    wasted_bits: u32,
+    /// Bits per sample after removing the wasted bits.
+    bits: u32,
+    /// The warm-up samples of a predicted subframe, or all samples of a verbatim subframe.
+    warmup: Vec<i64>,
+    residual: Option<Residual>,
+    /// The size of the subframe in bits.
+    size: u64,
+}
+
+impl Subframe {
+    fn write_to(&self, writer: &mut BitWriter) {
+        let subframe_type = match self.predictor {
+            Predictor::Constant => 0,
+            Predictor::Verbatim => 1,
+            Predictor::Fixed => 8 + self.warmup.len() as u64,
+            Predictor::Lpc { .. } => 31 + self.warmup.len() as u64,
+        };
+        writer.write_bits((subframe_type << 1) | (self.wasted_bits > 0) as u64, 8);
+        if self.wasted_bits > 0 {
+            writer.write_unary(self.wasted_bits - 1);
+        }
+
+        for &sample in self.warmup.iter() {
+            writer.write_signed(sample, self.bits);
+        }
+
+        if let Predictor::Lpc {
+            precision,
+            shift,
+            ref coefficients,
+        } = self.predictor
+        {
+            writer.write_bits(precision as u64 - 1, 4);
+            writer.write_bits(shift as u64, 5);
+            for &coefficient in coefficients.iter() {
+                writer.write_signed(coefficient, precision);
+            }
+        }
+
+        if let Some(ref residual) = self.residual {
+            residual.write_to(writer, self.warmup.len());
+        }
+    }
+}
+
+impl Residual {
+    fn write_to(&self, writer: &mut BitWriter, order: usize) {
+        let parameter_bits = if self.rice2 { 5 } else { 4 };
+        writer.write_bits(self.rice2 as u64, 2);
+        writer.write_bits(self.partition_order as u64, 4);
+
+        let block_size = self.values.len() + order;
+        let mut start = 0;
+        for (partition, &parameter) in self.parameters.iter().enumerate() {
+            let mut end = start + (block_size >> self.partition_order);
+            if partition == 0 {
+                end -= order;
+            }
+
+            writer.write_bits(parameter as u64, parameter_bits);
+            for &value in self.values[start..end].iter() {
+                let value = zigzag(value);
+                writer.write_unary((value >> parameter) as u32);
+                writer.write_bits(value, parameter);
+            }
+            start = end;
+        }
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Encodes the samples of a channel using the smallest of the subframe types tried by the
+/// compression level.
+fn encode_subframe(samples: &[i64], bits: u32, level: &Level) -> Subframe {
+    if samples.iter().all(|&s| s == samples[0]) {
+        return Subframe {
+            predictor: Predictor::Constant,
+            wasted_bits: 0,
+            bits,
+            warmup: vec![samples[0]],
+            residual: None,
+            size: 8 + bits as u64,
+        };
+    }
+
+    let wasted_bits = samples.iter().fold(0, |acc, &s| acc | s).trailing_zeros();
+    let shifted: Vec<i64>;
+    let samples = if wasted_bits > 0 {
+        shifted = samples.iter().map(|&s| s >> wasted_bits).collect();
+        &shifted[..]
+    } else {
+        samples
+    };
+    let bits = bits - wasted_bits;
+    let header_size = 8 + wasted_bits as u64;
+
+    let mut best = Subframe {
+        predictor: Predictor::Verbatim,
+        wasted_bits,
+        bits,
+        warmup: samples.to_vec(),
+        residual: None,
+        size: header_size + bits as u64 * samples.len() as u64,
+    };
+
+    for order in 0..=level.max_fixed_order.min(samples.len() - 1) {
+        let residual = match fixed_residual(samples, order) {
+            Some(residual) => rice_code(residual, samples.len(), order, level),
+            None => continue,
+        };
+        let size = header_size + (order as u64 * bits as u64) + residual.size;
+        if size < best.size {
+            best = Subframe {
+                predictor: Predictor::Fixed,
+                wasted_bits,
+                bits,
+                warmup: samples[..order].to_vec(),
+                residual: Some(residual),
+                size,
+            };
+        }
+    }
+
+    if level.max_lpc_order > 0 && samples.len() > level.max_lpc_order {
+        if let Some(subframe) = encode_lpc(samples, bits, wasted_bits, level) {
+            if subframe.size < best.size {
+                best = subframe;
+            }
+        }
+    }
+
+    best
+}
+
+/// Returns the residual of a fixed predictor, or `None` if it does not fit in 32 bits.
+fn fixed_residual(samples: &[i64], order: usize) -> Option<Vec<i64>> {
+    let mut residual = Vec::with_capacity(samples.len() - order);
+    for i in order..samples.len() {
+        let prediction = match order {
+            0 => 0,
+            1 => samples[i - 1],
+            2 => 2 * samples[i - 1] - samples[i - 2],
+            3 => 3 * samples[i - 1] - 3 * samples[i - 2] + samples[i - 3],
+            _ => 4 * samples[i - 1] - 6 * samples[i - 2] + 4 * samples[i - 3] - samples[i - 4],
+        };
+        residual.push(fits_i32(samples[i] - prediction)?);
+    }
+    Some(residual)
+}
+
+fn fits_i32(value: i64) -> Option<i64> {
+    if value < i32::MIN as i64 || value > i32::MAX as i64 {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+fn encode_lpc(samples: &[i64], bits: u32, wasted_bits: u32, level: &Level) -> Option<Subframe> {
+    let n = samples.len();
+
+    // Tukey(0.5) window
+    let taper = n / 4;
+    let windowed: Vec<f64> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| {
+            let distance = i.min(n - 1 - i);
+            let weight = if distance < taper {
+                0.5 - 0.5 * (PI * distance as f64 / taper as f64).cos()
+            } else {
+                1.0
+            };
+            s as f64 * weight
+        })
+        .collect();
+
+    let max_order = level.max_lpc_order;
+    let autocorrelation: Vec<f64> = (0..=max_order)
+        .map(|lag| (lag..n).map(|i| windowed[i] * windowed[i - lag]).sum())
+        .collect();
+
+    let (coefficients, errors) = levinson_durbin(&autocorrelation[..]);
+    if coefficients.is_empty() {
+        return None;
+    }
+
+    let precision = match n {
+        0..=192 => 7,
+        193..=384 => 8,
+        385..=576 => 9,
+        577..=1152 => 10,
+        1153..=2304 => 11,
+        2305..=4608 => 12,
+        _ => 13,
+    };
+
+    let orders: Vec<usize> = if level.exhaustive {
+        (1..=coefficients.len()).collect()
+    } else {
+        // estimate the best order from the prediction error of each order
+        let overhead = (bits + precision) as f64;
+        let estimate = |order: usize| {
+            let error = errors[order - 1];
+            let bits_per_sample = if error > 0.0 {
+                (0.5 * (0.5 * error / n as f64).log2()).max(0.0)
+            } else {
+                0.0
+            };
+            bits_per_sample * (n - order) as f64 + order as f64 * overhead
+        };
+        let best = (1..=coefficients.len())
+            .min_by(|&a, &b| estimate(a).partial_cmp(&estimate(b)).unwrap())
+            .unwrap();
+        vec![best]
+    };
+
+    let mut best: Option<Subframe> = None;
+    for order in orders {
+        let (quantized, shift) = match quantize(&coefficients[order - 1], precision) {
+            Some(quantized) => quantized,
+            None => continue,
+        };
+
+        let residual = match lpc_residual(samples, &quantized[..], shift) {
+            Some(residual) => rice_code(residual, n, order, level),
+            None => continue,
+        };
+
+        let size = 8
+            + wasted_bits as u64
+            + (order as u64 * bits as u64)
+            + 4
+            + 5
+            + (order as u64 * precision as u64)
+            + residual.size;
+        if best.as_ref().map_or(true, |best| size < best.size) {
+            best = Some(Subframe {
+                predictor: Predictor::Lpc {
+                    precision,
+                    shift,
+                    coefficients: quantized,
+                },
+                wasted_bits,
+                bits,
+                warmup: samples[..order].to_vec(),
+                residual: Some(residual),
+                size,
+            });
+        }
+    }
+
+    best
+}
+
+/// Computes the predictor coefficients of every order up to the number of autocorrelation lags
+/// using the Levinson-Durbin recursion. Returns the coefficients and the prediction error of each
+/// order.
+fn levinson_durbin(autocorrelation: &[f64]) -> (Vec<Vec<f64>>, Vec<f64>) {
+    let max_order = autocorrelation.len() - 1;
+    let mut coefficients = Vec::with_capacity(max_order);
+    let mut errors = Vec::with_capacity(max_order);
+
+    let mut error = autocorrelation[0];
+    let mut lpc = vec![0.0; max_order];
+    for i in 0..max_order {
+        if error <= 0.0 {
+            break;
+        }
+
+        let mut reflection = -autocorrelation[i + 1];
+        for j in 0..i {
+            reflection -= lpc[j] * autocorrelation[i - j];
+        }
+        reflection /= error;
+
+        lpc[i] = reflection;
+        for j in 0..i / 2 {
+            let tmp = lpc[j];
+            lpc[j] += reflection * lpc[i - 1 - j];
+            lpc[i - 1 - j] += reflection * tmp;
+        }
+        if i % 2 == 1 {
+            lpc[i / 2] += lpc[i / 2] * reflection;
+        }
+        error *= 1.0 - reflection * reflection;
+
+        coefficients.push(lpc[..=i].iter().map(|c| -c).collect());
+        errors.push(error);
+    }
+
+    (coefficients, errors)
+}
+
+/// Quantizes predictor coefficients to the precision. Returns the quantized coefficients and the
+/// shift, or `None` if the coefficients cannot be represented.
+fn quantize(coefficients: &[f64], precision: u32) -> Option<(Vec<i64>, u32)> {
+    let max = coefficients.iter().fold(0.0f64, |acc, c| acc.max(c.abs()));
+    if max <= 0.0 || !max.is_finite() {
+        return None;
+    }
+
+    let shift = (precision as i32 - 1) - max.log2().floor() as i32 - 1;
+    if shift < 0 {
+        return None;
+    }
+    let shift = shift.min(15) as u32;
+
+    let limit = (1i64 << (precision - 1)) - 1;
+    let mut error = 0.0;
+    let mut quantized = Vec::with_capacity(coefficients.len());
+    for &coefficient in coefficients.iter() {
+        error += coefficient * (1 << shift) as f64;
+        let q = (error.round() as i64).clamp(-limit - 1, limit);
+        error -= q as f64;
+        quantized.push(q);
+    }
+
+    Some((quantized, shift))
+}
+
+/// Returns the residual of a quantized LPC predictor, or `None` if it does not fit in 32 bits.
+fn lpc_residual(samples: &[i64], coefficients: &[i64], shift: u32) -> Option<Vec<i64>> {
+    let order = coefficients.len();
+    let mut residual = Vec::with_capacity(samples.len() - order);
+    for i in order..samples.len() {
+        let prediction: i64 = coefficients
+            .iter()
+            .enumerate()
+            .map(|(j, c)| c * samples[i - 1 - j])
+            .sum();
+        residual.push(fits_i32(samples[i] - (prediction >> shift))?);
+    }
+    Some(residual)
+}
+
+/// Chooses the partition order and rice parameters which code the residual in the fewest bits.
+fn rice_code(values: Vec<i64>, block_size: usize, order: usize, level: &Level) -> Residual {
+    let zigzagged: Vec<u64> = values.iter().map(|&v| zigzag(v)).collect();
+
+    let mut best: Option<Residual> = None;
+    for partition_order in 0..=level.max_partition_order {
+        let partition_size = block_size >> partition_order;
+        if block_size % (1 << partition_order) != 0 || partition_size <= order {
+            break;
+        }
+
+        let mut parameters = Vec::with_capacity(1 << partition_order);
+        let mut size = 0;
+        let mut start = 0;
+        for partition in 0..(1 << partition_order) {
+            let mut end = start + partition_size;
+            if partition == 0 {
+                end -= order;
+            }
+            let (parameter, bits) = rice_parameter(&zigzagged[start..end]);
+            parameters.push(parameter);
+            size += bits;
+            start = end;
+        }
+
+        let rice2 = parameters.iter().any(|&p| p > 14);
+        let parameter_bits = if rice2 { 5 } else { 4 };
+        size += 2 + 4 + parameter_bits * parameters.len() as u64;
+
+        if best.as_ref().map_or(true, |best| size < best.size) {
+            best = Some(Residual {
+                values: Vec::new(),
+                partition_order,
+                parameters,
+                rice2,
+                size,
+            });
+        }
+    }
+
+    let mut best = best.unwrap();
+    best.values = values;
+    best
+}
+
+/// Returns the rice parameter which codes the zigzagged values in the fewest bits, along with
+/// the number of bits.
+fn rice_parameter(values: &[u64]) -> (u32, u64) {
+    if values.is_empty() {
+        return (0, 0);
+    }
+
+    let bits = |parameter: u32| -> u64 {
+        values.len() as u64 * (parameter as u64 + 1)
+            + values.iter().map(|v| v >> parameter).sum::<u64>()
+    };
+
+    let mean = values.iter().sum::<u64>() / values.len() as u64;
+    let estimate = if mean > 0 {
+        63 - mean.leading_zeros()
+    } else {
+        0
+    };
+
+    (estimate.saturating_sub(1)..=(estimate + 1).min(30))
+        .map(|parameter| (parameter, bits(parameter)))
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap()
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder;
+
+    /// Returns interleaved samples of a sine wave mixed with noise, scaled to the bits per sample.
+    fn signal(num_samples: usize, num_channels: usize, bits_per_sample: u32) -> Vec<i32> {
+        let amplitude = ((1i64 << (bits_per_sample - 1)) - 1) as f64;
+        let mut seed = 0x1234_5678u32;
+        let mut samples = Vec::with_capacity(num_samples * num_channels);
+        for n in 0..num_samples {
+            for channel in 0..num_channels {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let noise = (seed >> 16) as f64 / 65536.0 - 0.5;
+                let phase = n as f64 * (0.01 + channel as f64 * 0.003);
+                let value = 0.7 * phase.sin() + 0.05 * noise;
+                samples.push((value * amplitude) as i32);
+            }
+        }
+        samples
+    }
+
+    fn round_trip(config: &EncoderConfig, samples: &[i32]) -> usize {
+        let mut flac = Vec::new();
+        let streaminfo = encode(config, samples, &Tag::new(), &mut flac).unwrap();
+
+        let (tag, decoded) = decoder::read_from(&mut &flac[..]).unwrap();
+        assert_eq!(tag.get_streaminfo(), Some(&streaminfo));
+        assert_eq!(&decoded[..], samples);
+        assert!(decoder::verify_md5(&streaminfo, &decoded[..]));
+
+        let audio = Tag::skip_metadata(&mut std::io::Cursor::new(&flac[..]));
+        assert!(streaminfo.check_frames(&audio[..]).is_empty());
+
+        flac.len()
+    }
+
+    #[test]
+    fn round_trip_levels() {
+        let samples = signal(10_000, 2, 16);
+        let mut sizes = Vec::new();
+        for level in 0..=8 {
+            let mut config = EncoderConfig::new(44100, 2, 16);
+            config.compression_level = level;
+            sizes.push(round_trip(&config, &samples[..]));
+        }
+
+        // all levels compress, and the LPC levels compress better than the fixed ones
+        assert!(sizes.iter().all(|&size| size < samples.len() * 2));
+        assert!(sizes[8] < sizes[0]);
+    }
+
+    #[test]
+    fn round_trip_bit_depths() {
+        round_trip(&EncoderConfig::new(48000, 1, 24), &signal(5000, 1, 24)[..]);
+        round_trip(&EncoderConfig::new(96000, 2, 32), &signal(5000, 2, 32)[..]);
+        round_trip(&EncoderConfig::new(8000, 3, 12), &signal(5000, 3, 12)[..]);
+
+        // wasted bits, constant subframes and a short final block
+        let samples: Vec<i32> = signal(4099, 2, 8).iter().map(|s| s & !0x3).collect();
+        round_trip(&EncoderConfig::new(22050, 2, 8), &samples[..]);
+        round_trip(&EncoderConfig::new(22050, 2, 8), &[0; 2 * 5000][..]);
+
+        let extremes: Vec<i32> = (0..2000)
+            .map(|n| if n % 3 == 0 { i32::MIN } else { i32::MAX })
+            .collect();
+        round_trip(&EncoderConfig::new(44100, 2, 32), &extremes[..]);
+    }
+
+    #[test]
+    fn decode_untrusted_input() {
+        let samples = signal(5000, 1, 16);
+        let mut flac = Vec::new();
+        let mut streaminfo = encode(
+            &EncoderConfig::new(44100, 1, 16),
+            &samples,
+            &Tag::new(),
+            &mut flac,
+        )
+        .unwrap();
+        let mut audio = Tag::skip_metadata(&mut std::io::Cursor::new(&flac[..]));
+
+        // a wrong total number of samples and a trailing ID3v1 tag
+        streaminfo.total_samples = (1 << 36) - 1;
+        streaminfo.num_channels = 8;
+        audio.extend(b"TAG");
+        audio.extend(&[0; 125][..]);
+        assert_eq!(decoder::decode_audio(&streaminfo, &audio).unwrap(), samples);
+
+        // a fixed subframe whose residual overflows the bits per sample
+        let mut header = FrameHeader::new();
+        header.block_size = 16;
+        header.sample_rate = Some(44100);
+        header.bits_per_sample = Some(8);
+        let mut frame = header.to_bytes().unwrap();
+        let mut writer = BitWriter::new();
+        writer.write_bits(9 << 1, 8);
+        writer.write_signed(127, 8);
+        writer.write_bits(0, 6);
+        writer.write_bits(0xF, 4);
+        writer.write_bits(20, 5);
+        for _ in 1..16 {
+            writer.write_signed(100_000, 20);
+        }
+        frame.extend(writer.into_bytes());
+        let crc = crc16(&frame[..]);
+        frame.extend(crc.to_be_bytes().iter());
+
+        let error = decoder::decode_audio(&streaminfo, &frame).unwrap_err();
+        assert_eq!(
+            error.description,
+            "predicted sample does not fit the bits per sample"
+        );
+    }
+
+    #[test]
+    fn invalid_config() {
+        let mut flac = Vec::new();
+        let tag = Tag::new();
+        assert!(encode(&EncoderConfig::new(44100, 2, 16), &[0; 3], &tag, &mut flac).is_err());
+        assert!(encode(&EncoderConfig::new(44100, 1, 16), &[40000], &tag, &mut flac).is_err());
+        assert!(encode(&EncoderConfig::new(0, 1, 16), &[0], &tag, &mut flac).is_err());
+        assert!(encode_i16(&EncoderConfig::new(44100, 1, 24), &[0], &tag, &mut flac).is_err());
+    }
+}
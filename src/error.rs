@@ -14,6 +14,9 @@ pub enum ErrorKind {
     /// An error kind indicating that a string decoding error has occurred. Contains the invalid
     /// bytes.
     StringDecoding(string::FromUtf8Error),
+    /// An error kind indicating that a vorbis comment contains keys which are not valid field
+    /// names. Contains the invalid keys.
+    InvalidFieldNames(Vec<String>),
     /// An error kind indicating that some input was invalid.
     InvalidInput,
 }
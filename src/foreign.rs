@@ -0,0 +1,1148 @@
+use crate::block::{Application, Block, BlockType};
+use crate::error::{Error, ErrorKind, Result};
+use crate::tag::Tag;
+
+use std::convert::{TryFrom, TryInto};
+
+/// The GUID suffix of the Wave64 `riff` and `list` chunks.
+const W64_RIFF_SUFFIX: [u8; 12] = [
+    0x2E, 0x91, 0xCF, 0x11, 0xA5, 0xD6, 0x28, 0xDB, 0x04, 0xC1, 0x00, 0x00,
+];
+/// The GUID suffix of the other Wave64 chunks.
+const W64_CHUNK_SUFFIX: [u8; 12] = [
+    0xF3, 0xAC, 0xD3, 0x11, 0x8C, 0xD1, 0x00, 0xC0, 0x4F, 0x8E, 0xDB, 0x8A,
+];
+
+// ForeignFormat {{{
+/// Container formats whose chunks can be kept in application blocks.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ForeignFormat {
+    /// A RIFF WAVE file, kept in `riff` application blocks.
+    Riff,
+    /// An AIFF or AIFF-C file, kept in `aiff` application blocks.
+    Aiff,
+    /// A Sony Wave64 file, kept in `w64 ` application blocks.
+    Wave64,
+}
+
+impl ForeignFormat {
+    /// Returns the id of the application blocks holding chunks of the format.
+    pub fn application_id(self) -> &'static [u8; 4] {
+        match self {
+            ForeignFormat::Riff => b"riff",
+            ForeignFormat::Aiff => b"aiff",
+            ForeignFormat::Wave64 => b"w64 ",
+        }
+    }
+
+    /// Returns the format whose chunks are kept in application blocks with the specified id.
+    pub fn from_application_id(id: &[u8; 4]) -> Option<ForeignFormat> {
+        match id {
+            b"riff" => Some(ForeignFormat::Riff),
+            b"aiff" => Some(ForeignFormat::Aiff),
+            b"w64 " => Some(ForeignFormat::Wave64),
+            _ => None,
+        }
+    }
+
+    /// Returns the format of the file which begins with the bytes.
+    fn detect(bytes: &[u8]) -> Option<ForeignFormat> {
+        if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WAVE" {
+            Some(ForeignFormat::Riff)
+        } else if bytes.len() >= 12
+            && &bytes[..4] == b"FORM"
+            && (&bytes[8..12] == b"AIFF" || &bytes[8..12] == b"AIFC")
+        {
+            Some(ForeignFormat::Aiff)
+        } else if bytes.len() >= 40
+            && &bytes[..4] == b"riff"
+            && bytes[4..16] == W64_RIFF_SUFFIX
+            && &bytes[24..28] == b"wave"
+            && bytes[28..40] == W64_CHUNK_SUFFIX
+        {
+            Some(ForeignFormat::Wave64)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the length of the file header.
+    fn header_length(self) -> usize {
+        match self {
+            ForeignFormat::Riff | ForeignFormat::Aiff => 12,
+            ForeignFormat::Wave64 => 40,
+        }
+    }
+
+    /// Returns the length of a chunk header.
+    fn chunk_header_length(self) -> usize {
+        match self {
+            ForeignFormat::Riff | ForeignFormat::Aiff => 8,
+            ForeignFormat::Wave64 => 24,
+        }
+    }
+
+    /// Returns the id of the chunk which holds the audio data.
+    fn data_chunk_id(self) -> &'static [u8; 4] {
+        match self {
+            ForeignFormat::Riff | ForeignFormat::Wave64 => b"data",
+            ForeignFormat::Aiff => b"SSND",
+        }
+    }
+
+    /// Returns the length of the data of the chunk (or file) which begins with the header.
+    fn read_size(self, header: &[u8]) -> u64 {
+        match self {
+            ForeignFormat::Riff => u32::from_le_bytes(header[4..8].try_into().unwrap()) as u64,
+            ForeignFormat::Aiff => u32::from_be_bytes(header[4..8].try_into().unwrap()) as u64,
+            // the size of a Wave64 chunk includes its header
+            ForeignFormat::Wave64 => {
+                u64::from_le_bytes(header[16..24].try_into().unwrap()).saturating_sub(24)
+            }
+        }
+    }
+
+    /// Writes the length of the data of the chunk (or file) to the header.
+    fn write_size(self, header: &mut [u8], size: u64) {
+        match self {
+            ForeignFormat::Riff => header[4..8].copy_from_slice(&(size as u32).to_le_bytes()),
+            ForeignFormat::Aiff => header[4..8].copy_from_slice(&(size as u32).to_be_bytes()),
+            ForeignFormat::Wave64 => header[16..24].copy_from_slice(&(size + 24).to_le_bytes()),
+        }
+    }
+
+    /// Returns the number of padding bytes which follow chunk data of the specified length.
+    fn padding(self, size: u64) -> usize {
+        match self {
+            ForeignFormat::Riff | ForeignFormat::Aiff => (size % 2) as usize,
+            ForeignFormat::Wave64 => ((8 - (size + 24) % 8) % 8) as usize,
+        }
+    }
+}
+//}}}
+
+// ForeignMetadata {{{
+/// The chunks of a WAV, AIFF or Wave64 file other than the audio data, stored in the same way as
+/// `flac --keep-foreign-metadata`. Each application block holds the bytes of one chunk, including
+/// its header and padding, in the order they appear in the file. The first block holds the file
+/// header and the block of the audio data chunk only holds the chunk header.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ForeignMetadata {
+    /// The format of the original file.
+    pub format: ForeignFormat,
+    /// The contents of the application blocks.
+    pub blocks: Vec<Vec<u8>>,
+}
+
+impl ForeignMetadata {
+    /// Returns the foreign metadata of a file which only contains an audio data chunk of the
+    /// specified length.
+    pub fn new(format: ForeignFormat, audio_length: u64) -> ForeignMetadata {
+        let mut header = match format {
+            ForeignFormat::Riff => b"RIFF\0\0\0\0WAVE".to_vec(),
+            ForeignFormat::Aiff => b"FORM\0\0\0\0AIFF".to_vec(),
+            ForeignFormat::Wave64 => {
+                let mut header = b"riff".to_vec();
+                header.extend(&W64_RIFF_SUFFIX);
+                header.extend(&[0; 8]);
+                header.extend(b"wave");
+                header.extend(&W64_CHUNK_SUFFIX);
+                header
+            }
+        };
+
+        let mut data = format.data_chunk_id().to_vec();
+        if format == ForeignFormat::Wave64 {
+            data.extend(&W64_CHUNK_SUFFIX);
+        }
+        data.resize(format.chunk_header_length(), 0);
+        let mut data_size = audio_length;
+        if format == ForeignFormat::Aiff {
+            // the offset and block size fields
+            data.extend(&[0; 8]);
+            data_size += 8;
+        }
+        format.write_size(&mut data, data_size);
+
+        let size = (format.header_length() - format.chunk_header_length()) as u64
+            + data.len() as u64
+            + audio_length
+            + format.padding(data_size) as u64;
+        format.write_size(&mut header, size);
+
+        ForeignMetadata {
+            format,
+            blocks: vec![header, data],
+        }
+    }
+
+    /// Attempts to split the bytes of a WAV, AIFF or Wave64 file into its foreign metadata and
+    /// the bytes of the audio data.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::foreign::{ForeignFormat, ForeignMetadata};
+    ///
+    /// let mut wav = b"RIFF\x1e\0\0\0WAVE".to_vec();
+    /// wav.extend(b"iXML\x08\0\0\0<BWFXML>");
+    /// wav.extend(b"data\x02\0\0\0\x01\x02");
+    ///
+    /// let (metadata, audio) = ForeignMetadata::split(&wav).unwrap();
+    /// assert_eq!(metadata.format, ForeignFormat::Riff);
+    /// assert_eq!(metadata.chunk(b"iXML"), Some(&b"<BWFXML>"[..]));
+    /// assert_eq!(audio, &[1, 2]);
+    /// assert_eq!(metadata.rebuild(audio).unwrap(), wav);
+    /// ```
+    pub fn split(bytes: &[u8]) -> Result<(ForeignMetadata, &[u8])> {
+        let invalid = |description| Error::new(ErrorKind::InvalidInput, description);
+
+        let format = match ForeignFormat::detect(bytes) {
+            Some(format) => format,
+            None => return Err(invalid("input is not a WAV, AIFF or Wave64 file")),
+        };
+
+        let mut metadata = ForeignMetadata {
+            format,
+            blocks: vec![bytes[..format.header_length()].to_vec()],
+        };
+        let mut audio = None;
+
+        let header_length = format.chunk_header_length();
+        let mut i = format.header_length();
+        while i + header_length <= bytes.len() {
+            let size = format.read_size(&bytes[i..]);
+            let start = i + header_length;
+            let end = match usize::try_from(size)
+                .ok()
+                .and_then(|size| start.checked_add(size))
+            {
+                Some(end) if end <= bytes.len() => end,
+                _ => return Err(invalid("chunk extends past the end of the file")),
+            };
+            let padded_end = (end + format.padding(size)).min(bytes.len());
+
+            if &bytes[i..i + 4] == format.data_chunk_id() {
+                if audio.is_some() {
+                    return Err(invalid("file contains more than one audio data chunk"));
+                }
+                let data_start = match format {
+                    ForeignFormat::Riff | ForeignFormat::Wave64 => start,
+                    ForeignFormat::Aiff => {
+                        if size < 8 {
+                            return Err(invalid("SSND chunk is too short"));
+                        }
+                        let offset =
+                            u32::from_be_bytes(bytes[start..start + 4].try_into().unwrap());
+                        if start + 8 + offset as usize > end {
+                            return Err(invalid("SSND offset extends past the end of the chunk"));
+                        }
+                        start + 8 + offset as usize
+                    }
+                };
+                metadata.blocks.push(bytes[i..data_start].to_vec());
+                audio = Some(&bytes[data_start..end]);
+            } else {
+                metadata.blocks.push(bytes[i..padded_end].to_vec());
+            }
+
+            i = padded_end;
+        }
+
+        match audio {
+            Some(audio) => Ok((metadata, audio)),
+            None => Err(invalid("file does not contain an audio data chunk")),
+        }
+    }
+
+    /// Returns the foreign metadata stored in the application blocks of the tag, if any. If the
+    /// tag contains blocks of more than one format, the format of the first block is used.
+    /// `None` is returned if the first block does not hold a valid file header.
+    pub fn from_tag(tag: &Tag) -> Option<ForeignMetadata> {
+        let mut format = None;
+        let mut blocks = Vec::new();
+        for block in tag.get_blocks(BlockType::Application) {
+            if let Block::Application(ref application) = *block {
+                let block_format = ForeignFormat::from_application_id(&application.id);
+                if block_format.is_some() && (format.is_none() || format == block_format) {
+                    format = block_format;
+                    blocks.push(application.data.clone());
+                }
+            }
+        }
+
+        format
+            .filter(|&format| ForeignFormat::detect(&blocks[0]) == Some(format))
+            .map(|format| ForeignMetadata { format, blocks })
+    }
+
+    /// Adds the foreign metadata to the tag as application blocks.
+    pub fn push_to_tag(&self, tag: &mut Tag) {
+        for data in self.blocks.iter() {
+            let mut application = Application::new();
+            application.id = *self.format.application_id();
+            application.data = data.clone();
+            tag.push_block(Block::Application(application));
+        }
+    }
+
+    /// Returns the index of the block holding the header of the audio data chunk.
+    fn data_block(&self) -> Result<usize> {
+        let header_length = self.format.chunk_header_length();
+        let id = self.format.data_chunk_id();
+        self.blocks
+            .iter()
+            .skip(1)
+            .position(|block| block.len() >= header_length && &block[..4] == id)
+            .map(|index| index + 1)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    "foreign metadata does not contain an audio data chunk",
+                )
+            })
+    }
+
+    /// Returns the length of the audio data declared by the audio data chunk header.
+    pub fn data_size(&self) -> Result<u64> {
+        let block = &self.blocks[self.data_block()?];
+        let size = self.format.read_size(block);
+        // the SSND chunk also holds the offset and block size fields and the offset padding
+        let extra = (block.len() - self.format.chunk_header_length()) as u64;
+        Ok(size.saturating_sub(extra))
+    }
+
+    /// Returns the bytes of the original file which precede the audio data.
+    pub fn header(&self) -> Result<Vec<u8>> {
+        Ok(self.blocks[..=self.data_block()?].concat())
+    }
+
+    /// Returns the bytes of the original file which follow the audio data, including the padding
+    /// of the audio data chunk.
+    pub fn trailer(&self) -> Result<Vec<u8>> {
+        let index = self.data_block()?;
+        let size = self.format.read_size(&self.blocks[index]);
+        let mut trailer = vec![0; self.format.padding(size)];
+        trailer.extend(self.blocks[index + 1..].concat());
+        Ok(trailer)
+    }
+
+    /// Rebuilds the original file from the foreign metadata and the bytes of the audio data. An
+    /// `Error::InvalidInput` is returned if the length of the audio data does not match the
+    /// audio data chunk.
+    pub fn rebuild(&self, audio: &[u8]) -> Result<Vec<u8>> {
+        if self.data_size()? != audio.len() as u64 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "foreign metadata does not match the length of the audio",
+            ));
+        }
+
+        let mut bytes = self.header()?;
+        bytes.extend(audio);
+        bytes.extend(self.trailer()?);
+        Ok(bytes)
+    }
+
+    /// Returns the ids and data of the chunks other than the audio data chunk, in the order they
+    /// appear in the file. For Wave64 files the id is the first four bytes of the chunk GUID.
+    pub fn chunks(&self) -> impl Iterator<Item = ([u8; 4], &[u8])> + '_ {
+        let format = self.format;
+        let header_length = format.chunk_header_length();
+        self.blocks
+            .iter()
+            .skip(1)
+            .filter(move |block| {
+                block.len() >= header_length && &block[..4] != format.data_chunk_id()
+            })
+            .map(move |block| {
+                let size = usize::try_from(format.read_size(block)).unwrap_or(usize::MAX);
+                let end = header_length.saturating_add(size).min(block.len());
+                (
+                    [block[0], block[1], block[2], block[3]],
+                    &block[header_length..end],
+                )
+            })
+    }
+
+    /// Returns the data of the first chunk with the specified id.
+    pub fn chunk(&self, id: &[u8; 4]) -> Option<&[u8]> {
+        self.chunks()
+            .find(|(chunk_id, _)| chunk_id == id)
+            .map(|(_, data)| data)
+    }
+
+    /// Returns the typed contents of the chunks other than the audio data chunk.
+    pub fn parsed_chunks(&self) -> Vec<ForeignChunk> {
+        self.chunks()
+            .map(|(id, data)| ForeignChunk::from_bytes(id, data))
+            .collect()
+    }
+
+    /// Replaces the data of the first chunk with the specified id, or inserts a new chunk before
+    /// the audio data chunk if there is no such chunk. The size in the file header is updated so
+    /// that the rebuilt file stays valid. An `Error::InvalidInput` is returned if the id is the
+    /// id of the audio data chunk, if the first block does not hold a file header or if the sizes
+    /// of a WAV or AIFF file would no longer fit in 32 bits.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::foreign::{BroadcastExtension, ForeignChunk, ForeignMetadata};
+    ///
+    /// let wav = b"RIFF\x0c\0\0\0WAVEdata\0\0\0\0".to_vec();
+    /// let (mut metadata, audio) = ForeignMetadata::split(&wav).unwrap();
+    ///
+    /// let mut bext = BroadcastExtension::new();
+    /// bext.description = "Interview".to_owned();
+    /// bext.time_reference = 48000 * 3600;
+    /// metadata.set_chunk(b"bext", &bext.to_bytes()).unwrap();
+    ///
+    /// let rebuilt = metadata.rebuild(audio).unwrap();
+    /// let (metadata, _) = ForeignMetadata::split(&rebuilt).unwrap();
+    /// assert_eq!(metadata.parsed_chunks(), vec![ForeignChunk::Broadcast(bext)]);
+    /// ```
+    pub fn set_chunk(&mut self, id: &[u8; 4], data: &[u8]) -> Result<()> {
+        let format = self.format;
+        if id == format.data_chunk_id() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the audio data chunk can not be replaced",
+            ));
+        }
+
+        if self
+            .blocks
+            .first()
+            .map_or(true, |header| header.len() < format.header_length())
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "foreign metadata does not start with a file header",
+            ));
+        }
+
+        let header_length = format.chunk_header_length();
+        let existing = self
+            .blocks
+            .iter()
+            .skip(1)
+            .position(|block| block.len() >= header_length && &block[..4] == id)
+            .map(|index| index + 1);
+
+        let mut block = match existing {
+            Some(index) => self.blocks[index][..header_length].to_vec(),
+            None => {
+                let mut header = id.to_vec();
+                if format == ForeignFormat::Wave64 {
+                    header.extend(&W64_CHUNK_SUFFIX);
+                }
+                header.resize(header_length, 0);
+                header
+            }
+        };
+        format.write_size(&mut block, data.len() as u64);
+        block.extend(data);
+        block.resize(block.len() + format.padding(data.len() as u64), 0);
+
+        let old_length = existing.map_or(0, |index| self.blocks[index].len() as u64);
+        let size =
+            (format.read_size(&self.blocks[0]) + block.len() as u64).saturating_sub(old_length);
+        if format != ForeignFormat::Wave64 && size > u32::MAX as u64 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "chunk does not fit in a WAV or AIFF file",
+            ));
+        }
+
+        match existing {
+            Some(index) => self.blocks[index] = block,
+            None => {
+                let index = self.data_block()?;
+                self.blocks.insert(index, block);
+            }
+        }
+        format.write_size(&mut self.blocks[0], size);
+        Ok(())
+    }
+}
+//}}}
+
+// ForeignChunk {{{
+/// The typed contents of a chunk of a foreign file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ForeignChunk {
+    /// A `fmt ` chunk.
+    Format(WaveFormat),
+    /// A `LIST` chunk of type `INFO`.
+    Info(Vec<InfoEntry>),
+    /// A Broadcast Wave Format `bext` chunk.
+    Broadcast(BroadcastExtension),
+    /// An `iXML` chunk.
+    Ixml(String),
+    /// A `cue ` chunk.
+    Cue(Vec<CuePoint>),
+    /// A `smpl` chunk.
+    Sampler(SamplerInfo),
+    /// A chunk which is not parsed, or which could not be parsed.
+    Unknown {
+        /// The chunk id.
+        id: [u8; 4],
+        /// The chunk data.
+        data: Vec<u8>,
+    },
+}
+
+impl ForeignChunk {
+    /// Parses the chunk with the specified id. Chunk ids are matched case-insensitively, since
+    /// Wave64 uses lower case ids. Chunks which are too short are returned as
+    /// `ForeignChunk::Unknown`.
+    pub fn from_bytes(id: [u8; 4], data: &[u8]) -> ForeignChunk {
+        let parsed = match &id.to_ascii_lowercase()[..] {
+            b"fmt " => WaveFormat::from_bytes(data).map(ForeignChunk::Format),
+            b"list" if data.len() >= 4 && &data[..4] == b"INFO" => {
+                Some(ForeignChunk::Info(InfoEntry::from_list(&data[4..])))
+            }
+            b"bext" => BroadcastExtension::from_bytes(data).map(ForeignChunk::Broadcast),
+            b"ixml" => Some(ForeignChunk::Ixml(read_string(data))),
+            b"cue " => CuePoint::from_cue(data).map(ForeignChunk::Cue),
+            b"smpl" => SamplerInfo::from_bytes(data).map(ForeignChunk::Sampler),
+            _ => None,
+        };
+
+        parsed.unwrap_or_else(|| ForeignChunk::Unknown {
+            id,
+            data: data.to_vec(),
+        })
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Reads a string which may be terminated or padded with NUL bytes. Strings which are not valid
+/// UTF-8 are read as ISO-8859-1.
+fn read_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    match String::from_utf8(data[..end].to_vec()) {
+        Ok(string) => string,
+        Err(_) => data[..end].iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// Writes a string to a field of the specified length, padding it with NUL bytes. A string which
+/// is too long is truncated at the last character which fits.
+fn write_string(bytes: &mut Vec<u8>, string: &str, length: usize) {
+    let mut end = string.len().min(length);
+    while !string.is_char_boundary(end) {
+        end -= 1;
+    }
+    let data = &string.as_bytes()[..end];
+    bytes.extend(data);
+    bytes.resize(bytes.len() + length - data.len(), 0);
+}
+//}}}
+
+// WaveFormat {{{
+/// The contents of a `fmt ` chunk.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WaveFormat {
+    /// The format tag, 1 for PCM and 0xFFFE for `WAVE_FORMAT_EXTENSIBLE`.
+    pub format_tag: u16,
+    /// The number of channels.
+    pub num_channels: u16,
+    /// The sample rate in Hz.
+    pub sample_rate: u32,
+    /// The average number of bytes per second.
+    pub avg_bytes_per_sec: u32,
+    /// The number of bytes in a sample of all channels.
+    pub block_align: u16,
+    /// The number of bits in the container of a sample.
+    pub bits_per_sample: u16,
+    /// The number of significant bits of a sample, for `WAVE_FORMAT_EXTENSIBLE`.
+    pub valid_bits_per_sample: Option<u16>,
+    /// The speaker positions of the channels, for `WAVE_FORMAT_EXTENSIBLE`.
+    pub channel_mask: Option<u32>,
+    /// The GUID of the sub-format, for `WAVE_FORMAT_EXTENSIBLE`.
+    pub sub_format: Option<[u8; 16]>,
+}
+
+impl WaveFormat {
+    /// Parses the contents of a `fmt ` chunk. Returns `None` if the chunk is too short.
+    pub fn from_bytes(data: &[u8]) -> Option<WaveFormat> {
+        if data.len() < 16 {
+            return None;
+        }
+
+        let mut format = WaveFormat {
+            format_tag: read_u16(data, 0),
+            num_channels: read_u16(data, 2),
+            sample_rate: read_u32(data, 4),
+            avg_bytes_per_sec: read_u32(data, 8),
+            block_align: read_u16(data, 12),
+            bits_per_sample: read_u16(data, 14),
+            valid_bits_per_sample: None,
+            channel_mask: None,
+            sub_format: None,
+        };
+
+        if format.format_tag == 0xFFFE && data.len() >= 40 && read_u16(data, 16) >= 22 {
+            format.valid_bits_per_sample = Some(read_u16(data, 18));
+            format.channel_mask = Some(read_u32(data, 20));
+            format.sub_format = Some(data[24..40].try_into().unwrap());
+        }
+
+        Some(format)
+    }
+}
+//}}}
+
+// InfoEntry {{{
+/// An entry of a `LIST` chunk of type `INFO`, such as `INAM` (the title) or `IART` (the artist).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InfoEntry {
+    /// The id of the entry.
+    pub id: [u8; 4],
+    /// The value of the entry.
+    pub value: String,
+}
+
+impl InfoEntry {
+    fn from_list(data: &[u8]) -> Vec<InfoEntry> {
+        let mut entries = Vec::new();
+        let mut i = 0;
+        while i + 8 <= data.len() {
+            let size = read_u32(data, i + 4) as usize;
+            let end = (i + 8 + size).min(data.len());
+            entries.push(InfoEntry {
+                id: data[i..i + 4].try_into().unwrap(),
+                value: read_string(&data[i + 8..end]),
+            });
+            i = end + size % 2;
+        }
+        entries
+    }
+}
+//}}}
+
+// BroadcastExtension {{{
+/// The contents of a Broadcast Wave Format `bext` chunk (EBU Tech 3285).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BroadcastExtension {
+    /// A description of the sound sequence, at most 256 bytes.
+    pub description: String,
+    /// The name of the originator, at most 32 bytes.
+    pub originator: String,
+    /// The reference of the originator, at most 32 bytes.
+    pub originator_reference: String,
+    /// The date of creation, formatted as `yyyy-mm-dd`.
+    pub origination_date: String,
+    /// The time of creation, formatted as `hh:mm:ss`.
+    pub origination_time: String,
+    /// The number of samples since midnight at the start of the sequence.
+    pub time_reference: u64,
+    /// The version of the chunk.
+    pub version: u16,
+    /// The SMPTE UMID of the sequence, 64 bytes.
+    pub umid: Vec<u8>,
+    /// The integrated loudness in 0.01 LUFS, from version 2.
+    pub loudness_value: Option<i16>,
+    /// The loudness range in 0.01 LU, from version 2.
+    pub loudness_range: Option<i16>,
+    /// The maximum true peak level in 0.01 dBTP, from version 2.
+    pub max_true_peak_level: Option<i16>,
+    /// The maximum momentary loudness in 0.01 LUFS, from version 2.
+    pub max_momentary_loudness: Option<i16>,
+    /// The maximum short-term loudness in 0.01 LUFS, from version 2.
+    pub max_short_term_loudness: Option<i16>,
+    /// The coding history.
+    pub coding_history: String,
+}
+
+impl BroadcastExtension {
+    /// Returns a new version 1 `BroadcastExtension` with empty fields.
+    pub fn new() -> BroadcastExtension {
+        BroadcastExtension {
+            description: String::new(),
+            originator: String::new(),
+            originator_reference: String::new(),
+            origination_date: String::new(),
+            origination_time: String::new(),
+            time_reference: 0,
+            version: 1,
+            umid: vec![0; 64],
+            loudness_value: None,
+            loudness_range: None,
+            max_true_peak_level: None,
+            max_momentary_loudness: None,
+            max_short_term_loudness: None,
+            coding_history: String::new(),
+        }
+    }
+
+    /// Parses the contents of a `bext` chunk. Returns `None` if the chunk is too short.
+    pub fn from_bytes(data: &[u8]) -> Option<BroadcastExtension> {
+        if data.len() < 602 {
+            return None;
+        }
+
+        let version = read_u16(data, 346);
+        let loudness = |offset: usize| {
+            if version >= 2 {
+                Some(read_u16(data, offset) as i16)
+            } else {
+                None
+            }
+        };
+
+        Some(BroadcastExtension {
+            description: read_string(&data[0..256]),
+            originator: read_string(&data[256..288]),
+            originator_reference: read_string(&data[288..320]),
+            origination_date: read_string(&data[320..330]),
+            origination_time: read_string(&data[330..338]),
+            time_reference: read_u32(data, 338) as u64 | (read_u32(data, 342) as u64) << 32,
+            version,
+            umid: data[348..412].to_vec(),
+            loudness_value: loudness(412),
+            loudness_range: loudness(414),
+            max_true_peak_level: loudness(416),
+            max_momentary_loudness: loudness(418),
+            max_short_term_loudness: loudness(420),
+            coding_history: read_string(&data[602..]),
+        })
+    }
+
+    /// Returns the contents of a `bext` chunk. Strings which are longer than their field are
+    /// truncated.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(602 + self.coding_history.len());
+        write_string(&mut bytes, &self.description, 256);
+        write_string(&mut bytes, &self.originator, 32);
+        write_string(&mut bytes, &self.originator_reference, 32);
+        write_string(&mut bytes, &self.origination_date, 10);
+        write_string(&mut bytes, &self.origination_time, 8);
+        bytes.extend(&self.time_reference.to_le_bytes());
+        bytes.extend(&self.version.to_le_bytes());
+
+        let mut umid = self.umid.clone();
+        umid.resize(64, 0);
+        bytes.extend(umid);
+
+        for value in [
+            self.loudness_value,
+            self.loudness_range,
+            self.max_true_peak_level,
+            self.max_momentary_loudness,
+            self.max_short_term_loudness,
+        ]
+        .iter()
+        {
+            bytes.extend(&value.unwrap_or(0).to_le_bytes());
+        }
+
+        bytes.resize(602, 0);
+        bytes.extend(self.coding_history.as_bytes());
+        bytes
+    }
+}
+
+impl Default for BroadcastExtension {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+//}}}
+
+// CuePoint {{{
+/// A cue point of a `cue ` chunk.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CuePoint {
+    /// The unique id of the cue point.
+    pub id: u32,
+    /// The position of the cue point in samples.
+    pub position: u32,
+    /// The id of the chunk holding the cue point, usually `data`.
+    pub chunk_id: [u8; 4],
+    /// The offset of the chunk holding the cue point.
+    pub chunk_start: u32,
+    /// The offset of the block holding the cue point.
+    pub block_start: u32,
+    /// The offset of the cue point in samples from the start of the block.
+    pub sample_offset: u32,
+}
+
+impl CuePoint {
+    fn from_cue(data: &[u8]) -> Option<Vec<CuePoint>> {
+        if data.len() < 4 {
+            return None;
+        }
+
+        let num_points = read_u32(data, 0) as usize;
+        if num_points > (data.len() - 4) / 24 {
+            return None;
+        }
+
+        Some(
+            data[4..4 + num_points * 24]
+                .chunks(24)
+                .map(|point| CuePoint {
+                    id: read_u32(point, 0),
+                    position: read_u32(point, 4),
+                    chunk_id: point[8..12].try_into().unwrap(),
+                    chunk_start: read_u32(point, 12),
+                    block_start: read_u32(point, 16),
+                    sample_offset: read_u32(point, 20),
+                })
+                .collect(),
+        )
+    }
+}
+//}}}
+
+// SamplerInfo {{{
+/// The contents of a `smpl` chunk.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SamplerInfo {
+    /// The MIDI manufacturer code.
+    pub manufacturer: u32,
+    /// The product code of the manufacturer.
+    pub product: u32,
+    /// The duration of a sample in nanoseconds.
+    pub sample_period: u32,
+    /// The MIDI note which plays the sample at its original pitch.
+    pub midi_unity_note: u32,
+    /// The fraction of a semitone above the unity note.
+    pub midi_pitch_fraction: u32,
+    /// The SMPTE frame rate.
+    pub smpte_format: u32,
+    /// The SMPTE time offset.
+    pub smpte_offset: u32,
+    /// The sample loops.
+    pub loops: Vec<SampleLoop>,
+    /// The sampler specific data.
+    pub sampler_data: Vec<u8>,
+}
+
+/// A loop of a `smpl` chunk.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SampleLoop {
+    /// The unique id of the loop.
+    pub id: u32,
+    /// The type of the loop, 0 for forward, 1 for alternating and 2 for backward loops.
+    pub loop_type: u32,
+    /// The first sample of the loop.
+    pub start: u32,
+    /// The last sample of the loop.
+    pub end: u32,
+    /// The fraction of a sample at which to loop.
+    pub fraction: u32,
+    /// The number of times to play the loop, or 0 to loop forever.
+    pub play_count: u32,
+}
+
+impl SamplerInfo {
+    /// Parses the contents of a `smpl` chunk. Returns `None` if the chunk is too short.
+    pub fn from_bytes(data: &[u8]) -> Option<SamplerInfo> {
+        if data.len() < 36 {
+            return None;
+        }
+
+        let num_loops = read_u32(data, 28) as usize;
+        let sampler_data_length = read_u32(data, 32) as usize;
+        if num_loops > (data.len() - 36) / 24 {
+            return None;
+        }
+        let loops_end = 36 + num_loops * 24;
+        let sampler_data_end = loops_end + sampler_data_length.min(data.len() - loops_end);
+
+        Some(SamplerInfo {
+            manufacturer: read_u32(data, 0),
+            product: read_u32(data, 4),
+            sample_period: read_u32(data, 8),
+            midi_unity_note: read_u32(data, 12),
+            midi_pitch_fraction: read_u32(data, 16),
+            smpte_format: read_u32(data, 20),
+            smpte_offset: read_u32(data, 24),
+            loops: data[36..loops_end]
+                .chunks(24)
+                .map(|sample_loop| SampleLoop {
+                    id: read_u32(sample_loop, 0),
+                    loop_type: read_u32(sample_loop, 4),
+                    start: read_u32(sample_loop, 8),
+                    end: read_u32(sample_loop, 12),
+                    fraction: read_u32(sample_loop, 16),
+                    play_count: read_u32(sample_loop, 20),
+                })
+                .collect(),
+            sampler_data: data[loops_end..sampler_data_end].to_vec(),
+        })
+    }
+}
+//}}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn riff_chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend(&(data.len() as u32).to_le_bytes());
+        chunk.extend(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn w64_chunk(id: &[u8; 4], suffix: &[u8; 12], data: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend(suffix);
+        chunk.extend(&(data.len() as u64 + 24).to_le_bytes());
+        chunk.extend(data);
+        chunk.resize(chunk.len() + (8 - chunk.len() % 8) % 8, 0);
+        chunk
+    }
+
+    fn broadcast_wav(audio: &[u8]) -> (Vec<u8>, BroadcastExtension) {
+        let mut bext = BroadcastExtension::new();
+        bext.description = "Interview with the archivist".to_owned();
+        bext.originator = "Field recorder".to_owned();
+        bext.origination_date = "2024-05-17".to_owned();
+        bext.origination_time = "10:30:00".to_owned();
+        bext.time_reference = 0x1_0000_0010;
+        bext.version = 2;
+        bext.loudness_value = Some(-2300);
+        bext.loudness_range = Some(0);
+        bext.max_true_peak_level = Some(0);
+        bext.max_momentary_loudness = Some(0);
+        bext.max_short_term_loudness = Some(0);
+        bext.coding_history = "A=PCM,F=48000,W=24,M=mono\r\n".to_owned();
+
+        let mut fmt = Vec::new();
+        fmt.extend(&1u16.to_le_bytes());
+        fmt.extend(&1u16.to_le_bytes());
+        fmt.extend(&48000u32.to_le_bytes());
+        fmt.extend(&(48000u32 * 3).to_le_bytes());
+        fmt.extend(&3u16.to_le_bytes());
+        fmt.extend(&24u16.to_le_bytes());
+
+        let mut info = b"INFO".to_vec();
+        info.extend(riff_chunk(b"INAM", b"Tape 12\0"));
+        info.extend(riff_chunk(b"ICMT", b"Side A"));
+
+        let mut cue = 2u32.to_le_bytes().to_vec();
+        for &(id, position) in [(1u32, 0u32), (2, 480)].iter() {
+            cue.extend(&id.to_le_bytes());
+            cue.extend(&position.to_le_bytes());
+            cue.extend(b"data");
+            cue.extend(&[0; 8]);
+            cue.extend(&position.to_le_bytes());
+        }
+
+        let mut smpl = vec![0; 28];
+        smpl[12] = 60;
+        smpl.extend(&1u32.to_le_bytes());
+        smpl.extend(&0u32.to_le_bytes());
+        smpl.extend(&[0; 8]);
+        smpl.extend(&10u32.to_le_bytes());
+        smpl.extend(&400u32.to_le_bytes());
+        smpl.extend(&[0; 8]);
+
+        let mut body = b"WAVE".to_vec();
+        body.extend(riff_chunk(b"bext", &bext.to_bytes()));
+        body.extend(riff_chunk(b"fmt ", &fmt));
+        body.extend(riff_chunk(b"iXML", b"<BWFXML/>"));
+        body.extend(riff_chunk(b"data", audio));
+        body.extend(riff_chunk(b"LIST", &info));
+        body.extend(riff_chunk(b"cue ", &cue));
+        body.extend(riff_chunk(b"smpl", &smpl));
+        (riff_chunk(b"RIFF", &body), bext)
+    }
+
+    #[test]
+    fn parse_riff_chunks() {
+        let audio = vec![7; 33];
+        let (wav, bext) = broadcast_wav(&audio);
+
+        let (metadata, split_audio) = ForeignMetadata::split(&wav).unwrap();
+        assert_eq!(split_audio, &audio[..]);
+        assert_eq!(metadata.data_size().unwrap(), 33);
+        assert_eq!(metadata.rebuild(&audio).unwrap(), wav);
+        assert!(metadata.rebuild(&audio[1..]).is_err());
+
+        let chunks = metadata.parsed_chunks();
+        assert_eq!(chunks.len(), 6);
+        assert_eq!(chunks[0], ForeignChunk::Broadcast(bext));
+        match chunks[1] {
+            ForeignChunk::Format(ref format) => {
+                assert_eq!(format.sample_rate, 48000);
+                assert_eq!(format.bits_per_sample, 24);
+                assert_eq!(format.valid_bits_per_sample, None);
+            }
+            ref chunk => panic!("unexpected chunk {:?}", chunk),
+        }
+        assert_eq!(chunks[2], ForeignChunk::Ixml("<BWFXML/>".to_owned()));
+        assert_eq!(
+            chunks[3],
+            ForeignChunk::Info(vec![
+                InfoEntry {
+                    id: *b"INAM",
+                    value: "Tape 12".to_owned()
+                },
+                InfoEntry {
+                    id: *b"ICMT",
+                    value: "Side A".to_owned()
+                },
+            ])
+        );
+        match chunks[4] {
+            ForeignChunk::Cue(ref points) => {
+                assert_eq!(points.len(), 2);
+                assert_eq!(points[1].position, 480);
+                assert_eq!(&points[1].chunk_id, b"data");
+            }
+            ref chunk => panic!("unexpected chunk {:?}", chunk),
+        }
+        match chunks[5] {
+            ForeignChunk::Sampler(ref sampler) => {
+                assert_eq!(sampler.midi_unity_note, 60);
+                assert_eq!(sampler.loops[0].start, 10);
+                assert_eq!(sampler.loops[0].end, 400);
+            }
+            ref chunk => panic!("unexpected chunk {:?}", chunk),
+        }
+    }
+
+    #[test]
+    fn tag_round_trip() {
+        let audio = vec![1; 30];
+        let (wav, _) = broadcast_wav(&audio);
+        let (metadata, _) = ForeignMetadata::split(&wav).unwrap();
+
+        let mut tag = Tag::new();
+        assert_eq!(ForeignMetadata::from_tag(&tag), None);
+        metadata.push_to_tag(&mut tag);
+        assert_eq!(tag.get_blocks(BlockType::Application).count(), 8);
+
+        let mut bytes = Vec::new();
+        tag.write_to(&mut bytes).unwrap();
+        let tag = Tag::read_from(&mut &bytes[..]).unwrap();
+        let read = ForeignMetadata::from_tag(&tag).unwrap();
+        assert_eq!(read, metadata);
+        assert_eq!(read.rebuild(&audio).unwrap(), wav);
+    }
+
+    #[test]
+    fn truncated_strings() {
+        let mut bext = BroadcastExtension::new();
+        bext.description = format!("{}\u{E9}", "a".repeat(255));
+        bext.originator = "\u{C4}".repeat(40);
+
+        let bext = BroadcastExtension::from_bytes(&bext.to_bytes()).unwrap();
+        assert_eq!(bext.description, "a".repeat(255));
+        assert_eq!(bext.originator, "\u{C4}".repeat(16));
+    }
+
+    #[test]
+    fn replace_chunks() {
+        let audio = vec![0; 12];
+        let (wav, mut bext) = broadcast_wav(&audio);
+        let (mut metadata, _) = ForeignMetadata::split(&wav).unwrap();
+
+        bext.description = "Shorter".to_owned();
+        bext.coding_history = "A=PCM\r\n".to_owned();
+        metadata.set_chunk(b"bext", &bext.to_bytes()).unwrap();
+        metadata.set_chunk(b"ID3 ", b"ID3").unwrap();
+        assert!(metadata.set_chunk(b"data", &[]).is_err());
+
+        let rebuilt = metadata.rebuild(&audio).unwrap();
+        assert_eq!(read_u32(&rebuilt, 4) as usize, rebuilt.len() - 8);
+        let (metadata, _) = ForeignMetadata::split(&rebuilt).unwrap();
+        assert_eq!(metadata.chunk(b"ID3 "), Some(&b"ID3"[..]));
+        assert_eq!(metadata.parsed_chunks()[0], ForeignChunk::Broadcast(bext));
+    }
+
+    #[test]
+    fn malformed_metadata() {
+        // a chunk size which overflows the end offset
+        let mut w64 = b"riff".to_vec();
+        w64.extend(&W64_RIFF_SUFFIX);
+        w64.extend(&64u64.to_le_bytes());
+        w64.extend(b"wave");
+        w64.extend(&W64_CHUNK_SUFFIX);
+        w64.extend(b"data");
+        w64.extend(&W64_CHUNK_SUFFIX);
+        w64.extend(&u64::MAX.to_le_bytes());
+        assert!(ForeignMetadata::split(&w64).is_err());
+
+        // an application block too short to hold the file header
+        let mut tag = Tag::new();
+        let mut application = Application::new();
+        application.id = *b"riff";
+        application.data = b"RIFF".to_vec();
+        tag.push_block(Block::Application(application));
+        assert_eq!(ForeignMetadata::from_tag(&tag), None);
+
+        let mut metadata = ForeignMetadata {
+            format: ForeignFormat::Riff,
+            blocks: vec![b"RIFF".to_vec(), b"data\0\0\0\0".to_vec()],
+        };
+        assert!(metadata.set_chunk(b"LIST", b"INFO").is_err());
+
+        // the file size no longer fits in 32 bits
+        metadata.blocks[0] = b"RIFF\xfc\xff\xff\xffWAVE".to_vec();
+        assert!(metadata.set_chunk(b"LIST", b"INFO").is_err());
+        assert_eq!(metadata.blocks.len(), 2);
+    }
+
+    #[test]
+    fn wave64_chunks() {
+        let mut fmt = vec![0; 16];
+        fmt[0] = 1;
+        fmt[2] = 2;
+        fmt[4..8].copy_from_slice(&44100u32.to_le_bytes());
+
+        let mut body = b"wave".to_vec();
+        body.extend(&W64_CHUNK_SUFFIX);
+        body.extend(w64_chunk(b"fmt ", &W64_CHUNK_SUFFIX, &fmt));
+        body.extend(w64_chunk(b"data", &W64_CHUNK_SUFFIX, &[1, 2, 3, 4, 5]));
+        let mut list = b"INFO".to_vec();
+        list.extend(riff_chunk(b"IART", b"Nobody"));
+        body.extend(w64_chunk(b"list", &W64_RIFF_SUFFIX, &list));
+
+        let mut w64 = b"riff".to_vec();
+        w64.extend(&W64_RIFF_SUFFIX);
+        w64.extend(&(body.len() as u64 + 24).to_le_bytes());
+        w64.extend(body);
+
+        let (mut metadata, audio) = ForeignMetadata::split(&w64).unwrap();
+        assert_eq!(metadata.format, ForeignFormat::Wave64);
+        assert_eq!(audio, &[1, 2, 3, 4, 5]);
+        assert_eq!(metadata.rebuild(audio).unwrap(), w64);
+
+        let chunks = metadata.parsed_chunks();
+        match chunks[0] {
+            ForeignChunk::Format(ref format) => assert_eq!(format.sample_rate, 44100),
+            ref chunk => panic!("unexpected chunk {:?}", chunk),
+        }
+        assert_eq!(
+            chunks[1],
+            ForeignChunk::Info(vec![InfoEntry {
+                id: *b"IART",
+                value: "Nobody".to_owned()
+            }])
+        );
+
+        metadata.set_chunk(b"bext", &[0; 10]).unwrap();
+        let rebuilt = metadata.rebuild(audio).unwrap();
+        assert_eq!(
+            u64::from_le_bytes(rebuilt[16..24].try_into().unwrap()),
+            rebuilt.len() as u64
+        );
+        assert_eq!(rebuilt.len(), w64.len() + 40);
+    }
+}
@@ -0,0 +1,635 @@
+use crate::crc::{crc16, crc16_update, crc8};
+use crate::error::{Error, ErrorKind, Result};
+
+// FrameHeader {{{
+/// The blocking strategy of a frame, which determines how the frame's number is interpreted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlockingStrategy {
+    /// Every frame holds the same number of samples and is numbered by frame.
+    Fixed,
+    /// Frames may hold different numbers of samples and are numbered by their first sample.
+    Variable,
+}
+
+/// The way in which the channels of a frame are coded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChannelAssignment {
+    /// Each of the channels is coded independently. Contains the number of channels.
+    Independent(u8),
+    /// Two channels coded as the left channel and the side (difference) channel.
+    LeftSide,
+    /// Two channels coded as the side (difference) channel and the right channel.
+    RightSide,
+    /// Two channels coded as the mid (average) channel and the side (difference) channel.
+    MidSide,
+}
+
+impl ChannelAssignment {
+    /// Returns the number of channels in the frame.
+    pub fn num_channels(self) -> u8 {
+        match self {
+            ChannelAssignment::Independent(n) => n,
+            _ => 2,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            ChannelAssignment::Independent(n) => n - 1,
+            ChannelAssignment::LeftSide => 8,
+            ChannelAssignment::RightSide => 9,
+            ChannelAssignment::MidSide => 10,
+        }
+    }
+
+    fn from_u8(n: u8) -> Option<ChannelAssignment> {
+        match n {
+            0..=7 => Some(ChannelAssignment::Independent(n + 1)),
+            8 => Some(ChannelAssignment::LeftSide),
+            9 => Some(ChannelAssignment::RightSide),
+            10 => Some(ChannelAssignment::MidSide),
+            _ => None,
+        }
+    }
+}
+
+/// A structure representing the header of an audio frame.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FrameHeader {
+    /// The blocking strategy of the stream.
+    pub blocking_strategy: BlockingStrategy,
+    /// The number of samples (per channel) in the frame.
+    pub block_size: u32,
+    /// The sample rate in Hz, or `None` if it should be taken from the streaminfo block.
+    pub sample_rate: Option<u32>,
+    /// The channel assignment of the frame.
+    pub channel_assignment: ChannelAssignment,
+    /// The bits per sample, or `None` if it should be taken from the streaminfo block.
+    pub bits_per_sample: Option<u8>,
+    /// The frame number for a fixed blocking strategy or the number of the first sample in the
+    /// frame for a variable blocking strategy.
+    pub number: u64,
+}
+
+const SAMPLE_RATES: [(u8, u32); 11] = [
+    (1, 88200),
+    (2, 176400),
+    (3, 192000),
+    (4, 8000),
+    (5, 16000),
+    (6, 22050),
+    (7, 24000),
+    (8, 32000),
+    (9, 44100),
+    (10, 48000),
+    (11, 96000),
+];
+
+const BITS_PER_SAMPLE: [(u8, u8); 6] = [(1, 8), (2, 12), (4, 16), (5, 20), (6, 24), (7, 32)];
+
+impl FrameHeader {
+    /// Returns a new `FrameHeader` for a fixed blocking strategy with a single channel and all
+    /// other values zero/empty.
+    pub fn new() -> FrameHeader {
+        FrameHeader {
+            blocking_strategy: BlockingStrategy::Fixed,
+            block_size: 0,
+            sample_rate: None,
+            channel_assignment: ChannelAssignment::Independent(1),
+            bits_per_sample: None,
+            number: 0,
+        }
+    }
+
+    /// Returns the number of channels in the frame.
+    pub fn num_channels(&self) -> u8 {
+        self.channel_assignment.num_channels()
+    }
+
+    /// Returns the number of the first sample in the frame. The block size of a stream using a
+    /// fixed blocking strategy is required to convert the frame number into a sample number.
+    pub fn first_sample(&self, fixed_block_size: u32) -> u64 {
+        match self.blocking_strategy {
+            BlockingStrategy::Fixed => self.number * fixed_block_size as u64,
+            BlockingStrategy::Variable => self.number,
+        }
+    }
+
+    /// Attempts to parse the bytes as a frame header, including the CRC-8 which follows it.
+    /// Returns the `FrameHeader` and the number of bytes it occupies on success.
+    pub fn from_bytes(bytes: &[u8]) -> Result<(FrameHeader, usize)> {
+        let truncated = || Error::new(ErrorKind::InvalidInput, "frame header is truncated");
+
+        if bytes.len() < 4 {
+            return Err(truncated());
+        }
+
+        if bytes[0] != 0xFF || bytes[1] & 0xFE != 0xF8 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "frame header does not start with a sync code",
+            ));
+        }
+
+        let mut header = FrameHeader::new();
+        header.blocking_strategy = if bytes[1] & 0x1 == 0 {
+            BlockingStrategy::Fixed
+        } else {
+            BlockingStrategy::Variable
+        };
+
+        let block_size_code = bytes[2] >> 4;
+        let sample_rate_code = bytes[2] & 0xF;
+        if block_size_code == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "reserved block size in frame header",
+            ));
+        }
+        if sample_rate_code == 15 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "invalid sample rate in frame header",
+            ));
+        }
+
+        header.channel_assignment = match ChannelAssignment::from_u8(bytes[3] >> 4) {
+            Some(channel_assignment) => channel_assignment,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "reserved channel assignment in frame header",
+                ));
+            }
+        };
+
+        let bits_per_sample_code = (bytes[3] >> 1) & 0x7;
+        header.bits_per_sample = match bits_per_sample_code {
+            0 => None,
+            3 => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "reserved sample size in frame header",
+                ));
+            }
+            code => BITS_PER_SAMPLE
+                .iter()
+                .find(|&&(c, _)| c == code)
+                .map(|&(_, bps)| bps),
+        };
+
+        if bytes[3] & 0x1 != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "reserved bit set in frame header",
+            ));
+        }
+
+        let mut i = 4;
+
+        let (number, len) = read_utf8_number(&bytes[i..])?;
+        let max_number = match header.blocking_strategy {
+            BlockingStrategy::Fixed => 0x7F_FF_FF_FF,
+            BlockingStrategy::Variable => 0xF_FF_FF_FF_FF,
+        };
+        if number > max_number {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "frame number out of range in frame header",
+            ));
+        }
+        header.number = number;
+        i += len;
+
+        header.block_size = match block_size_code {
+            1 => 192,
+            2..=5 => 576 << (block_size_code - 2),
+            6 => {
+                let byte = *bytes.get(i).ok_or_else(truncated)?;
+                i += 1;
+                byte as u32 + 1
+            }
+            7 => {
+                let value = bytes.get(i..i + 2).ok_or_else(truncated)?;
+                i += 2;
+                u16::from_be_bytes([value[0], value[1]]) as u32 + 1
+            }
+            _ => 256 << (block_size_code - 8),
+        };
+
+        header.sample_rate = match sample_rate_code {
+            0 => None,
+            12 => {
+                let byte = *bytes.get(i).ok_or_else(truncated)?;
+                i += 1;
+                Some(byte as u32 * 1000)
+            }
+            13 | 14 => {
+                let value = bytes.get(i..i + 2).ok_or_else(truncated)?;
+                i += 2;
+                let value = u16::from_be_bytes([value[0], value[1]]) as u32;
+                Some(if sample_rate_code == 13 {
+                    value
+                } else {
+                    value * 10
+                })
+            }
+            code => SAMPLE_RATES
+                .iter()
+                .find(|&&(c, _)| c == code)
+                .map(|&(_, rate)| rate),
+        };
+
+        let crc = *bytes.get(i).ok_or_else(truncated)?;
+        if crc8(&bytes[..i]) != crc {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "frame header CRC mismatch",
+            ));
+        }
+        i += 1;
+
+        Ok((header, i))
+    }
+
+    /// Returns a vector representation of the frame header, including the CRC-8, suitable for
+    /// writing to a file. Sample rates and bits per sample which cannot be represented in a frame
+    /// header are written as references to the streaminfo block. Returns an error if the block
+    /// size is not between 1 and 65536 or the number does not fit in 36 bits.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.block_size == 0 || self.block_size > 0x1_0000 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "frame block size must be between 1 and 65536",
+            ));
+        }
+        if self.number > 0xF_FFFF_FFFF {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "frame number does not fit in 36 bits",
+            ));
+        }
+
+        let mut bytes = vec![0xFF, 0xF8];
+        if self.blocking_strategy == BlockingStrategy::Variable {
+            bytes[1] |= 0x1;
+        }
+
+        let (block_size_code, block_size_bytes) = match self.block_size {
+            192 => (1, Vec::new()),
+            576 | 1152 | 2304 | 4608 => (
+                (self.block_size / 576).trailing_zeros() as u8 + 2,
+                Vec::new(),
+            ),
+            256 | 512 | 1024 | 2048 | 4096 | 8192 | 16384 | 32768 => (
+                (self.block_size / 256).trailing_zeros() as u8 + 8,
+                Vec::new(),
+            ),
+            n if n <= 256 => (6, vec![(n - 1) as u8]),
+            n => (7, ((n - 1) as u16).to_be_bytes().to_vec()),
+        };
+
+        let (sample_rate_code, sample_rate_bytes) = match self.sample_rate {
+            None => (0, Vec::new()),
+            Some(rate) => match SAMPLE_RATES.iter().find(|&&(_, r)| r == rate) {
+                Some(&(code, _)) => (code, Vec::new()),
+                None if rate % 1000 == 0 && rate / 1000 <= 0xFF => (12, vec![(rate / 1000) as u8]),
+                None if rate <= 0xFF_FF => (13, (rate as u16).to_be_bytes().to_vec()),
+                None if rate % 10 == 0 && rate / 10 <= 0xFF_FF => {
+                    (14, ((rate / 10) as u16).to_be_bytes().to_vec())
+                }
+                None => (0, Vec::new()),
+            },
+        };
+        bytes.push((block_size_code << 4) | sample_rate_code);
+
+        let bits_per_sample_code = self
+            .bits_per_sample
+            .and_then(|bps| BITS_PER_SAMPLE.iter().find(|&&(_, b)| b == bps))
+            .map_or(0, |&(code, _)| code);
+        bytes.push((self.channel_assignment.to_u8() << 4) | (bits_per_sample_code << 1));
+
+        write_utf8_number(self.number, &mut bytes);
+        bytes.extend(block_size_bytes);
+        bytes.extend(sample_rate_bytes);
+
+        let crc = crc8(&bytes[..]);
+        bytes.push(crc);
+
+        Ok(bytes)
+    }
+}
+
+impl Default for FrameHeader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads a frame or sample number coded using the extended UTF-8 scheme of the frame header.
+/// Returns the number and the count of bytes used.
+fn read_utf8_number(bytes: &[u8]) -> Result<(u64, usize)> {
+    let invalid = || {
+        Error::new(
+            ErrorKind::InvalidInput,
+            "invalid coded number in frame header",
+        )
+    };
+
+    let first = *bytes.first().ok_or_else(invalid)?;
+    let (mut value, len) = match first {
+        0x00..=0x7F => return Ok((first as u64, 1)),
+        0xC0..=0xDF => ((first & 0x1F) as u64, 2),
+        0xE0..=0xEF => ((first & 0x0F) as u64, 3),
+        0xF0..=0xF7 => ((first & 0x07) as u64, 4),
+        0xF8..=0xFB => ((first & 0x03) as u64, 5),
+        0xFC..=0xFD => ((first & 0x01) as u64, 6),
+        0xFE => (0, 7),
+        _ => return Err(invalid()),
+    };
+
+    for i in 1..len {
+        let byte = *bytes.get(i).ok_or_else(invalid)?;
+        if byte & 0xC0 != 0x80 {
+            return Err(invalid());
+        }
+        value = (value << 6) | (byte & 0x3F) as u64;
+    }
+
+    Ok((value, len))
+}
+
+/// Writes a frame or sample number using the extended UTF-8 scheme of the frame header.
+fn write_utf8_number(value: u64, bytes: &mut Vec<u8>) {
+    if value < 0x80 {
+        bytes.push(value as u8);
+        return;
+    }
+
+    let len = match value {
+        0..=0x7FF => 2,
+        0x800..=0xFFFF => 3,
+        0x1_0000..=0x1F_FFFF => 4,
+        0x20_0000..=0x3FF_FFFF => 5,
+        0x400_0000..=0x7FFF_FFFF => 6,
+        _ => 7,
+    };
+
+    let first_mask = match len {
+        7 => 0,
+        _ => 0x7F >> len,
+    };
+    let prefix = !(0xFFu8 >> len);
+    bytes.push(prefix | ((value >> (6 * (len - 1))) as u8 & first_mask));
+    for i in (0..len - 1).rev() {
+        bytes.push(0x80 | ((value >> (6 * i)) as u8 & 0x3F));
+    }
+}
+// }}}
+
+// Frames {{{
+/// The location and header of an audio frame.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Frame {
+    /// Offset (in bytes) of the frame from the first byte of the first frame header.
+    pub offset: u64,
+    /// Length of the frame in bytes, including the header and the CRC-16 footer.
+    pub length: u64,
+    /// The parsed frame header.
+    pub header: FrameHeader,
+}
+
+/// Iterator over the frames of the audio data following the metadata of a FLAC stream.
+///
+/// Frames are located by their sync codes and header CRCs. The end of a frame is found by
+/// verifying the CRC-16 footer, so sync code patterns inside of the frame data are not mistaken
+/// for frames.
+pub struct Frames<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Frames<'a> {
+    /// Creates a new iterator over the frames in the audio data.
+    pub fn new(data: &'a [u8]) -> Frames<'a> {
+        Frames { data, pos: 0 }
+    }
+
+    /// Finds the first valid frame header at or after `pos`.
+    fn find_header(&self, mut pos: usize) -> Option<(usize, FrameHeader, usize)> {
+        while pos + 1 < self.data.len() {
+            if is_sync(&self.data[pos..]) {
+                if let Ok((header, len)) = FrameHeader::from_bytes(&self.data[pos..]) {
+                    return Some((pos, header, len));
+                }
+            }
+            pos += 1;
+        }
+
+        None
+    }
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        let (start, header, header_len) = self.find_header(self.pos)?;
+
+        let mut crc = crc16(&self.data[start..start + header_len]);
+        let mut fallback = None;
+        let mut end = None;
+
+        // the smallest possible frame has a one byte subframe and the two byte footer
+        let mut pos = start + header_len;
+        let min_end = pos + 3;
+        while pos < self.data.len() {
+            if pos >= min_end
+                && is_sync(&self.data[pos..])
+                && FrameHeader::from_bytes(&self.data[pos..]).is_ok()
+            {
+                if crc == 0 {
+                    end = Some(pos);
+                    break;
+                }
+                fallback = fallback.or(Some(pos));
+            }
+            crc = crc16_update(crc, self.data[pos]);
+            pos += 1;
+        }
+
+        let end = match end {
+            Some(end) => end,
+            None if crc == 0 => self.data.len(),
+            None => fallback.unwrap_or(self.data.len()),
+        };
+        self.pos = end;
+
+        Some(Frame {
+            offset: start as u64,
+            length: (end - start) as u64,
+            header,
+        })
+    }
+}
+
+fn is_sync(bytes: &[u8]) -> bool {
+    bytes.len() >= 2 && bytes[0] == 0xFF && bytes[1] & 0xFE == 0xF8
+}
+// }}}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Returns a frame in which every channel is coded as a constant 16-bit zero subframe.
+    pub(crate) fn constant_frame(header: &FrameHeader) -> Vec<u8> {
+        let mut bytes = header.to_bytes().unwrap();
+        for _ in 0..header.num_channels() {
+            bytes.extend([0x00, 0x00, 0x00].iter());
+        }
+        let crc = crc16(&bytes[..]);
+        bytes.extend(crc.to_be_bytes().iter());
+        bytes
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let mut header = FrameHeader::new();
+        header.blocking_strategy = BlockingStrategy::Variable;
+        header.block_size = 1000;
+        header.sample_rate = Some(44100);
+        header.channel_assignment = ChannelAssignment::MidSide;
+        header.bits_per_sample = Some(16);
+
+        for &number in [0, 0x7F, 0x80, 0x800, 0x1_0000, 0x7FFF_FFFF, 0xF_FFFF_FFFF].iter() {
+            header.number = number;
+            let bytes = header.to_bytes().unwrap();
+            assert_eq!(
+                FrameHeader::from_bytes(&bytes[..]).unwrap(),
+                (header, bytes.len())
+            );
+        }
+
+        header.sample_rate = Some(12345);
+        header.block_size = 100;
+        let bytes = header.to_bytes().unwrap();
+        assert_eq!(FrameHeader::from_bytes(&bytes[..]).unwrap().0, header);
+    }
+
+    #[test]
+    fn header_out_of_range() {
+        let mut header = FrameHeader::new();
+        assert!(header.to_bytes().is_err());
+        header.block_size = 0x1_0001;
+        assert!(header.to_bytes().is_err());
+        header.block_size = 0x1_0000;
+        assert_eq!(
+            FrameHeader::from_bytes(&header.to_bytes().unwrap()[..])
+                .unwrap()
+                .0,
+            header
+        );
+        header.number = 0x10_0000_0000;
+        assert!(header.to_bytes().is_err());
+    }
+
+    #[test]
+    fn header_crc_mismatch() {
+        let mut header = FrameHeader::new();
+        header.block_size = 4096;
+        let mut bytes = header.to_bytes().unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        assert!(FrameHeader::from_bytes(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn frames_ignore_embedded_sync() {
+        let mut header = FrameHeader::new();
+        header.block_size = 4096;
+        header.channel_assignment = ChannelAssignment::Independent(2);
+
+        let mut data = Vec::new();
+        for number in 0..3 {
+            header.number = number;
+            data.extend(constant_frame(&header));
+        }
+
+        // a verbatim frame whose samples contain a valid frame header
+        let mut verbatim = FrameHeader::new();
+        verbatim.block_size = 16;
+        verbatim.bits_per_sample = Some(16);
+        verbatim.number = 3;
+        let mut frame = verbatim.to_bytes().unwrap();
+        frame.push(0x02);
+        let mut samples = header.to_bytes().unwrap();
+        samples.resize(32, 0);
+        frame.extend(samples);
+        let crc = crc16(&frame[..]);
+        frame.extend(crc.to_be_bytes().iter());
+        data.extend(frame);
+
+        let frames: Vec<_> = Frames::new(&data[..]).collect();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[1].offset, frames[0].length);
+        assert_eq!(frames[3].header.block_size, 16);
+        assert_eq!(
+            frames.iter().map(|f| f.length).sum::<u64>(),
+            data.len() as u64
+        );
+    }
+
+    #[test]
+    fn streaminfo_mismatches() {
+        use crate::block::{StreamInfo, StreamInfoMismatch};
+
+        let mut header = FrameHeader::new();
+        header.block_size = 4096;
+        header.sample_rate = Some(44100);
+        header.bits_per_sample = Some(16);
+        header.channel_assignment = ChannelAssignment::Independent(2);
+
+        let mut data = Vec::new();
+        for number in 0..2 {
+            header.number = number;
+            data.extend(constant_frame(&header));
+        }
+
+        let mut streaminfo = StreamInfo::new();
+        streaminfo.min_block_size = 4096;
+        streaminfo.max_block_size = 4096;
+        streaminfo.sample_rate = 44100;
+        streaminfo.num_channels = 2;
+        streaminfo.bits_per_sample = 16;
+        streaminfo.total_samples = 8192;
+        assert!(streaminfo.check_frames(&data[..]).is_empty());
+
+        streaminfo.sample_rate = 48000;
+        streaminfo.total_samples = 10000;
+        assert_eq!(
+            streaminfo.check_frames(&data[..]),
+            vec![
+                StreamInfoMismatch::SampleRate {
+                    offset: 0,
+                    expected: 48000,
+                    found: 44100
+                },
+                StreamInfoMismatch::SampleRate {
+                    offset: data.len() as u64 / 2,
+                    expected: 48000,
+                    found: 44100
+                },
+                StreamInfoMismatch::TotalSamples {
+                    expected: 10000,
+                    found: 8192
+                },
+            ]
+        );
+
+        assert_eq!(
+            streaminfo.check_frames(&[]),
+            vec![StreamInfoMismatch::NoFrames]
+        );
+    }
+}
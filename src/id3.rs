@@ -0,0 +1,1156 @@
+//! ID3v2.2, v2.3 and v2.4 tags are parsed into frames, which can be mapped into vorbis comments
+//! and picture blocks. Text frames are mapped with `TEXT_FRAMES` and `TXXX` frames with
+//! `USER_TEXT_FRAMES`, falling back to their upper-cased description. In addition:
+//!
+//! * `TRCK` and `TPOS` values of the form `n/total` are split into `TRACKNUMBER` and
+//!   `TOTALTRACKS`, and `DISCNUMBER` and `DISCTOTAL`.
+//! * `TCON` references to ID3v1 genres such as `(17)` are replaced by the genre names.
+//! * `COMM` frames without a description are mapped to `COMMENT`, and `USLT` frames to `LYRICS`.
+//! * `UFID` frames owned by MusicBrainz are mapped to `MUSICBRAINZ_TRACKID`.
+//! * `TYER` frames are only mapped to `DATE` if the tag has no `TDRC` frame.
+//! * `APIC` frames are mapped to picture blocks.
+
+use crate::block::{Block, FieldName, Picture, PictureType, VorbisComment};
+use crate::error::{Error, ErrorKind, Result};
+use crate::tag::Tag;
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+/// Mapping of ID3v2.3 and v2.4 text frame ids to vorbis comment field names.
+pub const TEXT_FRAMES: &[(&str, &str)] = &[
+    ("TALB", "ALBUM"),
+    ("TBPM", "BPM"),
+    ("TCMP", "COMPILATION"),
+    ("TCOM", "COMPOSER"),
+    ("TCON", "GENRE"),
+    ("TCOP", "COPYRIGHT"),
+    ("TDOR", "ORIGINALDATE"),
+    ("TDRC", "DATE"),
+    ("TENC", "ENCODED-BY"),
+    ("TEXT", "LYRICIST"),
+    ("TIT1", "GROUPING"),
+    ("TIT2", "TITLE"),
+    ("TIT3", "SUBTITLE"),
+    ("TKEY", "KEY"),
+    ("TLAN", "LANGUAGE"),
+    ("TMED", "MEDIA"),
+    ("TMOO", "MOOD"),
+    ("TORY", "ORIGINALDATE"),
+    ("TPE1", "ARTIST"),
+    ("TPE2", "ALBUMARTIST"),
+    ("TPE3", "CONDUCTOR"),
+    ("TPE4", "REMIXER"),
+    ("TPOS", "DISCNUMBER"),
+    ("TPUB", "LABEL"),
+    ("TRCK", "TRACKNUMBER"),
+    ("TSO2", "ALBUMARTISTSORT"),
+    ("TSOA", "ALBUMSORT"),
+    ("TSOC", "COMPOSERSORT"),
+    ("TSOP", "ARTISTSORT"),
+    ("TSOT", "TITLESORT"),
+    ("TSRC", "ISRC"),
+    ("TSSE", "ENCODER"),
+    ("TYER", "DATE"),
+];
+
+/// Mapping of the descriptions of `TXXX` frames to vorbis comment field names. The descriptions
+/// of other `TXXX` frames are upper-cased.
+pub const USER_TEXT_FRAMES: &[(&str, &str)] = &[
+    ("MusicBrainz Album Artist Id", "MUSICBRAINZ_ALBUMARTISTID"),
+    ("MusicBrainz Album Id", "MUSICBRAINZ_ALBUMID"),
+    ("MusicBrainz Artist Id", "MUSICBRAINZ_ARTISTID"),
+    ("MusicBrainz Release Group Id", "MUSICBRAINZ_RELEASEGROUPID"),
+    ("MusicBrainz Release Track Id", "MUSICBRAINZ_RELEASETRACKID"),
+    ("MusicBrainz Work Id", "MUSICBRAINZ_WORKID"),
+];
+
+/// Owner of the `UFID` frames holding MusicBrainz recording ids.
+const MUSICBRAINZ_UFID_OWNER: &str = "http://musicbrainz.org";
+
+/// Mapping of ID3v2.2 frame ids to their ID3v2.3 equivalents.
+const V22_FRAMES: &[(&str, &str)] = &[
+    ("COM", "COMM"),
+    ("PIC", "APIC"),
+    ("TAL", "TALB"),
+    ("TBP", "TBPM"),
+    ("TCM", "TCOM"),
+    ("TCO", "TCON"),
+    ("TCP", "TCMP"),
+    ("TCR", "TCOP"),
+    ("TEN", "TENC"),
+    ("TKE", "TKEY"),
+    ("TLA", "TLAN"),
+    ("TMT", "TMED"),
+    ("TOR", "TORY"),
+    ("TP1", "TPE1"),
+    ("TP2", "TPE2"),
+    ("TP3", "TPE3"),
+    ("TP4", "TPE4"),
+    ("TPA", "TPOS"),
+    ("TPB", "TPUB"),
+    ("TRC", "TSRC"),
+    ("TRK", "TRCK"),
+    ("TS2", "TSO2"),
+    ("TSA", "TSOA"),
+    ("TSC", "TSOC"),
+    ("TSP", "TSOP"),
+    ("TSS", "TSSE"),
+    ("TST", "TSOT"),
+    ("TT1", "TIT1"),
+    ("TT2", "TIT2"),
+    ("TT3", "TIT3"),
+    ("TXT", "TEXT"),
+    ("TXX", "TXXX"),
+    ("TYE", "TYER"),
+    ("UFI", "UFID"),
+    ("ULT", "USLT"),
+];
+
+/// The genres of ID3v1 and their Winamp extensions, referenced by number in `TCON` frames.
+const ID3V1_GENRES: &[&str] = &[
+    "Blues",
+    "Classic Rock",
+    "Country",
+    "Dance",
+    "Disco",
+    "Funk",
+    "Grunge",
+    "Hip-Hop",
+    "Jazz",
+    "Metal",
+    "New Age",
+    "Oldies",
+    "Other",
+    "Pop",
+    "R&B",
+    "Rap",
+    "Reggae",
+    "Rock",
+    "Techno",
+    "Industrial",
+    "Alternative",
+    "Ska",
+    "Death Metal",
+    "Pranks",
+    "Soundtrack",
+    "Euro-Techno",
+    "Ambient",
+    "Trip-Hop",
+    "Vocal",
+    "Jazz+Funk",
+    "Fusion",
+    "Trance",
+    "Classical",
+    "Instrumental",
+    "Acid",
+    "House",
+    "Game",
+    "Sound Clip",
+    "Gospel",
+    "Noise",
+    "AlternRock",
+    "Bass",
+    "Soul",
+    "Punk",
+    "Space",
+    "Meditative",
+    "Instrumental Pop",
+    "Instrumental Rock",
+    "Ethnic",
+    "Gothic",
+    "Darkwave",
+    "Techno-Industrial",
+    "Electronic",
+    "Pop-Folk",
+    "Eurodance",
+    "Dream",
+    "Southern Rock",
+    "Comedy",
+    "Cult",
+    "Gangsta",
+    "Top 40",
+    "Christian Rap",
+    "Pop/Funk",
+    "Jungle",
+    "Native American",
+    "Cabaret",
+    "New Wave",
+    "Psychadelic",
+    "Rave",
+    "Showtunes",
+    "Trailer",
+    "Lo-Fi",
+    "Tribal",
+    "Acid Punk",
+    "Acid Jazz",
+    "Polka",
+    "Retro",
+    "Musical",
+    "Rock & Roll",
+    "Hard Rock",
+    // the Winamp extensions
+    "Folk",
+    "Folk-Rock",
+    "National Folk",
+    "Swing",
+    "Fast Fusion",
+    "Bebop",
+    "Latin",
+    "Revival",
+    "Celtic",
+    "Bluegrass",
+    "Avantgarde",
+    "Gothic Rock",
+    "Progressive Rock",
+    "Psychedelic Rock",
+    "Symphonic Rock",
+    "Slow Rock",
+    "Big Band",
+    "Chorus",
+    "Easy Listening",
+    "Acoustic",
+    "Humour",
+    "Speech",
+    "Chanson",
+    "Opera",
+    "Chamber Music",
+    "Sonata",
+    "Symphony",
+    "Booty Bass",
+    "Primus",
+    "Porn Groove",
+    "Satire",
+    "Slow Jam",
+    "Club",
+    "Tango",
+    "Samba",
+    "Folklore",
+    "Ballad",
+    "Power Ballad",
+    "Rhythmic Soul",
+    "Freestyle",
+    "Duet",
+    "Punk Rock",
+    "Drum Solo",
+    "A capella",
+    "Euro-House",
+    "Dance Hall",
+    "Goa",
+    "Drum & Bass",
+    "Club-House",
+    "Hardcore Techno",
+    "Terror",
+    "Indie",
+    "BritPop",
+    "Afro-Punk",
+    "Polsk Punk",
+    "Beat",
+    "Christian Gangsta Rap",
+    "Heavy Metal",
+    "Black Metal",
+    "Crossover",
+    "Contemporary Christian",
+    "Christian Rock",
+    "Merengue",
+    "Salsa",
+    "Thrash Metal",
+    "Anime",
+    "Jpop",
+    "Synthpop",
+    "Abstract",
+    "Art Rock",
+    "Baroque",
+    "Bhangra",
+    "Big Beat",
+    "Breakbeat",
+    "Chillout",
+    "Downtempo",
+    "Dub",
+    "EBM",
+    "Eclectic",
+    "Electro",
+    "Electroclash",
+    "Emo",
+    "Experimental",
+    "Garage",
+    "Global",
+    "IDM",
+    "Illbient",
+    "Industro-Goth",
+    "Jam Band",
+    "Krautrock",
+    "Leftfield",
+    "Lounge",
+    "Math Rock",
+    "New Romantic",
+    "Nu-Breakz",
+    "Post-Punk",
+    "Post-Rock",
+    "Psytrance",
+    "Shoegaze",
+    "Space Rock",
+    "Trop Rock",
+    "World Music",
+    "Neoclassical",
+    "Audiobook",
+    "Audio Theatre",
+    "Neue Deutsche Welle",
+    "Podcast",
+    "Indie Rock",
+    "G-Funk",
+    "Dubstep",
+    "Garage Rock",
+    "Psybient",
+];
+
+// Id3Tag {{{
+/// An ID3v2 tag.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Id3Tag {
+    /// The major version of the tag: 2, 3 or 4.
+    pub major_version: u8,
+    /// The revision of the tag.
+    pub revision: u8,
+    /// The frames of the tag, in the order in which they were read.
+    pub frames: Vec<Frame>,
+}
+
+impl Id3Tag {
+    /// Returns a new ID3v2.4 tag with no frames.
+    pub fn new() -> Id3Tag {
+        Id3Tag {
+            major_version: 4,
+            revision: 0,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Attempts to read an ID3v2 tag from the reader. The reader is left after the tag.
+    ///
+    /// Frames with invalid ids are skipped, and frames whose contents can not be parsed, such as
+    /// text in an unknown encoding, are kept as `Content::Binary`.
+    pub fn read_from(reader: &mut dyn Read) -> Result<Id3Tag> {
+        let mut header = [0; 10];
+        reader.read_exact(&mut header)?;
+        if &header[..3] != b"ID3" || !(2..=4).contains(&header[3]) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "reader does not contain an ID3v2 tag",
+            ));
+        }
+
+        let (major_version, revision, flags) = (header[3], header[4], header[5]);
+        let size = syncsafe(&header[6..10]);
+        let mut body = Vec::new();
+        reader.take(size as u64).read_to_end(&mut body)?;
+        if body.len() != size as usize {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "ID3v2 tag is truncated",
+            ));
+        }
+        if major_version == 4 && flags & 0x10 != 0 {
+            // skip the footer
+            io::copy(&mut reader.take(10), &mut io::sink())?;
+        }
+
+        if major_version < 4 && flags & 0x80 != 0 {
+            body = remove_unsynchronisation(&body[..]);
+        }
+
+        let mut frames = &body[..];
+        if flags & 0x40 != 0 {
+            let length = match major_version {
+                2 => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "compressed ID3v2.2 tags are not supported",
+                    ));
+                }
+                3 => frames.get(..4).map(|size| 4 + read_u32(size) as usize),
+                _ => frames.get(..4).map(|size| syncsafe(size) as usize),
+            };
+            frames = match length.and_then(|length| frames.get(length..)) {
+                Some(frames) => frames,
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "ID3v2 extended header is truncated",
+                    ));
+                }
+            };
+        }
+
+        Ok(Id3Tag {
+            major_version,
+            revision,
+            frames: Frame::parse_all(major_version, frames)?,
+        })
+    }
+
+    /// Attempts to read an ID3v2 tag from the start of the file at the specified path.
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Id3Tag> {
+        let file = File::open(path)?;
+        let mut reader = BufReader::new(file);
+        Id3Tag::read_from(&mut reader)
+    }
+
+    /// Returns an iterator over the frames with the id. ID3v2.2 frames have the ids of their
+    /// ID3v2.3 equivalents.
+    pub fn frames<'a>(&'a self, id: &'a str) -> impl Iterator<Item = &'a Frame> + 'a {
+        self.frames.iter().filter(move |frame| frame.id == id)
+    }
+
+    /// Returns an iterator over the pictures of the `APIC` frames.
+    pub fn pictures(&self) -> impl Iterator<Item = &Picture> {
+        self.frames.iter().filter_map(|frame| match frame.content {
+            Content::Picture(ref picture) => Some(picture),
+            _ => None,
+        })
+    }
+
+    /// Returns the vorbis comments which the frames map to. See the module documentation for the
+    /// mapping.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::id3::{Content, Frame, Id3Tag};
+    ///
+    /// let mut id3 = Id3Tag::new();
+    /// id3.frames.push(Frame::new("TIT2", Content::Text(vec!["Title".to_owned()])));
+    /// id3.frames.push(Frame::new("TRCK", Content::Text(vec!["3/12".to_owned()])));
+    ///
+    /// let comments = id3.vorbis_comments();
+    /// assert_eq!(comments.title(), Some(&vec!["Title".to_owned()]));
+    /// assert_eq!(comments.track(), Some(3));
+    /// assert_eq!(comments.total_tracks(), Some(12));
+    /// ```
+    pub fn vorbis_comments(&self) -> VorbisComment {
+        let mut comments = VorbisComment::new();
+        let mut push = |key: &str, value: &str| {
+            if !value.is_empty() {
+                comments
+                    .comments
+                    .entry(key.to_owned())
+                    .or_default()
+                    .push(value.to_owned());
+            }
+        };
+
+        // TYER and TDRC both map to DATE, and TDRC holds the full date
+        let has_recording_time = self.frames("TDRC").next().is_some();
+
+        for frame in self.frames.iter() {
+            match (frame.id.as_str(), &frame.content) {
+                ("TYER", _) if has_recording_time => {}
+                (id @ "TRCK", Content::Text(values)) | (id @ "TPOS", Content::Text(values)) => {
+                    let (number, total) = if id == "TRCK" {
+                        ("TRACKNUMBER", "TOTALTRACKS")
+                    } else {
+                        ("DISCNUMBER", "DISCTOTAL")
+                    };
+                    for value in values {
+                        let mut split = value.splitn(2, '/');
+                        push(number, split.next().unwrap_or("").trim());
+                        push(total, split.next().unwrap_or("").trim());
+                    }
+                }
+                ("TCON", Content::Text(values)) => {
+                    for value in values {
+                        push("GENRE", &genre(value));
+                    }
+                }
+                (id, Content::Text(values)) => {
+                    if let Some(&(_, key)) = TEXT_FRAMES.iter().find(|&&(frame, _)| frame == id) {
+                        for value in values {
+                            push(key, value);
+                        }
+                    }
+                }
+                (
+                    _,
+                    Content::UserText {
+                        description,
+                        values,
+                    },
+                ) => {
+                    let key = match USER_TEXT_FRAMES
+                        .iter()
+                        .find(|&&(frame, _)| frame.eq_ignore_ascii_case(description))
+                    {
+                        Some(&(_, key)) => key.to_owned(),
+                        None => description.to_ascii_uppercase(),
+                    };
+                    if FieldName::is_valid(&key) {
+                        for value in values {
+                            push(&key, value);
+                        }
+                    }
+                }
+                (
+                    "COMM",
+                    Content::Comment {
+                        description, text, ..
+                    },
+                ) if description.is_empty() => push("COMMENT", text),
+                ("USLT", Content::Comment { text, .. }) => push("LYRICS", text),
+                (_, Content::UniqueFileId { owner, identifier })
+                    if owner == MUSICBRAINZ_UFID_OWNER =>
+                {
+                    if let Ok(identifier) = std::str::from_utf8(identifier) {
+                        push("MUSICBRAINZ_TRACKID", identifier);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        comments
+    }
+
+    /// Adds the vorbis comments and pictures which the frames map to to the tag. Fields which
+    /// are already present in the tag, and pictures of types already present in the tag, are
+    /// kept.
+    pub fn migrate_into(&self, tag: &mut Tag) {
+        for (key, values) in self.vorbis_comments().comments {
+            if tag.get_vorbis(&key).is_none() {
+                tag.set_vorbis(key, values);
+            }
+        }
+
+        for picture in self.pictures() {
+            if !tag
+                .pictures()
+                .any(|existing| existing.picture_type == picture.picture_type)
+            {
+                tag.push_block(Block::Picture(picture.clone()));
+            }
+        }
+    }
+}
+
+impl Default for Id3Tag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+//}}}
+
+// Frame {{{
+/// A frame of an ID3v2 tag.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// The frame id. ID3v2.2 frames have the ids of their ID3v2.3 equivalents.
+    pub id: String,
+    /// The parsed contents of the frame.
+    pub content: Content,
+}
+
+/// The contents of an ID3v2 frame.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Content {
+    /// The values of a text frame.
+    Text(Vec<String>),
+    /// The description and values of a `TXXX` frame.
+    UserText {
+        /// The description of the values.
+        description: String,
+        /// The values.
+        values: Vec<String>,
+    },
+    /// The contents of a `COMM` or `USLT` frame.
+    Comment {
+        /// The ISO-639-2 language code.
+        language: String,
+        /// The description of the text.
+        description: String,
+        /// The text.
+        text: String,
+    },
+    /// The picture of an `APIC` frame.
+    Picture(Picture),
+    /// The owner and identifier of a `UFID` frame.
+    UniqueFileId {
+        /// The owner of the identifier.
+        owner: String,
+        /// The identifier.
+        identifier: Vec<u8>,
+    },
+    /// The data of another frame, or of a compressed or encrypted frame.
+    Binary(Vec<u8>),
+}
+
+impl Frame {
+    /// Returns a new frame with the id and contents.
+    pub fn new<T: Into<String>>(id: T, content: Content) -> Frame {
+        Frame {
+            id: id.into(),
+            content,
+        }
+    }
+
+    /// Attempts to parse the frames of a tag, which may be followed by padding.
+    fn parse_all(major_version: u8, mut bytes: &[u8]) -> Result<Vec<Frame>> {
+        let (id_length, header_length) = if major_version == 2 { (3, 6) } else { (4, 10) };
+
+        let mut frames = Vec::new();
+        while bytes.len() >= header_length && bytes[0] != 0 {
+            let id = bytes[..id_length].to_vec();
+            let size = match major_version {
+                2 => bytes[3..6]
+                    .iter()
+                    .fold(0, |acc, &b| (acc << 8) | b as usize),
+                3 => read_u32(&bytes[4..8]) as usize,
+                _ => syncsafe(&bytes[4..8]) as usize,
+            };
+            let flags = if major_version == 2 {
+                0
+            } else {
+                u16::from_be_bytes([bytes[8], bytes[9]])
+            };
+
+            let data = match bytes.get(header_length..header_length + size) {
+                Some(data) => data,
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "ID3v2 frame is truncated",
+                    ));
+                }
+            };
+            bytes = &bytes[header_length + size..];
+
+            // frames with invalid ids are skipped
+            let id = match String::from_utf8(id) {
+                Ok(ref id) if id.bytes().all(|b| b.is_ascii_alphanumeric()) => {
+                    match V22_FRAMES.iter().find(|&&(v22, _)| v22 == id) {
+                        Some(&(_, id)) => id.to_owned(),
+                        None => id.clone(),
+                    }
+                }
+                _ => continue,
+            };
+            // frames which can not be parsed are kept as binary data
+            let content = match Frame::frame_data(major_version, flags, data) {
+                Some(data) => Frame::parse_content(major_version, &id, &data[..])
+                    .unwrap_or(Content::Binary(data)),
+                None => Content::Binary(data.to_vec()),
+            };
+            frames.push(Frame { id, content });
+        }
+
+        Ok(frames)
+    }
+
+    /// Returns the data of a frame without the additional fields and unsynchronisation
+    /// indicated by its flags, or `None` if the frame is compressed or encrypted.
+    fn frame_data(major_version: u8, flags: u16, mut data: &[u8]) -> Option<Vec<u8>> {
+        match major_version {
+            3 => {
+                if flags & 0x00C0 != 0 {
+                    return None;
+                }
+                if flags & 0x0020 != 0 {
+                    data = data.get(1..)?;
+                }
+                Some(data.to_vec())
+            }
+            4 => {
+                if flags & 0x000C != 0 {
+                    return None;
+                }
+                if flags & 0x0040 != 0 {
+                    data = data.get(1..)?;
+                }
+                if flags & 0x0001 != 0 {
+                    data = data.get(4..)?;
+                }
+                if flags & 0x0002 != 0 {
+                    Some(remove_unsynchronisation(data))
+                } else {
+                    Some(data.to_vec())
+                }
+            }
+            _ => Some(data.to_vec()),
+        }
+    }
+
+    fn parse_content(major_version: u8, id: &str, data: &[u8]) -> Result<Content> {
+        let (&encoding, data) = match data.split_first() {
+            Some(split) => split,
+            None => return Ok(Content::Binary(Vec::new())),
+        };
+
+        let content = match id {
+            "TXXX" => {
+                let (description, values) = split_terminated(encoding, data);
+                Content::UserText {
+                    description: decode_text(encoding, description)?,
+                    values: decode_values(encoding, values)?,
+                }
+            }
+            _ if id.starts_with('T') => Content::Text(decode_values(encoding, data)?),
+            "COMM" | "USLT" if data.len() >= 3 => {
+                let (description, text) = split_terminated(encoding, &data[3..]);
+                Content::Comment {
+                    language: String::from_utf8_lossy(&data[..3]).into_owned(),
+                    description: decode_text(encoding, description)?,
+                    text: decode_text(encoding, text)?,
+                }
+            }
+            "APIC" => return Frame::parse_picture(major_version, encoding, data),
+            "UFID" => {
+                // the owner is always latin-1, so the first byte is not an encoding
+                let (owner, identifier) = split_terminated(0, data);
+                let mut owner_bytes = vec![encoding];
+                owner_bytes.extend(owner);
+                Content::UniqueFileId {
+                    owner: decode_text(0, &owner_bytes[..])?,
+                    identifier: identifier.to_vec(),
+                }
+            }
+            _ => {
+                let mut bytes = vec![encoding];
+                bytes.extend(data);
+                Content::Binary(bytes)
+            }
+        };
+
+        Ok(content)
+    }
+
+    fn parse_picture(major_version: u8, encoding: u8, data: &[u8]) -> Result<Content> {
+        let (mime_type, data) = if major_version == 2 {
+            let format = match data.get(..3) {
+                Some(format) => format.to_ascii_uppercase(),
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "ID3v2 frame is truncated",
+                    ))
+                }
+            };
+            let mime_type = match &format[..] {
+                b"JPG" => "image/jpeg".to_owned(),
+                b"PNG" => "image/png".to_owned(),
+                b"-->" => "-->".to_owned(),
+                _ => format!(
+                    "image/{}",
+                    String::from_utf8_lossy(&format[..]).to_lowercase()
+                ),
+            };
+            (mime_type, &data[3..])
+        } else {
+            let (mime_type, data) = split_terminated(0, data);
+            (decode_text(0, mime_type)?, data)
+        };
+
+        let (&picture_type, data) = match data.split_first() {
+            Some(split) => split,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "ID3v2 frame is truncated",
+                ))
+            }
+        };
+        let (description, data) = split_terminated(encoding, data);
+
+        let mut picture = Picture::new();
+        picture.picture_type =
+            PictureType::from_u32(picture_type as u32).unwrap_or(PictureType::Other);
+        picture.mime_type = mime_type;
+        picture.description = decode_text(encoding, description)?;
+        picture.data = data.to_vec();
+        Ok(Content::Picture(picture))
+    }
+}
+//}}}
+
+// Text {{{
+/// Splits the bytes at the first terminator of the encoding. The second part does not include
+/// the terminator, and is empty if there is no terminator.
+fn split_terminated(encoding: u8, bytes: &[u8]) -> (&[u8], &[u8]) {
+    let end = if encoding == 1 || encoding == 2 {
+        (0..bytes.len() / 2)
+            .map(|i| i * 2)
+            .find(|&i| bytes[i] == 0 && bytes[i + 1] == 0)
+            .map(|i| (i, i + 2))
+    } else {
+        bytes.iter().position(|&b| b == 0).map(|i| (i, i + 1))
+    };
+
+    match end {
+        Some((end, next)) => (&bytes[..end], &bytes[next..]),
+        None => (bytes, &[]),
+    }
+}
+
+/// Attempts to decode text in one of the ID3v2 encodings: latin-1, UTF-16 with a byte order
+/// mark, UTF-16BE or UTF-8. A trailing terminator is removed.
+fn decode_text(encoding: u8, bytes: &[u8]) -> Result<String> {
+    let text = match encoding {
+        0 => bytes.iter().map(|&b| b as char).collect(),
+        1 | 2 => {
+            let (little_endian, bytes) = match bytes {
+                [0xFF, 0xFE, rest @ ..] => (true, rest),
+                [0xFE, 0xFF, rest @ ..] => (false, rest),
+                _ => (false, bytes),
+            };
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|unit| {
+                    if little_endian {
+                        u16::from_le_bytes([unit[0], unit[1]])
+                    } else {
+                        u16::from_be_bytes([unit[0], unit[1]])
+                    }
+                })
+                .collect();
+            match String::from_utf16(&units[..]) {
+                Ok(text) => text,
+                Err(_) => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "ID3v2 frame contains invalid UTF-16 text",
+                    ));
+                }
+            }
+        }
+        3 => String::from_utf8(bytes.to_vec())?,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "ID3v2 frame has an unknown text encoding",
+            ));
+        }
+    };
+
+    Ok(text.trim_end_matches('\0').to_owned())
+}
+
+/// Attempts to decode the null separated values of a text frame.
+fn decode_values(encoding: u8, mut bytes: &[u8]) -> Result<Vec<String>> {
+    let mut values = Vec::new();
+    while !bytes.is_empty() {
+        let (value, rest) = split_terminated(encoding, bytes);
+        values.push(decode_text(encoding, value)?);
+        bytes = rest;
+    }
+    Ok(values)
+}
+
+/// Returns the genre names of a `TCON` value, replacing references to ID3v1 genres.
+fn genre(value: &str) -> String {
+    let name = |number: &str| {
+        number
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| ID3V1_GENRES.get(number))
+            .map(|&name| name.to_owned())
+    };
+
+    if let Some(name) = name(value) {
+        return name;
+    }
+    // ID3v2.3 references such as "(17)" or "(17)Rock"
+    if let Some(rest) = value.strip_prefix('(') {
+        if let Some(end) = rest.find(')') {
+            let refinement = &rest[end + 1..];
+            if !refinement.is_empty() {
+                return refinement.to_owned();
+            }
+            if let Some(name) = name(&rest[..end]) {
+                return name;
+            }
+        }
+    }
+    value.to_owned()
+}
+//}}}
+
+fn syncsafe(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |acc, &b| (acc << 7) | (b & 0x7F) as u32)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Replaces the `0xFF 0x00` sequences of unsynchronised data by `0xFF`.
+fn remove_unsynchronisation(bytes: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut previous = 0;
+    for &byte in bytes {
+        if !(previous == 0xFF && byte == 0) {
+            output.push(byte);
+        }
+        previous = byte;
+    }
+    output
+}
+
+/// Migrates the ID3v2 tag at the start of the FLAC file at the specified path into its FLAC
+/// metadata, and removes it from the file. Returns false if the file does not start with an
+/// ID3v2 tag. See `Id3Tag::migrate_into`.
+pub fn migrate_and_strip<P: AsRef<Path>>(path: P) -> Result<bool> {
+    let bytes = fs::read(&path)?;
+    if !bytes.starts_with(b"ID3") {
+        return Ok(false);
+    }
+
+    let mut reader = &bytes[..];
+    let id3 = Id3Tag::read_from(&mut reader)?;
+    let mut tag = Tag::read_from(&mut reader)?;
+    id3.migrate_into(&mut tag);
+
+    let mut output = Vec::with_capacity(bytes.len());
+    tag.write_to(&mut output)?;
+    output.extend(reader);
+    fs::write(path, output)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::StreamInfo;
+
+    fn frame(version: u8, id: &[u8], flags: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        match version {
+            2 => bytes.extend(&(data.len() as u32).to_be_bytes()[1..]),
+            3 => bytes.extend(&(data.len() as u32).to_be_bytes()),
+            _ => bytes.extend(&to_syncsafe(data.len() as u32)),
+        }
+        if version > 2 {
+            bytes.extend(&flags.to_be_bytes());
+        }
+        bytes.extend(data);
+        bytes
+    }
+
+    fn to_syncsafe(n: u32) -> [u8; 4] {
+        [
+            (n >> 21) as u8 & 0x7F,
+            (n >> 14) as u8 & 0x7F,
+            (n >> 7) as u8 & 0x7F,
+            n as u8 & 0x7F,
+        ]
+    }
+
+    fn id3(version: u8, flags: u8, frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut body: Vec<u8> = frames.concat();
+        body.extend(&[0; 16]);
+        let mut bytes = vec![b'I', b'D', b'3', version, 0, flags];
+        bytes.extend(&to_syncsafe(body.len() as u32));
+        bytes.extend(body);
+        bytes
+    }
+
+    fn utf16(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(
+            text.encode_utf16()
+                .flat_map(|unit| unit.to_le_bytes().to_vec()),
+        );
+        bytes
+    }
+
+    #[test]
+    fn parse_v24() {
+        let mut apic = b"\x00image/png\x00\x03cover\x00".to_vec();
+        apic.extend(&[0x89, b'P', b'N', b'G']);
+        let mut comm = b"\x01eng".to_vec();
+        comm.extend(utf16(""));
+        comm.extend(&[0, 0]);
+        comm.extend(utf16("A comment"));
+
+        let bytes = id3(
+            4,
+            0,
+            &[
+                frame(4, b"TIT2", 0, b"\x03Title"),
+                frame(4, b"TPE1", 0, b"\x03First\x00Second"),
+                frame(4, b"TRCK", 0, b"\x003/12"),
+                frame(4, b"TCON", 0, b"\x0017"),
+                frame(4, b"TXXX", 0, b"\x03MusicBrainz Album Id\x00abc"),
+                frame(4, b"TXXX", 0, b"\x03replaygain_track_gain\x00-6.00 dB"),
+                frame(4, b"UFID", 0, b"http://musicbrainz.org\x00id"),
+                frame(4, b"COMM", 0, &comm[..]),
+                frame(4, b"APIC", 0, &apic[..]),
+                frame(4, b"PRIV", 0, b"owner\x00data"),
+            ],
+        );
+
+        let tag = Id3Tag::read_from(&mut &bytes[..]).unwrap();
+        assert_eq!(tag.major_version, 4);
+        assert_eq!(tag.frames.len(), 10);
+        assert_eq!(
+            tag.frames[7].content,
+            Content::Comment {
+                language: "eng".to_owned(),
+                description: String::new(),
+                text: "A comment".to_owned(),
+            }
+        );
+
+        let comments = tag.vorbis_comments();
+        let get = |key: &str| comments.get(key).unwrap().clone();
+        assert_eq!(get("TITLE"), vec!["Title"]);
+        assert_eq!(get("ARTIST"), vec!["First", "Second"]);
+        assert_eq!(get("TRACKNUMBER"), vec!["3"]);
+        assert_eq!(get("TOTALTRACKS"), vec!["12"]);
+        assert_eq!(get("GENRE"), vec!["Rock"]);
+        assert_eq!(get("MUSICBRAINZ_ALBUMID"), vec!["abc"]);
+        assert_eq!(get("REPLAYGAIN_TRACK_GAIN"), vec!["-6.00 dB"]);
+        assert_eq!(get("MUSICBRAINZ_TRACKID"), vec!["id"]);
+        assert_eq!(get("COMMENT"), vec!["A comment"]);
+
+        let picture = tag.pictures().next().unwrap();
+        assert_eq!(picture.mime_type, "image/png");
+        assert_eq!(picture.picture_type, PictureType::CoverFront);
+        assert_eq!(picture.description, "cover");
+        assert_eq!(picture.data, vec![0x89, b'P', b'N', b'G']);
+    }
+
+    #[test]
+    fn parse_v22_and_v23() {
+        let bytes = id3(
+            2,
+            0,
+            &[
+                frame(2, b"TT2", 0, b"\x00Old title"),
+                frame(2, b"PIC", 0, b"\x00JPG\x04\x00\xFF\xD8"),
+            ],
+        );
+        let tag = Id3Tag::read_from(&mut &bytes[..]).unwrap();
+        assert_eq!(tag.frames[0].id, "TIT2");
+        assert_eq!(tag.vorbis_comments().title().unwrap(), &vec!["Old title"]);
+        let picture = tag.pictures().next().unwrap();
+        assert_eq!(picture.mime_type, "image/jpeg");
+        assert_eq!(picture.picture_type, PictureType::CoverBack);
+
+        // an unsynchronised v2.3 tag with a grouped frame
+        let mut data = b"\x01".to_vec();
+        data.extend(utf16("Album"));
+        let mut frames = frame(3, b"TALB", 0, &data[..]);
+        frames.extend(frame(3, b"TPOS", 0x0020, b"\x01\x001/2"));
+        let mut unsynchronised = Vec::new();
+        for byte in frames {
+            unsynchronised.push(byte);
+            if byte == 0xFF {
+                unsynchronised.push(0);
+            }
+        }
+        let bytes = id3(3, 0x80, &[unsynchronised]);
+        let comments = Id3Tag::read_from(&mut &bytes[..])
+            .unwrap()
+            .vorbis_comments();
+        assert_eq!(comments.album().unwrap(), &vec!["Album"]);
+        assert_eq!(comments.get("DISCNUMBER").unwrap(), &vec!["1"]);
+        assert_eq!(comments.get("DISCTOTAL").unwrap(), &vec!["2"]);
+    }
+
+    #[test]
+    fn malformed_frames() {
+        let bytes = id3(
+            4,
+            0,
+            &[
+                frame(4, b"TIT2", 0, b"\x07Unknown encoding"),
+                frame(4, b"TALB", 0, b"\x01\xFF\xFE\x00\xD8"),
+                frame(4, b"T\xFFT\xFE", 0, b"\x03Invalid id"),
+                frame(4, b"TYER", 0, b"\x032001"),
+                frame(4, b"TDRC", 0, b"\x032001-05-03"),
+                frame(4, b"TPE1", 0, b"\x03Artist"),
+            ],
+        );
+
+        let tag = Id3Tag::read_from(&mut &bytes[..]).unwrap();
+        assert_eq!(tag.frames.len(), 5);
+        assert_eq!(
+            tag.frames[0].content,
+            Content::Binary(b"\x07Unknown encoding".to_vec())
+        );
+        assert_eq!(
+            tag.frames[1].content,
+            Content::Binary(b"\x01\xFF\xFE\x00\xD8".to_vec())
+        );
+
+        let comments = tag.vorbis_comments();
+        assert_eq!(comments.get("DATE").unwrap(), &vec!["2001-05-03"]);
+        assert_eq!(comments.artist().unwrap(), &vec!["Artist"]);
+        assert!(comments.title().is_none());
+    }
+
+    #[test]
+    fn genres() {
+        assert_eq!(genre("(17)"), "Rock");
+        assert_eq!(genre("(4)Eurodisco"), "Eurodisco");
+        assert_eq!(genre("Shoegaze"), "Shoegaze");
+        assert_eq!(genre("(999)"), "(999)");
+        assert_eq!(genre("(80)"), "Folk");
+        assert_eq!(genre("(191)"), "Psybient");
+        assert_eq!(ID3V1_GENRES.len(), 192);
+    }
+
+    #[test]
+    fn migrate() {
+        let id3 = Id3Tag::read_from(
+            &mut &id3(
+                4,
+                0,
+                &[
+                    frame(4, b"TIT2", 0, b"\x03ID3 title"),
+                    frame(4, b"TALB", 0, b"\x03ID3 album"),
+                    frame(4, b"APIC", 0, b"\x00image/png\x00\x03\x00\x01"),
+                ],
+            )[..],
+        )
+        .unwrap();
+
+        let mut tag = Tag::new();
+        tag.set_vorbis("TITLE", vec!["FLAC title"]);
+        id3.migrate_into(&mut tag);
+        assert_eq!(tag.get_vorbis("TITLE").unwrap().next(), Some("FLAC title"));
+        assert_eq!(tag.get_vorbis("ALBUM").unwrap().next(), Some("ID3 album"));
+        assert_eq!(tag.pictures().count(), 1);
+
+        // pictures of types already present are kept
+        id3.migrate_into(&mut tag);
+        assert_eq!(tag.pictures().count(), 1);
+    }
+
+    #[test]
+    fn migrate_and_strip_file() {
+        let mut streaminfo = StreamInfo::new();
+        streaminfo.sample_rate = 44100;
+        streaminfo.num_channels = 1;
+        streaminfo.bits_per_sample = 16;
+        streaminfo.md5 = vec![0; 16];
+        let mut tag = Tag::new();
+        tag.set_streaminfo(streaminfo);
+
+        let mut bytes = id3(4, 0, &[frame(4, b"TIT2", 0, b"\x03Title")]);
+        tag.write_to(&mut bytes).unwrap();
+        bytes.extend(b"audio");
+
+        let path =
+            std::env::temp_dir().join(format!("metaflac-id3-migrate-{}.flac", std::process::id()));
+        fs::write(&path, &bytes[..]).unwrap();
+        assert!(migrate_and_strip(&path).unwrap());
+        assert!(!migrate_and_strip(&path).unwrap());
+
+        let stripped = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(stripped.starts_with(b"fLaC"));
+        assert!(stripped.ends_with(b"audio"));
+        let tag = Tag::read_from(&mut &stripped[..]).unwrap();
+        assert_eq!(tag.get_vorbis("TITLE").unwrap().next(), Some("Title"));
+    }
+}
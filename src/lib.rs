@@ -8,6 +8,31 @@ pub use tag::Tag;
 
 /// Includes various types of metadata blocks.
 pub mod block;
+/// Conversion between FLAC and WAV, AIFF or Wave64 files.
+pub mod convert;
+/// Decoding of the audio frames.
+pub mod decoder;
+/// Encoding of audio as a FLAC stream.
+pub mod encoder;
+/// Chunks of WAV, AIFF and Wave64 files kept in application blocks.
+pub mod foreign;
+/// Parsing of the audio frames which follow the metadata.
+pub mod frame;
+/// Parsing of ID3v2 tags and their migration into vorbis comments and pictures.
+pub mod id3;
+/// Measurement of loudness with EBU R128 and computation of ReplayGain values.
+pub mod loudness;
+/// Reading and writing of FLAC metadata in the `dfLa` box of MP4 files.
+pub mod mp4;
+/// Reading and writing of metadata in Ogg FLAC, Vorbis and Opus streams.
+pub mod ogg;
+/// Properties of the audio such as its duration and bitrate.
+pub mod properties;
+/// Locating samples within the audio frames.
+pub mod seek;
 
+mod base64;
+mod bitstream;
+mod crc;
 mod error;
 mod tag;
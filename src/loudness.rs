@@ -0,0 +1,632 @@
+//! Loudness is measured as described by EBU R128 and ITU-R BS.1770: the audio is K-weighted, its
+//! energy is measured over 400 ms blocks overlapping by 75%, and the integrated loudness is the
+//! mean energy of the blocks above an absolute gate of -70 LUFS and a relative gate 10 LU below
+//! the mean of those blocks. Peaks are sample peaks, as written by `metaflac --add-replay-gain`.
+//! True peaks are measured as well, by oversampling the audio as described by ITU-R BS.1770
+//! Annex 2, but are not written to tags.
+
+use crate::block::{ReplayGain, StreamInfo};
+use crate::decoder;
+use crate::error::{Error, ErrorKind, Result};
+use crate::tag::Tag;
+
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// The absolute gate in LUFS.
+const ABSOLUTE_GATE: f64 = -70.0;
+
+/// The relative gate in LU below the loudness of the blocks above the absolute gate.
+const RELATIVE_GATE: f64 = -10.0;
+
+/// The number of filter taps of each phase of the true peak interpolation filter.
+const TAPS_PER_PHASE: usize = 12;
+
+/// Returns the loudness in LUFS of a weighted mean square energy.
+fn loudness(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+/// Returns the weighted mean square energy of a loudness in LUFS.
+fn energy(loudness: f64) -> f64 {
+    10f64.powf((loudness + 0.691) / 10.0)
+}
+
+// Biquad {{{
+/// A second order IIR filter in transposed direct form II.
+#[derive(Clone, Debug)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+
+    /// Returns the high shelf filter of the first stage of the K-weighting.
+    fn shelf(sample_rate: u32) -> Biquad {
+        let gain = 3.999_843_853_973_347;
+        let q = 0.707_175_236_955_419_6;
+        let k = (PI * 1_681.974_450_955_533 / sample_rate as f64).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+        Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            state: [0.0; 2],
+        }
+    }
+
+    /// Returns the high pass filter of the second stage of the K-weighting.
+    fn high_pass(sample_rate: u32) -> Biquad {
+        let q = 0.500_327_037_323_877_3;
+        let k = (PI * 38.135_470_876_024_44 / sample_rate as f64).tan();
+        let a0 = 1.0 + k / q + k * k;
+        Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            state: [0.0; 2],
+        }
+    }
+}
+//}}}
+
+// Oversampler {{{
+/// Interpolates a channel with a windowed sinc filter, to find the peaks between samples.
+#[derive(Clone, Debug)]
+struct Oversampler {
+    /// The filter coefficients of each interpolated phase.
+    phases: Vec<Vec<f64>>,
+    /// The most recent samples, the newest first.
+    history: Vec<f64>,
+}
+
+impl Oversampler {
+    /// Returns an oversampler which brings the sample rate to at least 192 kHz.
+    fn new(sample_rate: u32) -> Oversampler {
+        let factor = match sample_rate {
+            0..=95_999 => 4,
+            96_000..=191_999 => 2,
+            _ => {
+                return Oversampler {
+                    phases: vec![vec![1.0]],
+                    history: vec![0.0],
+                };
+            }
+        };
+
+        let taps = TAPS_PER_PHASE * factor;
+        let coefficients: Vec<f64> = (0..taps)
+            .map(|j| {
+                let x = (j as f64 - (taps - 1) as f64 / 2.0) * PI / factor as f64;
+                let window = 0.5 * (1.0 - (2.0 * PI * j as f64 / (taps - 1) as f64).cos());
+                x.sin() / x * window
+            })
+            .collect();
+
+        Oversampler {
+            phases: (0..factor)
+                .map(|phase| {
+                    coefficients
+                        .iter()
+                        .skip(phase)
+                        .step_by(factor)
+                        .cloned()
+                        .collect()
+                })
+                .collect(),
+            history: vec![0.0; TAPS_PER_PHASE],
+        }
+    }
+
+    /// Adds a sample and returns the largest absolute value of the interpolated samples.
+    fn process(&mut self, x: f64) -> f64 {
+        self.history.rotate_right(1);
+        self.history[0] = x;
+        self.phases
+            .iter()
+            .map(|phase| {
+                phase
+                    .iter()
+                    .zip(self.history.iter())
+                    .map(|(c, x)| c * x)
+                    .sum::<f64>()
+                    .abs()
+            })
+            .fold(0.0, f64::max)
+    }
+}
+//}}}
+
+// Measurement {{{
+/// The result of measuring the loudness of some audio.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measurement {
+    /// The channel weighted mean square energy of the K-weighted audio in each 400 ms block.
+    pub block_energies: Vec<f64>,
+    /// The largest absolute sample value, relative to full scale. This is the peak written to
+    /// tags.
+    pub peak: f64,
+    /// The largest absolute value of the audio oversampled to at least 192 kHz, relative to full
+    /// scale. This estimates the true peak between samples, which can exceed the sample peak.
+    pub true_peak: f64,
+}
+
+impl Measurement {
+    /// Returns a measurement of no audio.
+    pub fn new() -> Measurement {
+        Measurement {
+            block_energies: Vec::new(),
+            peak: 0.0,
+            true_peak: 0.0,
+        }
+    }
+
+    /// Returns the measurement of the audio of all measurements played in sequence, as used for
+    /// the album gain.
+    pub fn combine(measurements: &[Measurement]) -> Measurement {
+        Measurement {
+            block_energies: measurements
+                .iter()
+                .flat_map(|measurement| measurement.block_energies.iter().cloned())
+                .collect(),
+            peak: measurements
+                .iter()
+                .fold(0.0, |peak, measurement| measurement.peak.max(peak)),
+            true_peak: measurements
+                .iter()
+                .fold(0.0, |peak, measurement| measurement.true_peak.max(peak)),
+        }
+    }
+
+    /// Returns the gated integrated loudness in LUFS, or `None` if the audio is shorter than a
+    /// block or silent.
+    pub fn integrated_loudness(&self) -> Option<f64> {
+        let absolute = energy(ABSOLUTE_GATE);
+        let blocks: Vec<f64> = self
+            .block_energies
+            .iter()
+            .cloned()
+            .filter(|&block| block > absolute)
+            .collect();
+        if blocks.is_empty() {
+            return None;
+        }
+
+        let mean = blocks.iter().sum::<f64>() / blocks.len() as f64;
+        let relative = mean * 10f64.powf(RELATIVE_GATE / 10.0);
+        let gated: Vec<f64> = blocks.into_iter().filter(|&b| b > relative).collect();
+        Some(loudness(gated.iter().sum::<f64>() / gated.len() as f64))
+    }
+
+    /// Returns the ReplayGain 2.0 gain in dB, which brings the integrated loudness to
+    /// `ReplayGain::REFERENCE_LOUDNESS`.
+    pub fn gain(&self) -> Option<f64> {
+        self.integrated_loudness()
+            .map(|loudness| ReplayGain::REFERENCE_LOUDNESS - loudness)
+    }
+}
+
+impl Default for Measurement {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+//}}}
+
+// Meter {{{
+/// Measures the loudness of audio pushed in any number of parts.
+///
+/// # Example
+/// ```
+/// use metaflac::loudness::Meter;
+///
+/// // one second of a full scale 1 kHz sine in the left channel
+/// let samples: Vec<i32> = (0..48000)
+///     .flat_map(|n| {
+///         let phase = n as f64 * 1000.0 / 48000.0 * 2.0 * std::f64::consts::PI;
+///         vec![(phase.sin() * 32767.0) as i32, 0]
+///     })
+///     .collect();
+///
+/// let mut meter = Meter::new(48000, 2, 16).unwrap();
+/// meter.push(&samples);
+/// let measurement = meter.finish();
+///
+/// let loudness = measurement.integrated_loudness().unwrap();
+/// assert!((loudness + 3.01).abs() < 0.05);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Meter {
+    filters: Vec<(Biquad, Biquad)>,
+    oversamplers: Vec<Oversampler>,
+    weights: Vec<f64>,
+    scale: f64,
+    step_size: usize,
+    step_length: usize,
+    step_energy: f64,
+    steps: [f64; 4],
+    num_steps: usize,
+    measurement: Measurement,
+}
+
+impl Meter {
+    /// Attempts to create a meter for audio of the format. Returns an error if the sample rate is
+    /// below 4000 Hz, or if the number of channels or bits per sample are not supported by FLAC.
+    pub fn new(sample_rate: u32, num_channels: u8, bits_per_sample: u8) -> Result<Meter> {
+        if sample_rate < 4000 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "sample rate is too low to measure loudness",
+            ));
+        }
+        if !(1..=8).contains(&num_channels) || !(4..=32).contains(&bits_per_sample) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "unsupported audio format",
+            ));
+        }
+
+        Ok(Meter {
+            filters: (0..num_channels)
+                .map(|_| (Biquad::shelf(sample_rate), Biquad::high_pass(sample_rate)))
+                .collect(),
+            oversamplers: (0..num_channels)
+                .map(|_| Oversampler::new(sample_rate))
+                .collect(),
+            weights: (0..num_channels)
+                .map(|channel| channel_weight(num_channels, channel))
+                .collect(),
+            scale: 1.0 / (1u64 << (bits_per_sample - 1)) as f64,
+            step_size: (sample_rate as usize + 5) / 10,
+            step_length: 0,
+            step_energy: 0.0,
+            steps: [0.0; 4],
+            num_steps: 0,
+            measurement: Measurement::new(),
+        })
+    }
+
+    /// Returns a meter for the audio described by the streaminfo block.
+    pub fn from_streaminfo(streaminfo: &StreamInfo) -> Result<Meter> {
+        Meter::new(
+            streaminfo.sample_rate,
+            streaminfo.num_channels,
+            streaminfo.bits_per_sample,
+        )
+    }
+
+    /// Measures interleaved samples of all channels. A trailing partial frame is ignored.
+    pub fn push(&mut self, samples: &[i32]) {
+        for frame in samples.chunks_exact(self.filters.len()) {
+            for (((&sample, filters), oversampler), weight) in frame
+                .iter()
+                .zip(self.filters.iter_mut())
+                .zip(self.oversamplers.iter_mut())
+                .zip(self.weights.iter())
+            {
+                let x = sample as f64 * self.scale;
+                let measurement = &mut self.measurement;
+                measurement.peak = measurement.peak.max(x.abs());
+                measurement.true_peak = measurement
+                    .true_peak
+                    .max(x.abs())
+                    .max(oversampler.process(x));
+                let y = filters.1.process(filters.0.process(x));
+                self.step_energy += weight * y * y;
+            }
+
+            self.step_length += 1;
+            if self.step_length == self.step_size {
+                self.steps.rotate_left(1);
+                self.steps[3] = self.step_energy;
+                self.step_energy = 0.0;
+                self.step_length = 0;
+                self.num_steps += 1;
+                if self.num_steps >= self.steps.len() {
+                    let energy = self.steps.iter().sum::<f64>() / (4 * self.step_size) as f64;
+                    self.measurement.block_energies.push(energy);
+                }
+            }
+        }
+    }
+
+    /// Returns the measurement of the audio. A trailing partial block is ignored.
+    pub fn finish(self) -> Measurement {
+        self.measurement
+    }
+}
+
+/// Returns the weight of a channel in the FLAC channel order. The LFE channel is ignored and
+/// surround channels are weighted by +1.5 dB.
+fn channel_weight(num_channels: u8, channel: u8) -> f64 {
+    match (num_channels, channel) {
+        (6..=8, 3) => 0.0,
+        (4, 2..=3) | (5, 3..=4) | (6..=8, 4..) => 1.41,
+        _ => 1.0,
+    }
+}
+//}}}
+
+/// Attempts to measure the loudness of the interleaved samples of the audio described by the
+/// streaminfo block.
+pub fn analyze(streaminfo: &StreamInfo, samples: &[i32]) -> Result<Measurement> {
+    let mut meter = Meter::from_streaminfo(streaminfo)?;
+    meter.push(samples);
+    Ok(meter.finish())
+}
+
+/// Attempts to decode the FLAC file at the specified path and measure the loudness of its audio.
+pub fn analyze_path<P: AsRef<Path>>(path: P) -> Result<Measurement> {
+    let file = File::open(path)?;
+    analyze_stream(&mut BufReader::new(file)).map(|(_, measurement)| measurement)
+}
+
+/// Attempts to read a FLAC stream from the reader and measure the loudness of its audio, decoding
+/// one frame at a time. Returns the tag of the stream and the measurement.
+fn analyze_stream(reader: &mut dyn Read) -> Result<(Tag, Measurement)> {
+    let tag = Tag::read_from(reader)?;
+    let mut audio = Vec::new();
+    reader.read_to_end(&mut audio)?;
+
+    let measurement = match tag.get_streaminfo() {
+        Some(streaminfo) => {
+            let mut meter = Meter::from_streaminfo(streaminfo)?;
+            for frame in decoder::frames(streaminfo, &audio[..]) {
+                meter.push(&frame?.interleaved()[..]);
+            }
+            meter.finish()
+        }
+        None => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "stream does not contain a streaminfo block",
+            ));
+        }
+    };
+    Ok((tag, measurement))
+}
+
+/// Sets the ReplayGain track gain and peak of the tag from the measurement. An album gain which is
+/// already set is kept, and all gains are written relative to `ReplayGain::REFERENCE_LOUDNESS`.
+/// A track gain which is already set is kept if the audio is silent or shorter than a block.
+pub fn set_track_gain(tag: &mut Tag, measurement: &Measurement) {
+    let comments = tag.vorbis_comments_mut();
+    let mut gain = comments.replay_gain().unwrap_or_default();
+    let offset = ReplayGain::REFERENCE_LOUDNESS - gain.reference();
+    gain.album_gain = gain.album_gain.map(|album_gain| album_gain + offset);
+    gain.track_gain = measurement
+        .gain()
+        .or_else(|| gain.track_gain.map(|track_gain| track_gain + offset));
+    gain.track_peak = Some(measurement.peak);
+    gain.reference_loudness = Some(ReplayGain::REFERENCE_LOUDNESS);
+    comments.set_replay_gain(&gain);
+}
+
+/// Sets the ReplayGain track and album gains and peaks of the tags of an album from the
+/// measurements of their audio, in the same order. Returns an error if the number of tags and
+/// measurements differ.
+///
+/// # Example
+/// ```
+/// use metaflac::loudness::{self, Measurement};
+/// use metaflac::Tag;
+///
+/// let quiet = Measurement { block_energies: vec![0.01; 10], peak: 0.25, true_peak: 0.25 };
+/// let loud = Measurement { block_energies: vec![0.1; 10], peak: 0.5, true_peak: 0.5 };
+///
+/// let mut tags = vec![Tag::new(), Tag::new()];
+/// loudness::set_album_gain(&mut tags, &[quiet, loud]).unwrap();
+///
+/// let track = tags[0].vorbis_comments().unwrap().replay_gain().unwrap();
+/// let album = tags[1].vorbis_comments().unwrap().replay_gain().unwrap();
+/// assert_eq!(track.album_gain, album.album_gain);
+/// assert_eq!(track.album_peak, Some(0.5));
+/// ```
+pub fn set_album_gain(tags: &mut [Tag], measurements: &[Measurement]) -> Result<()> {
+    if tags.len() != measurements.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "number of tags and measurements differ",
+        ));
+    }
+
+    let album = Measurement::combine(measurements);
+    for (tag, measurement) in tags.iter_mut().zip(measurements.iter()) {
+        set_track_gain(tag, measurement);
+        let comments = tag.vorbis_comments_mut();
+        let mut gain = comments.replay_gain().unwrap_or_default();
+        gain.album_gain = album.gain().or(gain.album_gain);
+        gain.album_peak = Some(album.peak);
+        comments.set_replay_gain(&gain);
+    }
+    Ok(())
+}
+
+/// Attempts to measure the FLAC files at the specified paths and write their ReplayGain track
+/// gains and peaks, and album gains and peaks treating all files as one album, like
+/// `metaflac --add-replay-gain`.
+pub fn add_replay_gain<P: AsRef<Path>>(paths: &[P]) -> Result<()> {
+    let mut tags = Vec::with_capacity(paths.len());
+    let mut measurements = Vec::with_capacity(paths.len());
+    for path in paths.iter() {
+        let file = File::open(path)?;
+        let (tag, measurement) = analyze_stream(&mut BufReader::new(file))?;
+        tags.push(tag);
+        measurements.push(measurement);
+    }
+
+    set_album_gain(&mut tags[..], &measurements[..])?;
+    for (tag, path) in tags.iter_mut().zip(paths.iter()) {
+        tag.write_to_path(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::{self, EncoderConfig};
+
+    fn sine(sample_rate: u32, seconds: f64, amplitude: f64, num_channels: usize) -> Vec<i32> {
+        let num_samples = (sample_rate as f64 * seconds) as usize;
+        (0..num_samples)
+            .flat_map(|n| {
+                let phase = 2.0 * PI * 997.0 * n as f64 / sample_rate as f64;
+                let sample = (phase.sin() * amplitude * 32767.0).round() as i32;
+                (0..num_channels).map(move |channel| if channel == 0 { sample } else { 0 })
+            })
+            .collect()
+    }
+
+    fn measure(sample_rate: u32, num_channels: u8, samples: &[i32]) -> Measurement {
+        let mut meter = Meter::new(sample_rate, num_channels, 16).unwrap();
+        for part in samples.chunks(1001 * num_channels as usize) {
+            meter.push(part);
+        }
+        meter.finish()
+    }
+
+    #[test]
+    fn sine_loudness() {
+        for &sample_rate in &[44100, 48000, 96000] {
+            let measurement = measure(sample_rate, 2, &sine(sample_rate, 3.0, 1.0, 2));
+            let loudness = measurement.integrated_loudness().unwrap();
+            assert!((loudness + 3.01).abs() < 0.05, "{}", loudness);
+            assert!(measurement.peak > 0.99 && measurement.peak < 1.0);
+        }
+
+        // -20 dBFS mono
+        let measurement = measure(48000, 1, &sine(48000, 3.0, 0.1, 1));
+        let loudness = measurement.integrated_loudness().unwrap();
+        assert!((loudness + 23.01).abs() < 0.05, "{}", loudness);
+        assert!((measurement.gain().unwrap() - 5.01).abs() < 0.05);
+
+        // the LFE channel of 5.1 audio is ignored
+
+        let lfe: Vec<i32> = sine(48000, 1.0, 1.0, 6)
+            .chunks(6)
+            .flat_map(|frame| vec![0, 0, 0, frame[0], 0, 0])
+            .collect();
+        assert_eq!(measure(48000, 6, &lfe).integrated_loudness(), None);
+    }
+
+    #[test]
+    fn gating() {
+        let mut samples = sine(48000, 10.0, 0.1, 1);
+        let loudness = measure(48000, 1, &samples).integrated_loudness().unwrap();
+
+        // silence and very quiet audio are gated, apart from the blocks overlapping the sine
+        samples.extend(vec![0; 48000 * 5]);
+        samples.extend(sine(48000, 2.0, 0.001, 1));
+        let gated = measure(48000, 1, &samples).integrated_loudness().unwrap();
+        assert!((gated - loudness).abs() < 0.2, "{} {}", gated, loudness);
+
+        assert_eq!(
+            measure(48000, 1, &sine(48000, 0.3, 1.0, 1))
+                .block_energies
+                .len(),
+            0
+        );
+        assert_eq!(measure(48000, 1, &[0; 48000]).integrated_loudness(), None);
+        assert!(Meter::new(2000, 1, 16).is_err());
+        assert!(Meter::new(44100, 0, 16).is_err());
+    }
+
+    #[test]
+    fn true_peak() {
+        for &(sample_rate, true_peak) in
+            &[(44100, 0.5), (48000, 0.5), (96000, 0.47), (192000, 0.354)]
+        {
+            // a quarter of the sample rate, sampled halfway between its peaks, which is under-read
+            // with 2x oversampling and not oversampled at 192 kHz
+            let samples: Vec<i32> = (0..sample_rate as usize)
+                .map(|n| {
+                    let phase = PI / 2.0 * n as f64 + PI / 4.0;
+                    (phase.sin() * 0.5 * 32767.0).round() as i32
+                })
+                .collect();
+            let measurement = measure(sample_rate, 1, &samples);
+            assert!((measurement.peak - 0.354).abs() < 0.001);
+            assert!(
+                (measurement.true_peak - true_peak).abs() < 0.005,
+                "{}",
+                measurement.true_peak
+            );
+        }
+    }
+
+    #[test]
+    fn silent_track_gain() {
+        let mut tag = Tag::new();
+        tag.set_vorbis("REPLAYGAIN_TRACK_GAIN", vec!["-3.00 dB"]);
+        tag.set_vorbis("REPLAYGAIN_REFERENCE_LOUDNESS", vec!["89 dB"]);
+
+        set_track_gain(&mut tag, &measure(48000, 1, &[0; 48000]));
+        let gain = tag.vorbis_comments().unwrap().replay_gain().unwrap();
+        assert_eq!(gain.track_gain, Some(-3.0));
+        assert_eq!(gain.track_peak, Some(0.0));
+    }
+
+    #[test]
+    fn album_gain() {
+        let path = std::env::temp_dir().join(format!("metaflac-loudness-{}", std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+
+        let mut paths = Vec::new();
+        for (i, &amplitude) in [0.5, 0.05].iter().enumerate() {
+            let mut tag = Tag::new();
+            tag.set_vorbis("REPLAYGAIN_ALBUM_GAIN", vec!["+2.00 dB"]);
+            tag.set_vorbis("REPLAYGAIN_REFERENCE_LOUDNESS", vec!["89 dB"]);
+            tag.set_vorbis("R128_TRACK_GAIN", vec!["100"]);
+
+            let file = path.join(format!("{}.flac", i));
+            let mut flac = Vec::new();
+            let config = EncoderConfig::new(44100, 1, 16);
+            encoder::encode(&config, &sine(44100, 2.0, amplitude, 1), &tag, &mut flac).unwrap();
+            std::fs::write(&file, flac).unwrap();
+            paths.push(file);
+        }
+
+        // track mode keeps the album gain
+        let mut tag = Tag::read_from_path(&paths[0]).unwrap();
+        set_track_gain(&mut tag, &analyze_path(&paths[0]).unwrap());
+        let gain = tag.vorbis_comments().unwrap().replay_gain().unwrap();
+        assert_eq!(gain.album_gain, Some(2.0));
+        assert!((gain.track_gain.unwrap() + 8.97).abs() < 0.05);
+        assert!(tag.get_vorbis("R128_TRACK_GAIN").is_none());
+
+        add_replay_gain(&paths[..]).unwrap();
+        let gains: Vec<ReplayGain> = paths
+            .iter()
+            .map(|path| {
+                let tag = Tag::read_from_path(path).unwrap();
+                tag.vorbis_comments().unwrap().replay_gain().unwrap()
+            })
+            .collect();
+        std::fs::remove_dir_all(&path).unwrap();
+
+        assert!((gains[0].track_gain.unwrap() + 8.97).abs() < 0.05);
+        assert!((gains[1].track_gain.unwrap() - 11.03).abs() < 0.05);
+        assert_eq!(gains[0].album_gain, gains[1].album_gain);
+        // the quiet track is gated from the album loudness
+        assert!((gains[0].album_gain.unwrap() + 8.97).abs() < 0.05);
+        assert_eq!(gains[1].album_peak, gains[0].track_peak);
+        assert_eq!(gains[0].reference_loudness, Some(-18.0));
+
+        assert!(set_album_gain(&mut [Tag::new()], &[]).is_err());
+    }
+}
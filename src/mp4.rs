@@ -0,0 +1,462 @@
+use crate::block::{Block, BlockType};
+use crate::error::{Error, ErrorKind, Result};
+use crate::tag::Tag;
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Boxes which only contain other boxes on the way to the `dfLa` box.
+const CONTAINERS: &[&[u8; 4]] = &[b"moov", b"trak", b"mdia", b"minf", b"stbl"];
+
+/// Length of the fields of the `stsd` box before its sample entries.
+const STSD_HEADER_LENGTH: usize = 8;
+/// Length of the fields of an audio sample entry before its child boxes.
+const AUDIO_SAMPLE_ENTRY_LENGTH: usize = 28;
+/// Length of the version and flags of a full box.
+const FULL_BOX_HEADER_LENGTH: usize = 4;
+
+// Boxes {{{
+/// A box in a sequence of boxes.
+struct Mp4Box<'a> {
+    kind: [u8; 4],
+    /// Whether the size of the box is stored in a 64-bit field.
+    large: bool,
+    payload: &'a [u8],
+}
+
+impl<'a> Mp4Box<'a> {
+    /// Attempts to parse a sequence of boxes.
+    fn parse_all(mut bytes: &'a [u8]) -> Result<Vec<Mp4Box<'a>>> {
+        let mut boxes = Vec::new();
+        while !bytes.is_empty() {
+            let (header, size) = read_header(&mut bytes)?;
+            let length = match size {
+                Some(size) => size.checked_sub(header.length as u64),
+                None => Some(bytes.len() as u64),
+            };
+            let length = match length {
+                Some(length) if length <= bytes.len() as u64 => length as usize,
+                _ => return Err(Error::new(ErrorKind::InvalidInput, "MP4 box is truncated")),
+            };
+
+            boxes.push(Mp4Box {
+                kind: header.kind,
+                large: header.length == 16,
+                payload: &bytes[..length],
+            });
+            bytes = &bytes[length..];
+        }
+        Ok(boxes)
+    }
+
+    /// Returns the bytes of a box with the payload.
+    fn to_bytes(kind: &[u8; 4], large: bool, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 + payload.len());
+        let size = payload.len() as u64 + 8;
+        if large || size > u32::MAX as u64 {
+            bytes.extend(&1u32.to_be_bytes());
+            bytes.extend(kind);
+            bytes.extend(&(size + 8).to_be_bytes());
+        } else {
+            bytes.extend(&(size as u32).to_be_bytes());
+            bytes.extend(kind);
+        }
+        bytes.extend(payload);
+        bytes
+    }
+}
+
+/// The header of a box.
+struct BoxHeader {
+    kind: [u8; 4],
+    /// The length of the header in bytes.
+    length: usize,
+}
+
+/// Attempts to read a box header. Returns the header and the size of the box, which is `None`
+/// if the box extends to the end of the file.
+fn read_header(reader: &mut dyn Read) -> Result<(BoxHeader, Option<u64>)> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    let mut kind = [0; 4];
+    kind.copy_from_slice(&bytes[4..]);
+
+    match u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) {
+        0 => Ok((BoxHeader { kind, length: 8 }, None)),
+        1 => {
+            let mut size = [0; 8];
+            reader.read_exact(&mut size)?;
+            Ok((
+                BoxHeader { kind, length: 16 },
+                Some(u64::from_be_bytes(size)),
+            ))
+        }
+        size => Ok((BoxHeader { kind, length: 8 }, Some(size as u64))),
+    }
+}
+//}}}
+
+// Rewriting {{{
+/// Rebuilds the boxes of a `moov` box, replacing the contents of the first `dfLa` box and
+/// moving the chunk offsets which point after the `moov` box.
+struct Rewriter<'a> {
+    /// The new payload of the `dfLa` box, or `None` to find the current payload.
+    dfla: Option<&'a [u8]>,
+    /// The payload of the first `dfLa` box found.
+    found: Option<Vec<u8>>,
+    /// Chunk offsets at or after this offset are moved.
+    moved_from: u64,
+    /// The change of the chunk offsets which are moved.
+    delta: i64,
+}
+
+impl<'a> Rewriter<'a> {
+    fn rewrite_all(&mut self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(bytes.len());
+        for child in Mp4Box::parse_all(bytes)? {
+            let payload = self.rewrite(&child)?;
+            output.extend(Mp4Box::to_bytes(&child.kind, child.large, &payload[..]));
+        }
+        Ok(output)
+    }
+
+    fn rewrite(&mut self, child: &Mp4Box) -> Result<Vec<u8>> {
+        let payload = child.payload;
+        match &child.kind {
+            kind if CONTAINERS.contains(&kind) => self.rewrite_all(payload),
+            b"stsd" => self.rewrite_with_prefix(payload, STSD_HEADER_LENGTH),
+            b"fLaC" => self.rewrite_with_prefix(payload, AUDIO_SAMPLE_ENTRY_LENGTH),
+            b"dfLa" if self.found.is_none() => {
+                self.found = Some(payload.to_vec());
+                Ok(self.dfla.unwrap_or(payload).to_vec())
+            }
+            b"stco" => self.move_offsets(payload, 4),
+            b"co64" => self.move_offsets(payload, 8),
+            _ => Ok(payload.to_vec()),
+        }
+    }
+
+    fn rewrite_with_prefix(&mut self, payload: &[u8], length: usize) -> Result<Vec<u8>> {
+        if payload.len() < length {
+            return Err(Error::new(ErrorKind::InvalidInput, "MP4 box is truncated"));
+        }
+        let mut output = payload[..length].to_vec();
+        output.extend(self.rewrite_all(&payload[length..])?);
+        Ok(output)
+    }
+
+    /// Moves the offsets of a chunk offset box with offsets of `width` bytes.
+    fn move_offsets(&self, payload: &[u8], width: usize) -> Result<Vec<u8>> {
+        let mut output = payload.to_vec();
+        if self.delta == 0 {
+            return Ok(output);
+        }
+
+        let start = FULL_BOX_HEADER_LENGTH + 4;
+        let count = match payload.get(FULL_BOX_HEADER_LENGTH..start) {
+            Some(count) => u32::from_be_bytes([count[0], count[1], count[2], count[3]]) as usize,
+            None => return Err(Error::new(ErrorKind::InvalidInput, "MP4 box is truncated")),
+        };
+        if payload.len() < start + count * width {
+            return Err(Error::new(ErrorKind::InvalidInput, "MP4 box is truncated"));
+        }
+
+        for entry in output[start..start + count * width].chunks_mut(width) {
+            let offset = entry.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+            if offset < self.moved_from {
+                continue;
+            }
+            let moved = offset as i64 + self.delta;
+            if moved < 0 || (width == 4 && moved > u32::MAX as i64) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "chunk offset does not fit in the stco box",
+                ));
+            }
+            entry.copy_from_slice(&(moved as u64).to_be_bytes()[8 - width..]);
+        }
+        Ok(output)
+    }
+}
+
+/// Returns the payload of a `dfLa` box holding the blocks of the tag. The streaminfo block is
+/// moved to the front.
+fn dfla_payload(tag: &Tag) -> Result<Vec<u8>> {
+    let streaminfo = match tag.get_streaminfo() {
+        Some(streaminfo) => streaminfo.clone(),
+        None => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "tag does not contain a streaminfo block",
+            ));
+        }
+    };
+
+    let mut blocks = vec![Block::StreamInfo(streaminfo)];
+    blocks.extend(
+        tag.blocks()
+            .filter(|block| block.block_type() != BlockType::StreamInfo)
+            .cloned(),
+    );
+
+    let mut payload = vec![0; FULL_BOX_HEADER_LENGTH];
+    let num_blocks = blocks.len();
+    for (i, block) in blocks.iter().enumerate() {
+        block.write_to(i == num_blocks - 1, &mut payload)?;
+    }
+    Ok(payload)
+}
+
+/// Attempts to parse the blocks of the payload of a `dfLa` box.
+fn parse_dfla(payload: &[u8]) -> Result<Tag> {
+    if payload.len() < FULL_BOX_HEADER_LENGTH || payload[0] != 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "unsupported dfLa box version",
+        ));
+    }
+
+    let mut tag = Tag::new();
+    let mut reader = &payload[FULL_BOX_HEADER_LENGTH..];
+    while !reader.is_empty() {
+        let (is_last, _, block) = Block::read_from(&mut reader)?;
+        tag.push_block(block);
+        if is_last {
+            break;
+        }
+    }
+    Ok(tag)
+}
+//}}}
+
+/// Attempts to read the FLAC tag from the `dfLa` box of the first FLAC track of an MP4 file.
+/// Only the top level boxes up to the `moov` box are read.
+pub fn read_from(reader: &mut dyn Read) -> Result<Tag> {
+    loop {
+        let (header, size) = read_header(reader)?;
+        let length = match size {
+            Some(size) if size >= header.length as u64 => size - header.length as u64,
+            Some(_) => return Err(Error::new(ErrorKind::InvalidInput, "MP4 box is truncated")),
+            None if &header.kind == b"moov" => u64::MAX,
+            None => break,
+        };
+
+        if &header.kind != b"moov" {
+            io::copy(&mut reader.take(length), &mut io::sink())?;
+            continue;
+        }
+
+        let mut moov = Vec::new();
+        reader.take(length).read_to_end(&mut moov)?;
+        let mut rewriter = Rewriter {
+            dfla: None,
+            found: None,
+            moved_from: 0,
+            delta: 0,
+        };
+        rewriter.rewrite_all(&moov[..])?;
+        match rewriter.found {
+            Some(payload) => return parse_dfla(&payload[..]),
+            None => break,
+        }
+    }
+
+    Err(Error::new(
+        ErrorKind::InvalidInput,
+        "MP4 file does not contain a dfLa box",
+    ))
+}
+
+/// Attempts to read the FLAC tag of the MP4 file at the specified path.
+pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Tag> {
+    let file = File::open(path)?;
+    let mut reader = BufReader::new(file);
+    read_from(&mut reader)
+}
+
+/// Attempts to read an MP4 file from the reader and write it to the writer with the blocks of
+/// the tag in its `dfLa` box. The sizes of the boxes holding the `dfLa` box are updated, and the
+/// chunk offsets in the `stco` and `co64` boxes are moved if the media data follows the `moov`
+/// box.
+///
+/// # Example
+/// ```no_run
+/// use metaflac::mp4;
+///
+/// let mut tag = mp4::read_from_path("music.mp4").unwrap();
+/// tag.set_vorbis("TITLE", vec!["Title"]);
+///
+/// let mut output = Vec::new();
+/// mp4::write_to(&tag, &mut std::fs::File::open("music.mp4").unwrap(), &mut output).unwrap();
+/// ```
+pub fn write_to(tag: &Tag, reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
+    let dfla = dfla_payload(tag)?;
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    let mut offset = 0;
+    for child in Mp4Box::parse_all(&bytes[..])? {
+        let header_length = if child.large { 16 } else { 8 };
+        let end = offset + header_length + child.payload.len();
+        if &child.kind != b"moov" {
+            offset = end;
+            continue;
+        }
+
+        // the size of the moov box does not depend on the chunk offsets, so it is rebuilt once
+        // to find the change of the offsets and again to move them
+        let mut rewriter = Rewriter {
+            dfla: Some(&dfla[..]),
+            found: None,
+            moved_from: end as u64,
+            delta: 0,
+        };
+        let payload = rewriter.rewrite_all(child.payload)?;
+        if rewriter.found.is_none() {
+            break;
+        }
+        rewriter.delta = payload.len() as i64 - child.payload.len() as i64;
+        rewriter.found = None;
+        let payload = rewriter.rewrite_all(child.payload)?;
+
+        writer.write_all(&bytes[..offset])?;
+        writer.write_all(&Mp4Box::to_bytes(&child.kind, child.large, &payload[..])[..])?;
+        writer.write_all(&bytes[end..])?;
+        return Ok(());
+    }
+
+    Err(Error::new(
+        ErrorKind::InvalidInput,
+        "MP4 file does not contain a dfLa box",
+    ))
+}
+
+/// Attempts to write the tag to the MP4 file at the specified path, replacing the blocks of its
+/// `dfLa` box.
+pub fn write_to_path<P: AsRef<Path>>(tag: &Tag, path: P) -> Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    let mut output = Vec::with_capacity(bytes.len());
+    write_to(tag, &mut &bytes[..], &mut output)?;
+
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&output[..])?;
+    file.set_len(output.len() as u64)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::StreamInfo;
+
+    const AUDIO: &[u8] = b"audio chunk";
+
+    fn boxed(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        Mp4Box::to_bytes(kind, false, payload)
+    }
+
+    fn tag() -> Tag {
+        let mut streaminfo = StreamInfo::new();
+        streaminfo.sample_rate = 44100;
+        streaminfo.num_channels = 2;
+        streaminfo.bits_per_sample = 16;
+        streaminfo.md5 = vec![0; 16];
+        let mut tag = Tag::new();
+        tag.set_streaminfo(streaminfo);
+        tag.set_vorbis("TITLE", vec!["MP4"]);
+        tag
+    }
+
+    /// Returns an MP4 file with a FLAC track with one chunk, with the `moov` box before or
+    /// after the `mdat` box.
+    fn mp4(tag: &Tag, moov_first: bool, large_offsets: bool) -> Vec<u8> {
+        let ftyp = boxed(b"ftyp", b"isom\x00\x00\x02\x00");
+        let mdat = boxed(b"mdat", AUDIO);
+
+        let moov = |chunk_offset: u64| {
+            let mut sample_entry = vec![0; AUDIO_SAMPLE_ENTRY_LENGTH];
+            sample_entry.extend(boxed(b"dfLa", &dfla_payload(tag).unwrap()[..]));
+            let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+            stsd.extend(boxed(b"fLaC", &sample_entry[..]));
+
+            let mut stbl = boxed(b"stsd", &stsd[..]);
+            let mut offsets = vec![0, 0, 0, 0, 0, 0, 0, 1];
+            if large_offsets {
+                offsets.extend(&chunk_offset.to_be_bytes());
+                stbl.extend(boxed(b"co64", &offsets[..]));
+            } else {
+                offsets.extend(&(chunk_offset as u32).to_be_bytes());
+                stbl.extend(boxed(b"stco", &offsets[..]));
+            }
+
+            let minf = boxed(b"minf", &boxed(b"stbl", &stbl[..])[..]);
+            let trak = boxed(b"trak", &boxed(b"mdia", &minf[..])[..]);
+            boxed(b"moov", &trak[..])
+        };
+
+        let mut file = ftyp.clone();
+        if moov_first {
+            let length = moov(0).len();
+            file.extend(moov((ftyp.len() + length + 8) as u64));
+            file.extend(mdat);
+        } else {
+            file.extend(mdat);
+            file.extend(moov(ftyp.len() as u64 + 8));
+        }
+        file
+    }
+
+    /// Returns the bytes of the chunk at the offset of the first `stco` or `co64` box.
+    fn chunk(file: &[u8]) -> &[u8] {
+        let (position, width) = match file.windows(4).position(|w| w == b"stco") {
+            Some(position) => (position, 4),
+            None => (file.windows(4).position(|w| w == b"co64").unwrap(), 8),
+        };
+        let start = position + 4 + 8;
+        let offset = file[start..start + width]
+            .iter()
+            .fold(0, |acc, &b| (acc << 8) | b as usize);
+        &file[offset..offset + AUDIO.len()]
+    }
+
+    #[test]
+    fn read_dfla() {
+        let file = mp4(&tag(), true, false);
+        let tag = read_from(&mut &file[..]).unwrap();
+        assert_eq!(tag.get_streaminfo().unwrap().sample_rate, 44100);
+        assert_eq!(tag.get_vorbis("TITLE").unwrap().next(), Some("MP4"));
+        assert_eq!(chunk(&file[..]), AUDIO);
+    }
+
+    #[test]
+    fn rewrite_moves_chunk_offsets() {
+        for &(moov_first, large_offsets) in &[(true, false), (true, true), (false, false)] {
+            let file = mp4(&tag(), moov_first, large_offsets);
+            let mut tag = read_from(&mut &file[..]).unwrap();
+            tag.set_vorbis("ARTIST", vec!["Someone with a long name"]);
+
+            let mut output = Vec::new();
+            write_to(&tag, &mut &file[..], &mut output).unwrap();
+            assert!(output.len() > file.len());
+            assert_eq!(chunk(&output[..]), AUDIO);
+            assert_eq!(output, mp4(&tag, moov_first, large_offsets));
+
+            let read = read_from(&mut &output[..]).unwrap();
+            assert_eq!(
+                read.get_vorbis("ARTIST").unwrap().next(),
+                Some("Someone with a long name")
+            );
+        }
+    }
+
+    #[test]
+    fn missing_dfla() {
+        let file = boxed(b"moov", &boxed(b"trak", &[])[..]);
+        assert!(read_from(&mut &file[..]).is_err());
+        assert!(write_to(&tag(), &mut &file[..], &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,818 @@
+use crate::block::{Block, BlockType, VorbisComment};
+use crate::crc::crc32;
+use crate::error::{Error, ErrorKind, Result};
+use crate::tag::Tag;
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Page flag set on pages which begin with the continuation of a packet.
+const CONTINUED: u8 = 0x01;
+/// Page flag set on the first page of a logical stream.
+const BEGINNING_OF_STREAM: u8 = 0x02;
+
+/// Granule position of pages on which no packet ends.
+const NO_GRANULE: u64 = u64::MAX;
+
+/// The header of the first packet of an Ogg FLAC stream, followed by the mapping version 1.0.
+const FLAC_MAPPING: &[u8] = b"\x7FFLAC\x01\x00";
+
+// Page {{{
+/// A page of an Ogg stream.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Page {
+    pub(crate) header_type: u8,
+    pub(crate) granule: u64,
+    pub(crate) serial: u32,
+    pub(crate) sequence: u32,
+    /// The lacing values of the segments of the page.
+    pub(crate) segments: Vec<u8>,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Page {
+    /// Attempts to read a page from the reader. Returns `None` at the end of the reader.
+    pub(crate) fn read_from(reader: &mut dyn Read) -> Result<Option<Page>> {
+        let mut header = [0; 27];
+        let mut read = 0;
+        while read < header.len() {
+            match reader.read(&mut header[read..])? {
+                0 if read == 0 => return Ok(None),
+                0 => return Err(Error::new(ErrorKind::InvalidInput, "Ogg page is truncated")),
+                n => read += n,
+            }
+        }
+
+        if &header[..4] != b"OggS" || header[4] != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "reader does not contain an Ogg page",
+            ));
+        }
+
+        let mut segments = vec![0; header[26] as usize];
+        reader.read_exact(&mut segments)?;
+        let length = segments.iter().map(|&s| s as usize).sum();
+        let mut data = vec![0; length];
+        reader.read_exact(&mut data)?;
+
+        let read_u32 =
+            |i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+        let mut granule = [0; 8];
+        granule.copy_from_slice(&header[6..14]);
+        let page = Page {
+            header_type: header[5],
+            granule: u64::from_le_bytes(granule),
+            serial: read_u32(14),
+            sequence: read_u32(18),
+            segments,
+            data,
+        };
+
+        if page.to_bytes()[22..26] != header[22..26] {
+            return Err(Error::new(ErrorKind::InvalidInput, "Ogg page CRC mismatch"));
+        }
+
+        Ok(Some(page))
+    }
+
+    /// Returns the bytes of the page, including its CRC.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(27 + self.segments.len() + self.data.len());
+        bytes.extend(b"OggS\x00");
+        bytes.push(self.header_type);
+        bytes.extend(&self.granule.to_le_bytes());
+        bytes.extend(&self.serial.to_le_bytes());
+        bytes.extend(&self.sequence.to_le_bytes());
+        bytes.extend(&[0; 4]);
+        bytes.push(self.segments.len() as u8);
+        bytes.extend(&self.segments[..]);
+        bytes.extend(&self.data[..]);
+
+        let crc = crc32(&bytes[..]);
+        bytes[22..26].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+}
+
+/// Returns the pages holding a packet, which begins on a new page. Packets which need more than
+/// 255 segments are continued on the following pages.
+fn paginate(packet: &[u8], serial: u32, header_type: u8, granule: u64) -> Vec<Page> {
+    let mut segments = vec![255; packet.len() / 255];
+    segments.push((packet.len() % 255) as u8);
+
+    let mut pages = Vec::new();
+    let mut offset = 0;
+    for (i, chunk) in segments.chunks(255).enumerate() {
+        let length: usize = chunk.iter().map(|&s| s as usize).sum();
+        pages.push(Page {
+            header_type: if i == 0 { header_type } else { CONTINUED },
+            granule: NO_GRANULE,
+            serial,
+            sequence: 0,
+            segments: chunk.to_vec(),
+            data: packet[offset..offset + length].to_vec(),
+        });
+        offset += length;
+    }
+    // the packet ends on the last page
+    pages.last_mut().unwrap().granule = granule;
+    pages
+}
+//}}}
+
+// Header packets {{{
+/// The header packets at the start of a logical stream and the position where they end.
+pub(crate) struct Headers {
+    pub(crate) serial: u32,
+    pub(crate) packets: Vec<Vec<u8>>,
+    /// The index of the page on which the last header packet ends.
+    page: usize,
+    /// The index of the first segment of that page after the last header packet.
+    segment: usize,
+}
+
+impl Headers {
+    /// Attempts to read the header packets of the first logical stream of the pages. `done` is
+    /// called after each packet and returns true once all header packets have been read.
+    pub(crate) fn read(
+        pages: &mut dyn Iterator<Item = Result<Page>>,
+        done: &mut dyn FnMut(&[Vec<u8>]) -> Result<bool>,
+    ) -> Result<Headers> {
+        let mut serial = None;
+        let mut packets = Vec::new();
+        let mut packet = Vec::new();
+
+        for (index, page) in pages.enumerate() {
+            let page = page?;
+            match serial {
+                None if page.header_type & BEGINNING_OF_STREAM == 0 => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "Ogg stream does not begin with a first page",
+                    ));
+                }
+                None => serial = Some(page.serial),
+                Some(serial) if serial != page.serial => continue,
+                Some(_) => {}
+            }
+
+            let mut offset = 0;
+            for (i, &segment) in page.segments.iter().enumerate() {
+                packet.extend(&page.data[offset..offset + segment as usize]);
+                offset += segment as usize;
+                if segment < 255 {
+                    packets.push(std::mem::take(&mut packet));
+                    if done(&packets[..])? {
+                        return Ok(Headers {
+                            serial: serial.unwrap(),
+                            packets,
+                            page: index,
+                            segment: i + 1,
+                        });
+                    }
+                }
+            }
+        }
+
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Ogg stream ends before the end of the header packets",
+        ))
+    }
+
+    /// Attempts to write the pages to the writer, replacing the header packets by the new
+    /// packets. The following pages of the logical stream are renumbered, and pages of other
+    /// logical streams are copied unchanged. The contents of the pages following the header
+    /// packets are not changed.
+    pub(crate) fn write_pages(
+        &self,
+        pages: &[Page],
+        packets: &[Vec<u8>],
+        writer: &mut dyn Write,
+    ) -> Result<()> {
+        let first = match pages.iter().position(|page| page.serial == self.serial) {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+        let mut sequence = pages[first].sequence;
+
+        let mut header_pages = Vec::new();
+        for (i, packet) in packets.iter().enumerate() {
+            let header_type = if i == 0 { BEGINNING_OF_STREAM } else { 0 };
+            header_pages.extend(paginate(packet, self.serial, header_type, 0));
+        }
+
+        // packets which follow the header packets on the same page are moved to a new page
+        let last = &pages[self.page];
+        if self.segment < last.segments.len() {
+            let offset: usize = last.segments[..self.segment]
+                .iter()
+                .map(|&s| s as usize)
+                .sum();
+            header_pages.push(Page {
+                header_type: last.header_type & !(CONTINUED | BEGINNING_OF_STREAM),
+                granule: last.granule,
+                serial: self.serial,
+                sequence: 0,
+                segments: last.segments[self.segment..].to_vec(),
+                data: last.data[offset..].to_vec(),
+            });
+        }
+
+        for (i, page) in pages.iter().enumerate() {
+            if page.serial != self.serial {
+                writer.write_all(&page.to_bytes()[..])?;
+            } else if i == first {
+                for page in header_pages.iter_mut() {
+                    page.sequence = sequence;
+                    sequence = sequence.wrapping_add(1);
+                    writer.write_all(&page.to_bytes()[..])?;
+                }
+            } else if i > self.page {
+                let mut page = page.clone();
+                page.sequence = sequence;
+                sequence = sequence.wrapping_add(1);
+                writer.write_all(&page.to_bytes()[..])?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns an iterator over the pages of the reader.
+pub(crate) fn pages(reader: &mut dyn Read) -> impl Iterator<Item = Result<Page>> + '_ {
+    let mut finished = false;
+    std::iter::from_fn(move || {
+        if finished {
+            return None;
+        }
+        match Page::read_from(reader) {
+            Ok(Some(page)) => Some(Ok(page)),
+            Ok(None) => {
+                finished = true;
+                None
+            }
+            Err(err) => {
+                finished = true;
+                Some(Err(err))
+            }
+        }
+    })
+}
+//}}}
+
+// FLAC mapping {{{
+/// Returns true once the header packets of an Ogg FLAC stream have been read.
+fn flac_headers_done(packets: &[Vec<u8>]) -> Result<bool> {
+    let packet = &packets[packets.len() - 1];
+    let header = if packets.len() == 1 {
+        if packet.len() < 51
+            || packet[..5] != FLAC_MAPPING[..5]
+            || packet[5] != 1
+            || &packet[9..13] != b"fLaC"
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Ogg stream does not contain FLAC audio",
+            ));
+        }
+        packet[13]
+    } else {
+        match packet.first() {
+            Some(&header) => header,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Ogg FLAC header packet is empty",
+                ));
+            }
+        }
+    };
+
+    Ok(header & 0x80 != 0)
+}
+
+/// Attempts to read the FLAC tag of an Ogg FLAC stream from the reader. Only the pages holding
+/// the metadata are read.
+pub fn read_from(reader: &mut dyn Read) -> Result<Tag> {
+    let headers = Headers::read(&mut pages(reader), &mut flac_headers_done)?;
+
+    let mut tag = Tag::new();
+    for (i, packet) in headers.packets.iter().enumerate() {
+        let mut bytes = if i == 0 { &packet[13..] } else { &packet[..] };
+        let (_, _, block) = Block::read_from(&mut bytes)?;
+        tag.push_block(block);
+    }
+
+    Ok(tag)
+}
+
+/// Attempts to read the FLAC tag of the Ogg FLAC file at the specified path.
+pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Tag> {
+    let file = File::open(path)?;
+    let mut reader = BufReader::new(file);
+    read_from(&mut reader)
+}
+
+/// Returns the header packets of an Ogg FLAC stream holding the blocks of the tag. The vorbis
+/// comment block is moved after the streaminfo block, as required by the mapping, and an empty
+/// one is added if the tag does not contain one.
+fn flac_packets(tag: &Tag) -> Result<Vec<Vec<u8>>> {
+    let streaminfo = match tag.get_streaminfo() {
+        Some(streaminfo) => streaminfo.clone(),
+        None => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "tag does not contain a streaminfo block",
+            ));
+        }
+    };
+
+    let mut blocks = Vec::new();
+    match tag.vorbis_comments() {
+        Some(vorbis) => blocks.push(Block::VorbisComment(vorbis.clone())),
+        None => blocks.push(Block::VorbisComment(VorbisComment::new())),
+    }
+    let mut skipped_vorbis = false;
+    for block in tag.blocks() {
+        match block.block_type() {
+            BlockType::StreamInfo => {}
+            BlockType::VorbisComment if !skipped_vorbis => skipped_vorbis = true,
+            _ => blocks.push(block.clone()),
+        }
+    }
+
+    let mut mapping = FLAC_MAPPING.to_vec();
+    mapping.extend(&(blocks.len() as u16).to_be_bytes());
+    mapping.extend(b"fLaC");
+    Block::StreamInfo(streaminfo).write_to(false, &mut mapping)?;
+
+    let mut packets = vec![mapping];
+    let num_blocks = blocks.len();
+    for (i, block) in blocks.iter().enumerate() {
+        let mut packet = Vec::new();
+        block.write_to(i == num_blocks - 1, &mut packet)?;
+        packets.push(packet);
+    }
+
+    Ok(packets)
+}
+
+/// Attempts to read an Ogg FLAC stream from the reader and write it to the writer with the
+/// metadata of the tag. The header pages are rewritten, and the pages holding the audio are only
+/// renumbered if the number of header pages changes.
+///
+/// # Example
+/// ```no_run
+/// use metaflac::ogg;
+///
+/// let mut tag = ogg::read_from_path("music.oga").unwrap();
+/// tag.set_vorbis("TITLE", vec!["Title"]);
+///
+/// let mut output = Vec::new();
+/// ogg::write_to(&tag, &mut std::fs::File::open("music.oga").unwrap(), &mut output).unwrap();
+/// ```
+pub fn write_to(tag: &Tag, reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
+    let packets = flac_packets(tag)?;
+    let pages = pages(reader).collect::<Result<Vec<Page>>>()?;
+    let headers = Headers::read(&mut pages.iter().cloned().map(Ok), &mut flac_headers_done)?;
+    headers.write_pages(&pages[..], &packets[..], writer)
+}
+
+/// Attempts to write the tag to the Ogg FLAC file at the specified path, replacing its metadata.
+pub fn write_to_path<P: AsRef<Path>>(tag: &Tag, path: P) -> Result<()> {
+    rewrite_file(path.as_ref(), &mut |reader, writer| {
+        write_to(tag, reader, writer)
+    })
+}
+
+/// Will return true if the reader is a candidate for Ogg FLAC metadata. The reader position will
+/// be reset back to the previous position before returning.
+pub fn is_candidate<R: Read + Seek>(reader: &mut R) -> bool {
+    let mut header = [0; 33];
+    let result = reader.read_exact(&mut header);
+    let _ = reader.seek(SeekFrom::Current(-(header.len() as i64)));
+    result.is_ok() && &header[..4] == b"OggS" && header[28..33] == FLAC_MAPPING[..5]
+}
+//}}}
+
+// Vorbis and Opus {{{
+/// The codec of an Ogg stream which stores its metadata in a comment header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CommentCodec {
+    Vorbis,
+    Opus,
+}
+
+impl CommentCodec {
+    /// Attempts to detect the codec from the first packet of a stream.
+    fn detect(packet: &[u8]) -> Result<CommentCodec> {
+        if packet.starts_with(b"\x01vorbis") {
+            Ok(CommentCodec::Vorbis)
+        } else if packet.starts_with(b"OpusHead") {
+            Ok(CommentCodec::Opus)
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Ogg stream does not contain Vorbis or Opus audio",
+            ))
+        }
+    }
+
+    /// Returns the number of header packets of the codec.
+    fn num_headers(self) -> usize {
+        match self {
+            CommentCodec::Vorbis => 3,
+            CommentCodec::Opus => 2,
+        }
+    }
+
+    /// Returns the bytes preceding the comments in the comment header packet.
+    fn comment_magic(self) -> &'static [u8] {
+        match self {
+            CommentCodec::Vorbis => b"\x03vorbis",
+            CommentCodec::Opus => b"OpusTags",
+        }
+    }
+}
+
+/// Returns the length of the comments at the start of the bytes, or `None` if they are
+/// truncated.
+fn comments_length(bytes: &[u8]) -> Option<usize> {
+    let read_u32 = |i: usize| -> Option<usize> {
+        let field = bytes.get(i..i + 4)?;
+        Some(u32::from_le_bytes([field[0], field[1], field[2], field[3]]) as usize)
+    };
+
+    let mut i = 4 + read_u32(0)?;
+    let num_comments = read_u32(i)?;
+    i += 4;
+    for _ in 0..num_comments {
+        i += 4 + read_u32(i)?;
+    }
+
+    if i <= bytes.len() {
+        Some(i)
+    } else {
+        None
+    }
+}
+
+/// The header packets of an Ogg Vorbis or Opus stream.
+struct CommentHeaders {
+    codec: CommentCodec,
+    headers: Headers,
+    /// The length of the comments in the comment header packet.
+    comments_length: usize,
+}
+
+impl CommentHeaders {
+    fn read(pages: &mut dyn Iterator<Item = Result<Page>>) -> Result<CommentHeaders> {
+        let mut codec = None;
+        let headers = Headers::read(pages, &mut |packets| {
+            let codec = match codec {
+                Some(codec) => codec,
+                None => *codec.get_or_insert(CommentCodec::detect(&packets[0][..])?),
+            };
+            Ok(packets.len() == codec.num_headers())
+        })?;
+        let codec = codec.unwrap();
+
+        let packet = &headers.packets[1];
+        let magic = codec.comment_magic();
+        let comments_length = match packet.strip_prefix(magic).and_then(comments_length) {
+            Some(length) => length,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Ogg stream contains an invalid comment header",
+                ));
+            }
+        };
+
+        Ok(CommentHeaders {
+            codec,
+            headers,
+            comments_length,
+        })
+    }
+
+    fn comments(&self) -> Result<VorbisComment> {
+        let start = self.codec.comment_magic().len();
+        VorbisComment::from_bytes(&self.headers.packets[1][start..start + self.comments_length])
+    }
+
+    /// Returns the header packets with the comments replaced. The framing bit of a Vorbis
+    /// comment header and the binary data following the comments of an Opus comment header are
+    /// kept.
+    fn packets(&self, comments: &VorbisComment) -> Result<Vec<Vec<u8>>> {
+        let old = &self.headers.packets[1];
+        let end = self.codec.comment_magic().len() + self.comments_length;
+
+        let mut packet = self.codec.comment_magic().to_vec();
+        packet.extend(comments.to_bytes()?);
+        match self.codec {
+            CommentCodec::Vorbis => packet.push(1),
+            CommentCodec::Opus => packet.extend(&old[end..]),
+        }
+
+        let mut packets = self.headers.packets.clone();
+        packets[1] = packet;
+        Ok(packets)
+    }
+}
+
+/// Attempts to read the comments of an Ogg Vorbis or Opus stream from the reader. Only the pages
+/// holding the header packets are read.
+///
+/// # Example
+/// ```no_run
+/// use metaflac::ogg;
+///
+/// let comments = ogg::read_comments_from_path("music.opus").unwrap();
+/// println!("{:?}", comments.title());
+/// ```
+pub fn read_comments_from(reader: &mut dyn Read) -> Result<VorbisComment> {
+    CommentHeaders::read(&mut pages(reader))?.comments()
+}
+
+/// Attempts to read the comments of the Ogg Vorbis or Opus file at the specified path.
+pub fn read_comments_from_path<P: AsRef<Path>>(path: P) -> Result<VorbisComment> {
+    let file = File::open(path)?;
+    let mut reader = BufReader::new(file);
+    read_comments_from(&mut reader)
+}
+
+/// Attempts to read an Ogg Vorbis or Opus stream from the reader and write it to the writer with
+/// the comments. The other header packets and the audio packets are not changed.
+pub fn write_comments_to(
+    comments: &VorbisComment,
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+) -> Result<()> {
+    let pages = pages(reader).collect::<Result<Vec<Page>>>()?;
+    let headers = CommentHeaders::read(&mut pages.iter().cloned().map(Ok))?;
+    let packets = headers.packets(comments)?;
+    headers
+        .headers
+        .write_pages(&pages[..], &packets[..], writer)
+}
+
+/// Attempts to write the comments to the Ogg Vorbis or Opus file at the specified path.
+pub fn write_comments_to_path<P: AsRef<Path>>(comments: &VorbisComment, path: P) -> Result<()> {
+    rewrite_file(path.as_ref(), &mut |reader, writer| {
+        write_comments_to(comments, reader, writer)
+    })
+}
+//}}}
+
+/// Attempts to rewrite the file at the path with the function, which reads the current contents
+/// of the file and writes the new contents.
+fn rewrite_file(
+    path: &Path,
+    rewrite: &mut dyn FnMut(&mut dyn Read, &mut dyn Write) -> Result<()>,
+) -> Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    let mut output = Vec::with_capacity(bytes.len());
+    rewrite(&mut &bytes[..], &mut output)?;
+
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&output[..])?;
+    file.set_len(output.len() as u64)?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::block::PictureType;
+    use crate::encoder::{self, EncoderConfig};
+    use crate::frame::Frames;
+
+    use std::io::Cursor;
+
+    /// Returns an Ogg FLAC stream holding the native FLAC stream, with each frame in its own
+    /// page.
+    pub(crate) fn ogg_flac(flac: &[u8], serial: u32) -> Vec<u8> {
+        let mut reader = flac;
+        let tag = Tag::read_from(&mut reader).unwrap();
+
+        let mut pages = Vec::new();
+        for (i, packet) in flac_packets(&tag).unwrap().iter().enumerate() {
+            let header_type = if i == 0 { BEGINNING_OF_STREAM } else { 0 };
+            pages.extend(paginate(packet, serial, header_type, 0));
+        }
+
+        let mut samples = 0;
+        for frame in Frames::new(reader) {
+            samples += frame.header.block_size as u64;
+            let start = frame.offset as usize;
+            let end = start + frame.length as usize;
+            pages.extend(paginate(&reader[start..end], serial, 0, samples));
+        }
+        pages.last_mut().unwrap().header_type |= 0x04;
+
+        let mut bytes = Vec::new();
+        for (sequence, page) in pages.iter_mut().enumerate() {
+            page.sequence = sequence as u32;
+            bytes.extend(page.to_bytes());
+        }
+        bytes
+    }
+
+    fn flac_stream(tag: &Tag) -> Vec<u8> {
+        let samples: Vec<i32> = (0..44100).map(|i| (i * 37 % 2000) - 1000).collect();
+        let mut flac = Vec::new();
+        encoder::encode(&EncoderConfig::new(44100, 1, 16), &samples, tag, &mut flac).unwrap();
+        flac
+    }
+
+    fn audio_pages(bytes: &[u8]) -> Vec<(u64, Vec<u8>)> {
+        pages(&mut &bytes[..])
+            .map(|page| page.unwrap())
+            .filter(|page| page.granule != 0 && page.granule != NO_GRANULE)
+            .map(|page| (page.granule, page.data))
+            .collect()
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0x89A1_897F);
+    }
+
+    #[test]
+    fn read_and_write_tag() {
+        let mut tag = Tag::new();
+        tag.set_vorbis("TITLE", vec!["Ogg"]);
+        let ogg = ogg_flac(&flac_stream(&tag)[..], 1234);
+
+        assert!(is_candidate(&mut Cursor::new(&ogg[..])));
+        let mut tag = read_from(&mut &ogg[..]).unwrap();
+        assert_eq!(tag.get_vorbis("TITLE").unwrap().next(), Some("Ogg"));
+        assert_eq!(tag.get_streaminfo().unwrap().total_samples, 44100);
+
+        // a large picture spans several pages
+        tag.set_vorbis("TITLE", vec!["Ogg FLAC"]);
+        tag.add_picture("image/png", PictureType::CoverFront, vec![0xAB; 100_000]);
+        let mut output = Vec::new();
+        write_to(&tag, &mut &ogg[..], &mut output).unwrap();
+
+        let read = read_from(&mut &output[..]).unwrap();
+        assert_eq!(read.get_vorbis("TITLE").unwrap().next(), Some("Ogg FLAC"));
+        assert_eq!(read.pictures().next().unwrap().data.len(), 100_000);
+        assert_eq!(audio_pages(&output[..]), audio_pages(&ogg[..]));
+
+        // all pages have consecutive sequence numbers and valid CRCs
+        let sequences: Vec<u32> = pages(&mut &output[..])
+            .map(|page| page.unwrap().sequence)
+            .collect();
+        assert_eq!(sequences, (0..sequences.len() as u32).collect::<Vec<_>>());
+
+        // writing the same tag again produces the same stream
+        let mut again = Vec::new();
+        write_to(&read, &mut &output[..], &mut again).unwrap();
+        assert_eq!(again, output);
+    }
+
+    #[test]
+    fn other_streams_are_unchanged() {
+        let ogg = ogg_flac(&flac_stream(&Tag::new())[..], 1);
+        let mut other = Page {
+            header_type: BEGINNING_OF_STREAM,
+            granule: 0,
+            serial: 2,
+            sequence: 7,
+            segments: vec![3],
+            data: vec![1, 2, 3],
+        };
+
+        // interleave a page of another logical stream after the first page
+        let mut input = Vec::new();
+        let mut ogg_reader = &ogg[..];
+        let mut flac_pages = pages(&mut ogg_reader).map(|page| page.unwrap());
+        input.extend(flac_pages.next().unwrap().to_bytes());
+        input.extend(other.to_bytes());
+        for page in flac_pages {
+            input.extend(page.to_bytes());
+        }
+
+        let mut tag = read_from(&mut &input[..]).unwrap();
+        tag.set_vorbis("ARTIST", vec!["Someone"]);
+        let mut output = Vec::new();
+        write_to(&tag, &mut &input[..], &mut output).unwrap();
+
+        let output_pages: Vec<Page> = pages(&mut &output[..]).map(|page| page.unwrap()).collect();
+        other.sequence = 7;
+        assert!(output_pages.contains(&other));
+        assert_eq!(
+            read_from(&mut &output[..])
+                .unwrap()
+                .get_vorbis("ARTIST")
+                .unwrap()
+                .next(),
+            Some("Someone")
+        );
+    }
+
+    #[test]
+    fn invalid_streams() {
+        let flac = flac_stream(&Tag::new());
+        assert!(read_from(&mut &flac[..]).is_err());
+
+        let mut ogg = ogg_flac(&flac[..], 1);
+        ogg[40] ^= 0xFF;
+        assert!(read_from(&mut &ogg[..]).is_err());
+    }
+
+    /// Returns a page holding the complete packets.
+    fn page(packets: &[&[u8]], header_type: u8, granule: u64, sequence: u32) -> Page {
+        let mut page = Page {
+            header_type,
+            granule,
+            serial: 5,
+            sequence,
+            segments: Vec::new(),
+            data: Vec::new(),
+        };
+        for packet in packets {
+            page.segments.extend(vec![255; packet.len() / 255]);
+            page.segments.push((packet.len() % 255) as u8);
+            page.data.extend(&packet[..]);
+        }
+        page
+    }
+
+    fn comments() -> VorbisComment {
+        let mut comments = VorbisComment::new();
+        comments.vendor_string = "vendor".to_owned();
+        comments.set_title(vec!["Title"]);
+        comments
+    }
+
+    #[test]
+    fn vorbis_comments() {
+        let mut packet = b"\x03vorbis".to_vec();
+        packet.extend(comments().to_bytes().unwrap());
+        packet.push(1);
+
+        // the comment and setup headers share a page with the first audio packet
+        let input_pages = [
+            page(&[b"\x01vorbis identification"], BEGINNING_OF_STREAM, 0, 0),
+            page(&[&packet[..], b"\x05vorbis setup", b"audio 1"], 0, 128, 1),
+            page(&[b"audio 2"], 0x04, 256, 2),
+        ];
+        let input: Vec<u8> = input_pages.iter().flat_map(Page::to_bytes).collect();
+
+        let mut comments = read_comments_from(&mut &input[..]).unwrap();
+        assert_eq!(comments, super::tests::comments());
+
+        comments.set_artist(vec!["Artist"]);
+        let mut output = Vec::new();
+        write_comments_to(&comments, &mut &input[..], &mut output).unwrap();
+        assert_eq!(read_comments_from(&mut &output[..]).unwrap(), comments);
+
+        let output: Vec<Page> = pages(&mut &output[..]).map(|page| page.unwrap()).collect();
+        assert_eq!(output.len(), 5);
+        assert_eq!(output[0], input_pages[0]);
+        assert_eq!(output[2].data, b"\x05vorbis setup");
+        assert_eq!(output[3], page(&[b"audio 1"], 0, 128, 3));
+        assert_eq!(output[4], page(&[b"audio 2"], 0x04, 256, 4));
+        assert_eq!(output[1].data.last(), Some(&1));
+    }
+
+    #[test]
+    fn opus_comments() {
+        let mut packet = b"OpusTags".to_vec();
+        packet.extend(comments().to_bytes().unwrap());
+        packet.extend(b"\x01binary");
+
+        let input_pages = [
+            page(&[b"OpusHead"], BEGINNING_OF_STREAM, 0, 0),
+            page(&[&packet[..]], 0, 0, 1),
+            page(&[b"audio"], 0x04, 960, 2),
+        ];
+        let input: Vec<u8> = input_pages.iter().flat_map(Page::to_bytes).collect();
+
+        let mut comments = read_comments_from(&mut &input[..]).unwrap();
+        assert_eq!(comments, super::tests::comments());
+
+        comments.set_title(vec!["Another title"]);
+        let mut output = Vec::new();
+        write_comments_to(&comments, &mut &input[..], &mut output).unwrap();
+        assert_eq!(read_comments_from(&mut &output[..]).unwrap(), comments);
+
+        let output: Vec<Page> = pages(&mut &output[..]).map(|page| page.unwrap()).collect();
+        assert!(output[1].data.ends_with(b"\x01binary"));
+        assert_eq!(output[2], input_pages[2]);
+
+        // FLAC streams are not read as comments
+        let ogg = ogg_flac(&flac_stream(&Tag::new())[..], 1);
+        assert!(read_comments_from(&mut &ogg[..]).is_err());
+    }
+}
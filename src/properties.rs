@@ -0,0 +1,268 @@
+use crate::block::StreamInfo;
+use crate::error::{Error, ErrorKind, Result};
+use crate::frame::Frames;
+use crate::tag::Tag;
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+/// Properties of the audio in a FLAC stream, derived from the streaminfo block and the size of
+/// the stream.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AudioProperties {
+    sample_rate: u32,
+    num_channels: u8,
+    bits_per_sample: u8,
+    total_samples: u64,
+    num_frames: u64,
+    audio_length: u64,
+    file_length: u64,
+}
+
+impl AudioProperties {
+    /// Returns the audio properties of the audio data (the contents of a FLAC stream following the
+    /// metadata, as returned by `Tag::skip_metadata`). The frames are scanned if the streaminfo
+    /// block does not declare the total number of samples or if the stream does not use a fixed
+    /// block size. The file length is assumed to be the length of the audio data.
+    pub fn from_audio(streaminfo: &StreamInfo, audio: &[u8]) -> AudioProperties {
+        let mut properties = AudioProperties {
+            sample_rate: streaminfo.sample_rate,
+            num_channels: streaminfo.num_channels,
+            bits_per_sample: streaminfo.bits_per_sample,
+            total_samples: streaminfo.total_samples,
+            num_frames: 0,
+            audio_length: audio.len() as u64,
+            file_length: audio.len() as u64,
+        };
+
+        match fixed_num_frames(streaminfo) {
+            Some(num_frames) => properties.num_frames = num_frames,
+            None => {
+                let mut total_samples = 0;
+                let mut num_frames = 0;
+                for frame in Frames::new(audio) {
+                    total_samples += frame.header.block_size as u64;
+                    num_frames += 1;
+                }
+                if properties.total_samples == 0 {
+                    properties.total_samples = total_samples;
+                }
+                properties.num_frames = num_frames;
+            }
+        }
+
+        properties
+    }
+
+    /// Attempts to read the audio properties of the FLAC stream which begins at the current
+    /// position of the reader. The audio data is only read if the frames need to be scanned, see
+    /// `AudioProperties::from_audio`.
+    pub fn read_from<R: Read + Seek>(reader: &mut R) -> Result<AudioProperties> {
+        let start = reader.stream_position()?;
+        let tag = Tag::read_from(reader)?;
+        let audio_start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+
+        let streaminfo = match tag.get_streaminfo() {
+            Some(streaminfo) => streaminfo,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "stream does not contain a streaminfo block",
+                ));
+            }
+        };
+
+        let mut properties = match fixed_num_frames(streaminfo) {
+            Some(num_frames) => AudioProperties {
+                sample_rate: streaminfo.sample_rate,
+                num_channels: streaminfo.num_channels,
+                bits_per_sample: streaminfo.bits_per_sample,
+                total_samples: streaminfo.total_samples,
+                num_frames,
+                audio_length: end - audio_start,
+                file_length: 0,
+            },
+            None => {
+                reader.seek(SeekFrom::Start(audio_start))?;
+                let mut audio = Vec::new();
+                reader.read_to_end(&mut audio)?;
+                AudioProperties::from_audio(streaminfo, &audio[..])
+            }
+        };
+        properties.file_length = end - start;
+
+        Ok(properties)
+    }
+
+    /// Attempts to read the audio properties of the FLAC file at the specified path.
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<AudioProperties> {
+        let file = File::open(path)?;
+        let mut reader = BufReader::new(file);
+        AudioProperties::read_from(&mut reader)
+    }
+
+    /// Returns the sample rate in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> u8 {
+        self.num_channels
+    }
+
+    /// Returns the number of bits per sample.
+    pub fn bits_per_sample(&self) -> u8 {
+        self.bits_per_sample
+    }
+
+    /// Returns the total number of samples (per channel) in the stream.
+    pub fn total_samples(&self) -> u64 {
+        self.total_samples
+    }
+
+    /// Returns the number of frames in the stream.
+    pub fn num_frames(&self) -> u64 {
+        self.num_frames
+    }
+
+    /// Returns the length of the audio data in bytes.
+    pub fn audio_length(&self) -> u64 {
+        self.audio_length
+    }
+
+    /// Returns the length of the whole stream, including the metadata, in bytes.
+    pub fn file_length(&self) -> u64 {
+        self.file_length
+    }
+
+    /// Returns the exact duration of the stream. Returns a zero duration if the sample rate is
+    /// unknown.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::block::StreamInfo;
+    /// use metaflac::properties::AudioProperties;
+    /// use std::time::Duration;
+    ///
+    /// let mut streaminfo = StreamInfo::new();
+    /// streaminfo.sample_rate = 44100;
+    /// streaminfo.total_samples = 44100 * 3 + 441;
+    ///
+    /// let properties = AudioProperties::from_audio(&streaminfo, &[]);
+    /// assert_eq!(properties.duration(), Duration::from_millis(3010));
+    /// ```
+    pub fn duration(&self) -> Duration {
+        if self.sample_rate == 0 {
+            return Duration::from_secs(0);
+        }
+
+        let rate = self.sample_rate as u64;
+        let nanos = (self.total_samples % rate) * 1_000_000_000 / rate;
+        Duration::new(self.total_samples / rate, nanos as u32)
+    }
+
+    /// Returns the average bitrate of the audio data in bits per second. Returns 0 if the
+    /// duration is unknown.
+    pub fn bitrate(&self) -> u64 {
+        if self.total_samples == 0 || self.sample_rate == 0 {
+            return 0;
+        }
+
+        let bits = self.audio_length as u128 * 8 * self.sample_rate as u128;
+        (bits / self.total_samples as u128) as u64
+    }
+
+    /// Returns the size of the audio data relative to the size of the uncompressed PCM audio.
+    /// Returns 0 if the size of the PCM audio is unknown.
+    pub fn compression_ratio(&self) -> f64 {
+        let pcm_bits =
+            self.total_samples as f64 * self.num_channels as f64 * self.bits_per_sample as f64;
+        if pcm_bits == 0.0 {
+            return 0.0;
+        }
+
+        self.audio_length as f64 * 8.0 / pcm_bits
+    }
+}
+
+/// Returns the number of frames in a stream with a fixed block size and a known number of total
+/// samples, without scanning the frames.
+fn fixed_num_frames(streaminfo: &StreamInfo) -> Option<u64> {
+    if streaminfo.total_samples == 0
+        || streaminfo.max_block_size == 0
+        || streaminfo.min_block_size != streaminfo.max_block_size
+    {
+        return None;
+    }
+
+    let block_size = streaminfo.max_block_size as u64;
+    Some(streaminfo.total_samples.div_ceil(block_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::tests::constant_frame;
+    use crate::frame::{ChannelAssignment, FrameHeader};
+
+    use std::io::Cursor;
+
+    #[test]
+    fn properties_from_frame_scan() {
+        let mut header = FrameHeader::new();
+        header.block_size = 4410;
+        header.channel_assignment = ChannelAssignment::Independent(2);
+
+        let mut audio = Vec::new();
+        for number in 0..25 {
+            header.number = number;
+            audio.extend(constant_frame(&header));
+        }
+
+        let mut streaminfo = StreamInfo::new();
+        streaminfo.sample_rate = 44100;
+        streaminfo.num_channels = 2;
+        streaminfo.bits_per_sample = 16;
+        streaminfo.md5 = vec![0; 16];
+
+        let properties = AudioProperties::from_audio(&streaminfo, &audio[..]);
+        assert_eq!(properties.total_samples(), 25 * 4410);
+        assert_eq!(properties.num_frames(), 25);
+        assert_eq!(properties.duration(), Duration::from_millis(2500));
+        assert_eq!(properties.bitrate(), audio.len() as u64 * 8 * 10 / 25);
+
+        let mut tag = Tag::new();
+        tag.set_streaminfo(streaminfo);
+        let mut data = Vec::new();
+        tag.write_to(&mut data).unwrap();
+        data.extend(&audio[..]);
+
+        let properties = AudioProperties::read_from(&mut Cursor::new(&data[..])).unwrap();
+        assert_eq!(properties.total_samples(), 25 * 4410);
+        assert_eq!(properties.audio_length(), audio.len() as u64);
+        assert_eq!(properties.file_length(), data.len() as u64);
+        assert!(properties.compression_ratio() < 0.01);
+    }
+
+    #[test]
+    fn properties_without_scan() {
+        let mut streaminfo = StreamInfo::new();
+        streaminfo.min_block_size = 4096;
+        streaminfo.max_block_size = 4096;
+        streaminfo.sample_rate = 48000;
+        streaminfo.num_channels = 2;
+        streaminfo.bits_per_sample = 24;
+        streaminfo.total_samples = 48000 * 60;
+
+        let audio = vec![0; 48000 * 60 * 3];
+        let properties = AudioProperties::from_audio(&streaminfo, &audio[..]);
+        assert_eq!(properties.num_frames(), 704);
+        assert_eq!(properties.duration(), Duration::from_secs(60));
+        assert_eq!(properties.bitrate(), 48000 * 3 * 8);
+        assert_eq!(properties.compression_ratio(), 0.5);
+    }
+}
@@ -0,0 +1,332 @@
+use crate::block::{Block, StreamInfo};
+use crate::error::{Error, ErrorKind, Result};
+use crate::frame::{FrameHeader, Frames};
+use crate::tag::Tag;
+
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom};
+use std::time::Duration;
+
+/// The number of bytes read at a time while searching for frames.
+const WINDOW: u64 = 64 * 1024;
+
+/// The location of a sample within a FLAC stream.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SamplePosition {
+    /// Offset (in bytes) of the header of the frame containing the sample, relative to the start
+    /// of the stream.
+    pub offset: u64,
+    /// Offset (in bytes) of the header of the frame containing the sample, relative to the first
+    /// byte of the first frame header.
+    pub audio_offset: u64,
+    /// The number of the first sample in the frame.
+    pub frame_sample: u64,
+    /// The offset of the sample within the frame.
+    pub sample_offset: u32,
+    /// The header of the frame containing the sample.
+    pub header: FrameHeader,
+}
+
+/// Attempts to locate the frame containing the specified sample in a FLAC stream. The stream is
+/// expected to begin at the current position of the reader.
+///
+/// If the stream contains a seektable then the search starts from the closest seek point at or
+/// before the sample. Otherwise, the frame is located by bisecting the audio data and examining
+/// the frame headers found. An `Error::InvalidInput` will be returned if the sample is past the
+/// end of the stream.
+pub fn find_sample<R: Read + Seek>(reader: &mut R, sample: u64) -> Result<SamplePosition> {
+    let start = reader.stream_position()?;
+    let tag = Tag::read_from(reader)?;
+    let audio_start = reader.stream_position()?;
+    let audio_length = reader.seek(SeekFrom::End(0))? - audio_start;
+
+    let streaminfo = match tag.get_streaminfo() {
+        Some(streaminfo) => streaminfo,
+        None => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "stream does not contain a streaminfo block",
+            ));
+        }
+    };
+
+    if streaminfo.total_samples != 0 && sample >= streaminfo.total_samples {
+        return Err(past_end());
+    }
+
+    let mut search = FrameSearch {
+        reader,
+        audio_start,
+        audio_length,
+        streaminfo,
+    };
+
+    let seekpoint = tag
+        .get_blocks(crate::block::BlockType::SeekTable)
+        .filter_map(|block| match *block {
+            Block::SeekTable(ref seektable) => Some(seektable),
+            _ => None,
+        })
+        .flat_map(|seektable| seektable.seekpoints.iter())
+        .filter(|point| !point.is_placeholder() && point.sample_number() <= sample)
+        .max_by_key(|point| point.sample_number());
+
+    // seek points written by buggy tools may point to the wrong frame, in which case the search
+    // falls back to bisecting
+    if let Some(point) = seekpoint {
+        if point.offset() < audio_length {
+            if let Some(mut position) = search.scan(point.offset(), sample)? {
+                position.offset += audio_start - start;
+                return Ok(position);
+            }
+        }
+    }
+
+    let offset = search.bisect(sample)?;
+    match search.scan(offset, sample)? {
+        Some(mut position) => {
+            position.offset += audio_start - start;
+            Ok(position)
+        }
+        None => Err(past_end()),
+    }
+}
+
+/// Attempts to locate the frame containing the sample at the specified time in a FLAC stream. The
+/// stream is expected to begin at the current position of the reader. See `find_sample` for
+/// details.
+pub fn find_time<R: Read + Seek>(reader: &mut R, time: Duration) -> Result<SamplePosition> {
+    let start = reader.stream_position()?;
+    let sample_rate = {
+        let tag = Tag::read_from(reader)?;
+        tag.get_streaminfo()
+            .map_or(0, |streaminfo| streaminfo.sample_rate)
+    };
+    if sample_rate == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "stream does not declare a sample rate",
+        ));
+    }
+    reader.seek(SeekFrom::Start(start))?;
+
+    // times too far in the future to be a sample number are past the end of any stream
+    let sample = time.as_nanos() * sample_rate as u128 / 1_000_000_000;
+    match u64::try_from(sample) {
+        Ok(sample) => find_sample(reader, sample),
+        Err(_) => Err(past_end()),
+    }
+}
+
+fn past_end() -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        "sample is past the end of the stream",
+    )
+}
+
+struct FrameSearch<'a, R> {
+    reader: &'a mut R,
+    audio_start: u64,
+    audio_length: u64,
+    streaminfo: &'a StreamInfo,
+}
+
+impl<R: Read + Seek> FrameSearch<'_, R> {
+    /// Reads up to `length` bytes of audio data starting at `offset`.
+    fn read(&mut self, offset: u64, length: u64) -> Result<Vec<u8>> {
+        self.reader
+            .seek(SeekFrom::Start(self.audio_start + offset))?;
+        let mut data = Vec::new();
+        (&mut *self.reader).take(length).read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn first_sample(&self, header: &FrameHeader) -> u64 {
+        let block_size = match self.streaminfo.max_block_size {
+            0 => header.block_size,
+            n => n as u32,
+        };
+        header.first_sample(block_size)
+    }
+
+    /// Returns true if the frame header agrees with the streaminfo block.
+    fn is_consistent(&self, header: &FrameHeader) -> bool {
+        let streaminfo = self.streaminfo;
+        header
+            .sample_rate
+            .map_or(true, |rate| rate == streaminfo.sample_rate)
+            && header
+                .bits_per_sample
+                .map_or(true, |bps| bps == streaminfo.bits_per_sample)
+            && header.num_channels() == streaminfo.num_channels
+            && (streaminfo.max_block_size == 0
+                || header.block_size <= streaminfo.max_block_size as u32)
+    }
+
+    /// Scans forward from the frame at `offset` for the frame containing the sample. Returns
+    /// `None` if the sample is not found, which happens if the frame at `offset` starts after the
+    /// sample or if the end of the stream is reached.
+    fn scan(&mut self, mut offset: u64, sample: u64) -> Result<Option<SamplePosition>> {
+        let mut window = WINDOW;
+        loop {
+            let data = self.read(offset, window)?;
+            let eof = offset + data.len() as u64 >= self.audio_length;
+            let frames: Vec<_> = Frames::new(&data[..]).collect();
+
+            for frame in frames.iter() {
+                let frame_sample = self.first_sample(&frame.header);
+                if frame_sample > sample {
+                    return Ok(None);
+                }
+                if sample < frame_sample + frame.header.block_size as u64 {
+                    let audio_offset = offset + frame.offset;
+                    return Ok(Some(SamplePosition {
+                        offset: audio_offset,
+                        audio_offset,
+                        frame_sample,
+                        sample_offset: (sample - frame_sample) as u32,
+                        header: frame.header,
+                    }));
+                }
+            }
+
+            if eof {
+                return Ok(None);
+            }
+
+            // the last frame may be cut off by the end of the window, so continue from it
+            match frames.last() {
+                Some(frame) if frames.len() > 1 => offset += frame.offset,
+                _ => window *= 2,
+            }
+        }
+    }
+
+    /// Returns the offset and first sample of the first frame at or after `offset`.
+    fn frame_after(&mut self, offset: u64) -> Result<Option<(u64, u64)>> {
+        let data = self.read(offset, 2 * WINDOW)?;
+        let eof = offset + data.len() as u64 >= self.audio_length;
+        let frames: Vec<_> = Frames::new(&data[..]).collect();
+
+        // a frame is only trusted if it is immediately followed by another frame or the end of
+        // the stream, which rules out most false sync codes
+        for (i, frame) in frames.iter().enumerate() {
+            let end = frame.offset + frame.length;
+            let followed = match frames.get(i + 1) {
+                Some(next) => next.offset == end,
+                None => eof && end == data.len() as u64,
+            };
+            if followed && self.is_consistent(&frame.header) {
+                return Ok(Some((
+                    offset + frame.offset,
+                    self.first_sample(&frame.header),
+                )));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Bisects the audio data for a frame at or shortly before the sample. Returns the offset of
+    /// the frame.
+    fn bisect(&mut self, sample: u64) -> Result<u64> {
+        let mut low = 0;
+        let mut high = self.audio_length;
+        while high - low > WINDOW {
+            let mid = low + (high - low) / 2;
+            match self.frame_after(mid)? {
+                Some((offset, frame_sample)) if offset < high && frame_sample <= sample => {
+                    low = offset;
+                }
+                _ => high = mid,
+            }
+        }
+
+        Ok(low)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{SeekPointSpec, SeekTable};
+    use crate::frame::tests::constant_frame;
+    use crate::frame::ChannelAssignment;
+
+    use std::io::Cursor;
+
+    /// Returns a stream of constant frames and the offset of its first frame.
+    fn stream(num_frames: u64, seektable: bool) -> (Vec<u8>, u64) {
+        let mut header = FrameHeader::new();
+        header.block_size = 1152;
+        header.channel_assignment = ChannelAssignment::Independent(2);
+
+        let mut audio = Vec::new();
+        for number in 0..num_frames {
+            header.number = number;
+            audio.extend(constant_frame(&header));
+        }
+
+        let mut streaminfo = StreamInfo::new();
+        streaminfo.min_block_size = 1152;
+        streaminfo.max_block_size = 1152;
+        streaminfo.sample_rate = 44100;
+        streaminfo.num_channels = 2;
+        streaminfo.bits_per_sample = 16;
+        streaminfo.total_samples = num_frames * 1152;
+        streaminfo.md5 = vec![0; 16];
+
+        let mut tag = Tag::new();
+        if seektable {
+            let specs = [SeekPointSpec::EverySeconds(10.0)];
+            let seektable = SeekTable::generate(&streaminfo, &audio[..], &specs).unwrap();
+            tag.push_block(Block::SeekTable(seektable));
+        }
+        tag.set_streaminfo(streaminfo);
+
+        let mut data = Vec::new();
+        tag.write_to(&mut data).unwrap();
+        let audio_start = data.len() as u64;
+        data.extend(audio);
+        (data, audio_start)
+    }
+
+    #[test]
+    fn find_sample_by_bisecting() {
+        let (data, audio_start) = stream(20_000, false);
+
+        let position = find_sample(&mut Cursor::new(&data[..]), 15_000 * 1152 + 100).unwrap();
+        assert_eq!(position.header.number, 15_000);
+        assert_eq!(position.frame_sample, 15_000 * 1152);
+        assert_eq!(position.sample_offset, 100);
+        assert_eq!(position.offset, audio_start + position.audio_offset);
+
+        let frame = constant_frame(&position.header);
+        let offset = position.offset as usize;
+        assert_eq!(&data[offset..offset + frame.len()], &frame[..]);
+
+        assert!(find_sample(&mut Cursor::new(&data[..]), 20_000 * 1152).is_err());
+    }
+
+    #[test]
+    fn find_time_with_seektable() {
+        let (data, audio_start) = stream(5_000, true);
+
+        let position = find_time(&mut Cursor::new(&data[..]), Duration::from_secs(61)).unwrap();
+        assert_eq!(position.frame_sample, 61 * 44100 / 1152 * 1152);
+        assert_eq!(position.sample_offset, (61 * 44100 % 1152) as u32);
+
+        let frame = constant_frame(&position.header);
+        let offset = (audio_start + position.audio_offset) as usize;
+        assert_eq!(&data[offset..offset + frame.len()], &frame[..]);
+
+        for &secs in [u64::MAX / 1000, u64::MAX].iter() {
+            let error = find_time(&mut Cursor::new(&data[..]), Duration::from_secs(secs));
+            assert_eq!(
+                error.unwrap_err().description,
+                "sample is past the end of the stream"
+            );
+        }
+    }
+}
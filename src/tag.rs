@@ -1,4 +1,7 @@
-use crate::block::{Block, BlockType, Blocks, Picture, PictureType, StreamInfo, VorbisComment};
+use crate::block::{
+    read_ident, Application, ApplicationPayload, Block, BlockRegistry, BlockType, Blocks,
+    FieldName, IdentifierField, IdentifierIssue, Picture, PictureType, StreamInfo, VorbisComment,
+};
 use crate::error::{Error, ErrorKind, Result};
 
 use byteorder::{BigEndian, ReadBytesExt};
@@ -169,6 +172,28 @@ impl<'a> Tag {
             .set(key.into().to_ascii_uppercase(), values);
     }
 
+    /// Sets the values for the specified vorbis comment key after checking that it is a valid
+    /// field name. The tag is not changed if the key is invalid. See `VorbisComment::try_set`.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::Tag;
+    ///
+    /// let mut tag = Tag::new();
+    /// assert!(tag.try_set_vorbis("title", vec!["Title"]).is_ok());
+    /// assert!(tag.try_set_vorbis("FOO=BAR", vec!["value"]).is_err());
+    /// assert_eq!(tag.get_vorbis("TITLE").unwrap().collect::<Vec<_>>(), &["Title"]);
+    /// ```
+    pub fn try_set_vorbis<K: Into<String>, V: Into<String>>(
+        &mut self,
+        key: K,
+        values: Vec<V>,
+    ) -> Result<()> {
+        let key = FieldName::new(key.into().to_ascii_uppercase())?;
+        self.vorbis_comments_mut().set(key, values);
+        Ok(())
+    }
+
     /// Removes the values for the specified vorbis comment key.
     ///
     /// # Example
@@ -297,6 +322,122 @@ impl<'a> Tag {
         });
     }
 
+    /// Moves the pictures stored in the vorbis comments into picture blocks, skipping pictures
+    /// identical to an existing picture block. Returns the number of pictures which were stored
+    /// in the vorbis comments. The tag is not changed if a picture could not be decoded. See
+    /// `VorbisComment::encoded_pictures`.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::Tag;
+    /// use metaflac::block::Picture;
+    ///
+    /// let mut tag = Tag::new();
+    /// tag.vorbis_comments_mut().push_encoded_picture(&Picture::new());
+    ///
+    /// assert_eq!(tag.normalize_pictures().unwrap(), 1);
+    /// assert_eq!(tag.pictures().count(), 1);
+    /// assert!(tag.get_vorbis("METADATA_BLOCK_PICTURE").is_none());
+    /// ```
+    pub fn normalize_pictures(&mut self) -> Result<usize> {
+        let pictures = match self.vorbis_comments() {
+            Some(comments) => comments.encoded_pictures()?,
+            None => return Ok(0),
+        };
+
+        self.vorbis_comments_mut().remove_encoded_pictures();
+        for picture in pictures.iter() {
+            if !self.pictures().any(|existing| existing == picture) {
+                self.push_block(Block::Picture(picture.clone()));
+            }
+        }
+        Ok(pictures.len())
+    }
+
+    /// Returns the problems with the identifier fields of each tag which has any, together with
+    /// the index of the tag. Tags without vorbis comments are missing all required fields. See
+    /// `VorbisComment::identifier_issues`.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::Tag;
+    /// use metaflac::block::{IdentifierField, IdentifierIssue};
+    ///
+    /// let mut tags = vec![Tag::new(), Tag::new()];
+    /// tags[0].set_vorbis("MUSICBRAINZ_TRACKID", vec!["5b11f4ce-a62d-471e-81fc-a69a8278c7da"]);
+    ///
+    /// let issues = Tag::identifier_issues(&tags, &[IdentifierField::TrackId]);
+    /// assert_eq!(
+    ///     issues,
+    ///     vec![(1, vec![IdentifierIssue::Missing(IdentifierField::TrackId)])]
+    /// );
+    /// ```
+    pub fn identifier_issues(
+        tags: &[Tag],
+        required: &[IdentifierField],
+    ) -> Vec<(usize, Vec<IdentifierIssue>)> {
+        let empty = VorbisComment::new();
+        tags.iter()
+            .map(|tag| {
+                tag.vorbis_comments()
+                    .unwrap_or(&empty)
+                    .identifier_issues(required)
+            })
+            .enumerate()
+            .filter(|(_, issues)| !issues.is_empty())
+            .collect()
+    }
+
+    /// Returns an iterator over the application blocks with the specified id.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::{Block, Tag};
+    /// use metaflac::block::Application;
+    ///
+    /// let mut tag = Tag::new();
+    /// let mut application = Application::new();
+    /// application.id = *b"xmcd";
+    /// tag.push_block(Block::Application(application));
+    ///
+    /// assert_eq!(tag.applications(b"xmcd").count(), 1);
+    /// assert_eq!(tag.applications(b"riff").count(), 0);
+    /// ```
+    pub fn applications(&'a self, id: &'a [u8; 4]) -> impl Iterator<Item = &'a Application> + 'a {
+        self.blocks.iter().filter_map(move |block| match *block {
+            Block::Application(ref application) if application.id == *id => Some(application),
+            _ => None,
+        })
+    }
+
+    /// Returns the parsed payloads of the application blocks with the id of the payload.
+    pub fn application_payloads<P: ApplicationPayload>(
+        &'a self,
+    ) -> impl Iterator<Item = Result<P>> + 'a {
+        self.applications(&P::ID)
+            .map(|application| P::from_bytes(&application.data[..]))
+    }
+
+    /// Returns the parsed payload of the first application block with the id of the payload.
+    /// Returns `None` if there is no such block. See `ApplicationPayload` for an example.
+    pub fn get_application_payload<P: ApplicationPayload>(&self) -> Option<Result<P>> {
+        self.application_payloads().next()
+    }
+
+    /// Replaces the application blocks with the id of the payload by a block holding the payload.
+    pub fn set_application_payload<P: ApplicationPayload>(&mut self, payload: &P) {
+        self.remove_applications(&P::ID);
+        self.push_block(Block::Application(Application::from_payload(payload)));
+    }
+
+    /// Removes the application blocks with the specified id.
+    pub fn remove_applications(&mut self, id: &[u8; 4]) {
+        self.blocks.retain(|block: &Block| match *block {
+            Block::Application(ref application) => application.id != *id,
+            _ => true,
+        });
+    }
+
     /// Returns a reference to the first streaminfo block.
     /// Returns `None` if no streaminfo blocks are found.
     ///
@@ -411,9 +552,15 @@ impl<'a> Tag {
 
     /// Attempts to read a FLAC tag from the reader.
     pub fn read_from(reader: &mut dyn Read) -> Result<Tag> {
+        Tag::read_from_with_registry(reader, &BlockRegistry::new())
+    }
+
+    /// Attempts to read a FLAC tag from the reader, parsing custom block types with the codecs of
+    /// the registry. See `BlockCodec` for an example.
+    pub fn read_from_with_registry(reader: &mut dyn Read, registry: &BlockRegistry) -> Result<Tag> {
         let mut tag = Tag::new();
 
-        for result in Blocks::new(reader) {
+        for result in Blocks::with_registry(reader, registry.clone()) {
             let (length, block) = result?;
             tag.length += length;
             tag.blocks.push(block);
@@ -422,15 +569,65 @@ impl<'a> Tag {
         Ok(tag)
     }
 
+    /// Attempts to read a FLAC tag from the reader, leaving out the vorbis comments with an
+    /// invalid field name or without a `=` separator, so that the tag can be written again.
+    /// Returns the tag and the comments which were left out.
+    ///
+    /// # Example
+    /// ```
+    /// use metaflac::Tag;
+    ///
+    /// let mut bytes = b"fLaC".to_vec();
+    /// let comment = b"\0\0\0\0\x01\0\0\0\x0a\0\0\0T\xc3\x84G=value";
+    /// bytes.extend(&[0x84, 0, 0, comment.len() as u8]);
+    /// bytes.extend(&comment[..]);
+    ///
+    /// let (mut tag, invalid) = Tag::read_from_lenient(&mut &bytes[..]).unwrap();
+    /// assert_eq!(invalid, vec!["T\u{C4}G=value"]);
+    /// assert!(tag.write_to(&mut Vec::new()).is_ok());
+    /// ```
+    pub fn read_from_lenient(reader: &mut dyn Read) -> Result<(Tag, Vec<String>)> {
+        read_ident(&mut *reader)?;
+
+        let registry = BlockRegistry::new();
+        let mut tag = Tag::new();
+        let mut invalid = Vec::new();
+        loop {
+            let (is_last, length, block) = Block::read(reader, &registry, Some(&mut invalid))?;
+            tag.length += length;
+            tag.blocks.push(block);
+            if is_last {
+                break;
+            }
+        }
+
+        Ok((tag, invalid))
+    }
+
     /// Attempts to write the FLAC tag to the writer.
+    ///
+    /// An `ErrorKind::InvalidFieldNames` error is returned if the vorbis comments contain keys
+    /// which are not valid field names, which `Tag::read_from` keeps when reading a file. Such
+    /// keys can be removed with `VorbisComment::remove_invalid_keys`, or left out when reading
+    /// with `Tag::read_from_lenient`.
     pub fn write_to(&mut self, writer: &mut dyn Write) -> Result<()> {
+        self.write_to_with_registry(writer, &BlockRegistry::new())
+    }
+
+    /// Attempts to write the FLAC tag to the writer, encoding custom blocks with the codecs of the
+    /// registry.
+    pub fn write_to_with_registry(
+        &mut self,
+        writer: &mut dyn Write,
+        registry: &BlockRegistry,
+    ) -> Result<()> {
         writer.write_all(b"fLaC")?;
 
         let nblocks = self.blocks.len();
         self.length = 0;
         for i in 0..nblocks {
             let block = &self.blocks[i];
-            self.length += block.write_to(i == nblocks - 1, writer)?;
+            self.length += block.write_to_with_registry(i == nblocks - 1, writer, registry)?;
         }
 
         Ok(())
@@ -438,8 +635,18 @@ impl<'a> Tag {
 
     /// Attempts to write the FLAC tag to a file at the indicated path. If the specified path is
     /// the same path which the tag was read from, then the tag will be written to the padding if
-    /// possible.
+    /// possible. See `Tag::write_to` for the error returned for invalid field names.
     pub fn write_to_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.write_to_path_with_registry(path, &BlockRegistry::new())
+    }
+
+    /// Attempts to write the FLAC tag to a file at the indicated path, encoding custom blocks with
+    /// the codecs of the registry. See `Tag::write_to_path`.
+    pub fn write_to_path_with_registry<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        registry: &BlockRegistry,
+    ) -> Result<()> {
         self.remove_blocks(BlockType::Padding);
 
         let mut block_bytes = Vec::new();
@@ -448,7 +655,7 @@ impl<'a> Tag {
         for i in 0..nblocks {
             let block = &self.blocks[i];
             let mut writer = Vec::<u8>::new();
-            new_length += block.write_to(false, &mut writer)?;
+            new_length += block.write_to_with_registry(false, &mut writer, registry)?;
             block_bytes.push(writer);
         }
 
@@ -509,12 +716,31 @@ impl<'a> Tag {
 
     /// Attempts to read a FLAC tag from the file at the specified path.
     pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Tag> {
+        Tag::read_from_path_with_registry(path, &BlockRegistry::new())
+    }
+
+    /// Attempts to read a FLAC tag from the file at the specified path, parsing custom block types
+    /// with the codecs of the registry.
+    pub fn read_from_path_with_registry<P: AsRef<Path>>(
+        path: P,
+        registry: &BlockRegistry,
+    ) -> Result<Tag> {
         let file = File::open(&path)?;
         let mut reader = BufReader::new(file);
-        let mut tag = Tag::read_from(&mut reader)?;
+        let mut tag = Tag::read_from_with_registry(&mut reader, registry)?;
         tag.path = Some(path.as_ref().to_path_buf());
         Ok(tag)
     }
+
+    /// Attempts to read a FLAC tag from the file at the specified path, leaving out invalid
+    /// vorbis comments. See `Tag::read_from_lenient`.
+    pub fn read_from_path_lenient<P: AsRef<Path>>(path: P) -> Result<(Tag, Vec<String>)> {
+        let file = File::open(&path)?;
+        let mut reader = BufReader::new(file);
+        let (mut tag, invalid) = Tag::read_from_lenient(&mut reader)?;
+        tag.path = Some(path.as_ref().to_path_buf());
+        Ok((tag, invalid))
+    }
 }
 
 impl Default for Tag {
@@ -546,6 +772,23 @@ mod tests {
         assert!(tag.get_vorbis("KEY").is_none());
     }
 
+    #[test]
+    fn invalid_field_names() {
+        let mut tag = Tag::new();
+        tag.vorbis_comments_mut()
+            .comments
+            .insert("T\u{C4}G".to_owned(), vec!["value".to_owned()]);
+
+        let mut bytes = Vec::new();
+        match tag.write_to(&mut bytes).unwrap_err().kind {
+            ErrorKind::InvalidFieldNames(keys) => assert_eq!(keys, vec!["T\u{C4}G"]),
+            kind => panic!("unexpected error kind {:?}", kind),
+        }
+
+        tag.vorbis_comments_mut().remove_invalid_keys();
+        tag.write_to(&mut Vec::new()).unwrap();
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn test_serde() {